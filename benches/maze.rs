@@ -0,0 +1,108 @@
+use bevy::{input::InputPlugin, prelude::*};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use maze_game::{
+    maze::Maze,
+    settings::Settings,
+    utils::vars::{GameState, MazeState},
+    MazeGamePlugin,
+};
+use std::time::{Duration, Instant};
+
+const SIZES: [usize; 4] = [11, 31, 101, 1000];
+
+fn generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate");
+
+    for size in SIZES {
+        group.throughput(Throughput::Elements((size * size) as u64));
+        group.bench_with_input(BenchmarkId::new("backtracker", size), &size, |b, &size| {
+            let mut seed = 0;
+            b.iter(|| {
+                seed += 1;
+                Maze::from_seed(size, (size + 9) / 4, seed)
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn solving(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve");
+
+    for size in SIZES {
+        let maze = Maze::from_seed(size, 0, 1);
+
+        group.throughput(Throughput::Elements((size * size) as u64));
+        group.bench_with_input(BenchmarkId::new("bfs", size), &maze, |b, maze| {
+            b.iter(|| maze.solve(maze.entrance(), maze.exit()));
+        });
+    }
+
+    group.finish();
+}
+
+fn serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+
+    for size in SIZES {
+        let maze = Maze::from_seed(size, (size + 9) / 4, 1);
+        let contents = ron::to_string(&maze).unwrap();
+
+        group.throughput(Throughput::Bytes(contents.len() as u64));
+        group.bench_with_input(BenchmarkId::new("to_ron", size), &maze, |b, maze| {
+            b.iter(|| ron::to_string(maze).unwrap());
+        });
+        group.bench_with_input(
+            BenchmarkId::new("from_ron", size),
+            &contents,
+            |b, contents| {
+                b.iter(|| ron::from_str::<Maze>(contents).unwrap());
+            },
+        );
+    }
+
+    group.finish();
+}
+
+// Times entering `GameState::Game`, which spawns the player, walls and coins for the maze
+fn spawning(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spawn");
+
+    for size in [11, 31, 101] {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_plugin(InputPlugin)
+            .add_plugin(MazeGamePlugin);
+        app.world.resource_mut::<Settings>().ghost = false;
+        app.update();
+
+        let maze = Maze::from_seed(size, (size + 9) / 4, 1);
+
+        group.bench_function(BenchmarkId::new("game_screen", size), |b| {
+            b.iter_custom(|iterations| {
+                let mut total = Duration::ZERO;
+
+                for _ in 0..iterations {
+                    let mut maze_state = app.world.resource_mut::<MazeState>();
+                    maze_state.size = size;
+                    maze_state.path = Some(maze.clone());
+
+                    let start = Instant::now();
+                    app.world.run_schedule(OnEnter(GameState::Game));
+                    total += start.elapsed();
+
+                    app.world.run_schedule(OnExit(GameState::Game));
+                }
+
+                total
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, generation, solving, serialization, spawning);
+criterion_main!(benches);
@@ -0,0 +1,173 @@
+// Plays the maze game in a terminal, for when there's no window to open. Give it a difficulty
+// or a size to play a new maze, or a maze from the editor as text or a PNG image:
+//
+//     cargo run --bin tui -- hard
+//     cargo run --bin tui -- maze.txt
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute, queue,
+    style::Print,
+    terminal::{self, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use maze_game::{
+    editor, import,
+    maze::{Direction, Maze},
+    settings::Settings,
+    tui::Run,
+    utils::vars::DataDir,
+};
+use rand::{thread_rng, Rng};
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::Path,
+    process,
+    time::Duration,
+};
+
+// Where a new run comes from
+enum Source {
+    Size(usize),
+    File(Maze),
+}
+
+impl Source {
+    fn parse(arg: &str) -> Result<Source, String> {
+        let size = match arg {
+            "easy" => Some(11),
+            "medium" => Some(21),
+            "hard" => Some(31),
+            _ => arg.parse().ok(),
+        };
+        if let Some(size) = size {
+            return match size {
+                1.. => Ok(Source::Size(size)),
+                _ => Err("The maze needs at least one cell".to_string()),
+            };
+        }
+
+        let path = Path::new(arg);
+        let maze = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("png") => import::load(path).map_err(|err| err.to_string())?,
+            _ => fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| Maze::from_text(&text).map_err(|err| err.to_string()))?,
+        };
+        editor::check(&maze)?;
+
+        Ok(Source::File(maze))
+    }
+
+    fn run(&self, settings: &Settings) -> Run {
+        match self {
+            Source::Size(size) => Run::generate(*size, thread_rng().gen(), settings),
+            Source::File(maze) => Run::new(maze.clone()),
+        }
+    }
+}
+
+fn main() {
+    let arg = env::args().nth(1).unwrap_or_else(|| "medium".to_string());
+    let source = match Source::parse(&arg) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Could not open {arg}: {err}");
+            process::exit(1);
+        }
+    };
+
+    let mut stdout = io::stdout();
+    let result = terminal::enable_raw_mode()
+        .and_then(|_| execute!(stdout, EnterAlternateScreen, cursor::Hide))
+        .and_then(|_| play(&mut stdout, &source));
+
+    // The terminal is put back whatever happened, so the error can be read
+    let _ = execute!(stdout, cursor::Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+
+    if let Err(err) = result {
+        eprintln!("Terminal error: {err}");
+        process::exit(1);
+    }
+}
+
+fn play(stdout: &mut io::Stdout, source: &Source) -> io::Result<()> {
+    let settings = Settings::load(&DataDir::default());
+    let mut run = source.run(&settings);
+    execute!(stdout, terminal::Clear(ClearType::All))?;
+
+    loop {
+        draw(stdout, &run)?;
+
+        // Waking up now and then keeps the clock going between keys
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        let direction = match key.code {
+            KeyCode::Up | KeyCode::Char('w') => Some(Direction::North),
+            KeyCode::Down | KeyCode::Char('s') => Some(Direction::South),
+            KeyCode::Left | KeyCode::Char('a') => Some(Direction::West),
+            KeyCode::Right | KeyCode::Char('d') => Some(Direction::East),
+            KeyCode::Char('h') => {
+                run.hint = !run.hint;
+                None
+            }
+            KeyCode::Char('n') => {
+                run = source.run(&settings);
+                execute!(stdout, terminal::Clear(ClearType::All))?;
+                None
+            }
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            _ => None,
+        };
+
+        if let Some(direction) = direction {
+            run.step(direction);
+        }
+    }
+}
+
+fn draw(stdout: &mut io::Stdout, run: &Run) -> io::Result<()> {
+    let coins = run.maze().coins();
+    let taken = coins - run.coins_left().len();
+    let status = match run.stars() {
+        Some(stars) => format!(
+            "Out in {:.2} s and {} steps, {stars} of 3 stars",
+            run.elapsed().as_secs_f32(),
+            run.moves()
+        ),
+        None => format!(
+            "Time: {:.1} s   Coins: {taken}/{coins}   Steps: {}",
+            run.elapsed().as_secs_f32(),
+            run.moves()
+        ),
+    };
+    let help = match run.finished() {
+        true => "n: new game   q: quit",
+        false => "Arrows or WASD: move   h: hint   n: new game   q: quit",
+    };
+
+    let lines = run
+        .render()
+        .into_iter()
+        .chain([String::new(), status, help.to_string()]);
+    for (row, line) in lines.enumerate() {
+        queue!(
+            stdout,
+            cursor::MoveTo(0, row as u16),
+            Print(line),
+            terminal::Clear(ClearType::UntilNewLine)
+        )?;
+    }
+
+    stdout.flush()
+}
@@ -0,0 +1,419 @@
+use crate::game::{
+    on_floor, read_input, terrain_at, Climber, Coin, EndGate, Floor, InputSet, Layout,
+    OnGameScreen, Player, Position, PowerUps, TerrainTile,
+};
+use crate::maze::{Direction, Maze};
+use crate::replay::InputState;
+use crate::settings::Settings;
+use crate::utils::{
+    colors::TEXT_COLOR,
+    vars::{GameState, MazeState, DEMO_DELAY, HEIGHT, MAZE_BORDER_WIDTH, PLAYER_SPEED, TIMESTEP},
+};
+use bevy::{prelude::*, sprite::Anchor};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+pub struct BotPlugin;
+
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DemoTimer(Timer::from_seconds(DEMO_DELAY, TimerMode::Once)))
+            .add_system(start_bot.in_schedule(OnEnter(GameState::Game)))
+            .add_systems((start_bot, demo_setup).in_schedule(OnEnter(GameState::Demo)))
+            .add_system(stop_bot.in_schedule(OnExit(GameState::Game)))
+            .add_system(stop_bot.in_schedule(OnExit(GameState::Demo)))
+            .add_system(
+                drive_bot
+                    .in_set(InputSet)
+                    .before(read_input)
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .run_if(resource_exists::<Bot>()),
+            )
+            .add_system(reset_demo_timer.in_schedule(OnEnter(GameState::Menu)))
+            .add_system(start_demo.in_set(OnUpdate(GameState::Menu)))
+            .add_system(leave_demo.in_set(OnUpdate(GameState::Demo)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Strategy {
+    Optimal,
+    WallFollower,
+    RandomWalk,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 3] = [
+        Strategy::Optimal,
+        Strategy::WallFollower,
+        Strategy::RandomWalk,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Strategy::Optimal => "Optimal",
+            Strategy::WallFollower => "Wall follower",
+            Strategy::RandomWalk => "Random walk",
+        }
+    }
+}
+
+// Plays the game by holding the same keys a player would. It walks from cell centre to
+// cell centre and only decides where to go next once it gets there.
+#[derive(Resource)]
+pub struct Bot {
+    strategy: Strategy,
+    waypoints: VecDeque<Vec3>,
+    cell: Option<(usize, usize)>,
+    facing: Direction,
+    planned: bool,
+    finished: bool,
+    rng: StdRng,
+}
+
+impl Bot {
+    pub fn new(strategy: Strategy) -> Bot {
+        Bot {
+            strategy,
+            waypoints: VecDeque::new(),
+            cell: None,
+            facing: Direction::East,
+            planned: false,
+            finished: false,
+            rng: StdRng::from_rng(thread_rng()).unwrap(),
+        }
+    }
+
+    // Starts over from `cell`, or from the start pad, after losing a life
+    pub fn respawn(&mut self, cell: Option<(usize, usize)>) {
+        self.waypoints.clear();
+        self.cell = cell;
+        self.planned = false;
+        self.finished = false;
+    }
+
+    // Queues up the next waypoints, heading out through the exit once the gate is open
+    fn plan(&mut self, maze: &Maze, coins: &[(usize, usize)], gate_open: bool) {
+        let layout = Layout::new(maze);
+        let translations = |points: Vec<Vec2>| points.into_iter().map(|point| point.extend(1.));
+
+        let current = match self.cell {
+            Some(cell) => cell,
+            None => {
+                let (x, y) = maze.entrance();
+                self.cell = Some((x, y));
+                self.facing = maze.entrance_side().opposite();
+                self.waypoints.extend(translations(layout.straight_route(
+                    layout.start_translation().truncate(),
+                    layout.cell_center(x, y),
+                )));
+                return;
+            }
+        };
+
+        let exit = maze.exit();
+        let can_leave = |cell: (usize, usize), dir: Direction| {
+            gate_open && cell == exit && dir == maze.exit_side()
+        };
+
+        let next = match self.strategy {
+            Strategy::Optimal => {
+                if !self.planned {
+                    self.planned = true;
+
+                    for step in plan_route(maze, current, exit, coins).windows(2) {
+                        self.waypoints
+                            .extend(translations(layout.route(step[0], step[1])));
+                    }
+
+                    self.cell = Some(exit);
+                    return;
+                }
+
+                Some(maze.exit_side()).filter(|dir| can_leave(exit, *dir))
+            }
+            Strategy::WallFollower => maze.grid().right_hand_turns(self.facing).find(|&dir| {
+                maze.ways(current.0, current.1).any(|way| way == dir)
+                    && (maze.passage(current.0, current.1, dir).is_some()
+                        || can_leave(current, dir))
+            }),
+            Strategy::RandomWalk => {
+                if can_leave(current, maze.exit_side()) {
+                    Some(maze.exit_side())
+                } else {
+                    let options: Vec<Direction> = maze
+                        .ways(current.0, current.1)
+                        .filter(|&dir| maze.passage(current.0, current.1, dir).is_some())
+                        .collect();
+                    let forward: Vec<Direction> = options
+                        .iter()
+                        .copied()
+                        .filter(|&dir| dir != self.facing.opposite())
+                        .collect();
+
+                    if forward.is_empty() {
+                        options.choose(&mut self.rng).copied()
+                    } else {
+                        forward.choose(&mut self.rng).copied()
+                    }
+                }
+            }
+        };
+
+        let dir = match next {
+            Some(dir) => dir,
+            None => return,
+        };
+
+        self.facing = dir;
+
+        match maze.passage(current.0, current.1, dir) {
+            Some(cell) => {
+                self.cell = Some(cell);
+                self.waypoints
+                    .extend(translations(layout.route(current, cell)));
+            }
+            None => {
+                self.finished = true;
+                self.waypoints.extend(translations(layout.straight_route(
+                    layout.cell_center(current.0, current.1),
+                    layout.end_translation().truncate(),
+                )));
+            }
+        }
+    }
+}
+
+// The shortest walk from `from` that passes every coin and ends at `to`. In a perfect maze
+// that means walking each branch holding a coin there and back, and saving the branch that
+// leads to `to` for last.
+pub fn plan_route(
+    maze: &Maze,
+    from: (usize, usize),
+    to: (usize, usize),
+    coins: &[(usize, usize)],
+) -> Vec<(usize, usize)> {
+    let width = maze.width();
+    let index = |(x, y): (usize, usize)| y * width + x;
+    let cells = width * maze.height();
+
+    let mut parent = vec![usize::MAX; cells];
+    let mut order = vec![index(from)];
+    parent[index(from)] = index(from);
+    let mut i = 0;
+
+    while i < order.len() {
+        let current = order[i];
+
+        for next in maze.neighbours(current % width, current / width) {
+            if parent[index(next)] == usize::MAX {
+                parent[index(next)] = current;
+                order.push(index(next));
+            }
+        }
+
+        i += 1;
+    }
+
+    let mut needed = vec![false; cells];
+    let mut leads_to_end = vec![false; cells];
+
+    for &coin in coins {
+        needed[index(coin)] = true;
+    }
+    needed[index(to)] = true;
+    leads_to_end[index(to)] = true;
+
+    for &cell in order.iter().skip(1).rev() {
+        needed[parent[cell]] |= needed[cell];
+        leads_to_end[parent[cell]] |= leads_to_end[cell];
+    }
+
+    let mut children = vec![Vec::new(); cells];
+    for &cell in order.iter().skip(1) {
+        if needed[cell] {
+            children[parent[cell]].push(cell);
+        }
+    }
+
+    for list in &mut children {
+        list.sort_by_key(|&cell| leads_to_end[cell]);
+    }
+
+    let mut route = vec![from];
+    let mut stack = vec![(index(from), 0)];
+
+    while let Some((cell, next_child)) = stack.pop() {
+        match children[cell].get(next_child) {
+            Some(&child) => {
+                stack.push((cell, next_child + 1));
+                stack.push((child, 0));
+                route.push((child % width, child / width));
+            }
+            None => {
+                if let Some(&(parent, _)) = stack.last() {
+                    if !leads_to_end[cell] {
+                        route.push((parent % width, parent / width));
+                    }
+                }
+            }
+        }
+    }
+
+    route
+}
+
+#[derive(Resource)]
+struct DemoTimer(Timer);
+
+#[derive(Component)]
+struct DemoBanner;
+
+fn start_bot(mut commands: Commands, settings: Res<Settings>, state: Res<State<GameState>>) {
+    match (state.0, settings.bot) {
+        (GameState::Game, Some(strategy)) => commands.insert_resource(Bot::new(strategy)),
+        (GameState::Demo, strategy) => {
+            commands.insert_resource(Bot::new(strategy.unwrap_or(Strategy::Optimal)))
+        }
+        _ => {}
+    }
+}
+
+fn stop_bot(mut commands: Commands) {
+    commands.remove_resource::<Bot>();
+}
+
+pub fn drive_bot(
+    mut bot: ResMut<Bot>,
+    mut input: ResMut<InputState>,
+    maze_state: Res<MazeState>,
+    player_query: Query<(&Position, &Transform, &Climber, &PowerUps), With<Player>>,
+    coin_query: Query<&Coin>,
+    end_gate_query: Query<&EndGate>,
+    tile_query: Query<((&Transform, &TerrainTile), &Floor), Without<Player>>,
+) {
+    *input = InputState::default();
+
+    let maze = match &maze_state.path {
+        Some(maze) => maze,
+        None => return,
+    };
+
+    let (position, transform, climber, power_ups) = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    if maze_state.stopwatch.paused() {
+        return;
+    }
+
+    // How far the player moves in one tick along each axis, on the terrain it is on and
+    // with any speed boost it has
+    let terrain = terrain_at(
+        position.current,
+        on_floor(&tile_query, Floor(climber.floor)),
+    );
+    let step = TIMESTEP * PLAYER_SPEED * transform.scale.x * terrain.speed() * power_ups.speed();
+    // Close enough to a waypoint to count as there. A bit over half a step, so that a
+    // waypoint halfway between two steps can't be overshot back and forth.
+    let reach = step * 0.6;
+
+    loop {
+        if bot.waypoints.is_empty() && !bot.finished {
+            let coins: Vec<(usize, usize)> =
+                coin_query.iter().map(|coin| (coin.x, coin.y)).collect();
+            bot.plan(maze, &coins, end_gate_query.is_empty());
+        }
+
+        let target = match bot.waypoints.front() {
+            Some(target) => *target,
+            None => return,
+        };
+
+        let delta = (target - position.current).truncate();
+
+        if delta.x.abs() > reach || delta.y.abs() > reach {
+            let push = Vec2::new(
+                if delta.x.abs() > reach { delta.x } else { 0. },
+                if delta.y.abs() > reach { delta.y } else { 0. },
+            );
+
+            *input = InputState::from_direction(push);
+            return;
+        }
+
+        bot.waypoints.pop_front();
+    }
+}
+
+fn reset_demo_timer(mut demo_timer: ResMut<DemoTimer>) {
+    demo_timer.0.reset();
+}
+
+// Shows the bot playing a random maze after the menu has been left alone for a while
+fn start_demo(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut demo_timer: ResMut<DemoTimer>,
+    mut maze_state: ResMut<MazeState>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.get_just_pressed().next().is_some()
+        || mouse_input.get_just_pressed().next().is_some()
+    {
+        demo_timer.0.reset();
+    }
+
+    if demo_timer.0.tick(time.delta()).just_finished() {
+        maze_state.size = *[11, 21, 31].choose(&mut thread_rng()).unwrap();
+        maze_state.stopwatch.reset();
+        maze_state.stopwatch.unpause();
+        maze_state.ticks = 0;
+        maze_state.path = None;
+        maze_state.saved = None;
+        game_state.set(GameState::Demo);
+    }
+}
+
+fn demo_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "Demo - press any key",
+                TextStyle {
+                    font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                    font_size: 20.,
+                    color: TEXT_COLOR,
+                },
+            ),
+            transform: Transform::from_translation(Vec3::new(
+                0.,
+                -HEIGHT / 2. + MAZE_BORDER_WIDTH,
+                2.,
+            )),
+            text_anchor: Anchor::BottomCenter,
+            ..default()
+        },
+        DemoBanner,
+        OnGameScreen,
+    ));
+}
+
+fn leave_demo(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    maze_state: Res<MazeState>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.get_just_pressed().next().is_some()
+        || mouse_input.get_just_pressed().next().is_some()
+        || maze_state.stopwatch.paused()
+    {
+        game_state.set(GameState::Menu);
+    }
+}
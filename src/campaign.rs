@@ -0,0 +1,399 @@
+use crate::bot::plan_route;
+use crate::game::coins_for;
+use crate::mask::Mask;
+use crate::maze::{Generator, Grid, Hazards, Maze};
+use crate::utils::{
+    colors::{
+        BACKGROUND_COLOR, HOVERED_BUTTON, LOCKED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON, TEXT_COLOR,
+    },
+    despawn_screen,
+    vars::{DataDir, GameState, MazeState, CAMPAIGN_FILE, PLAYER_SPEED},
+    ButtonInteractions,
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        let progress = Progress::load(&app.world.get_resource_or_insert_with(DataDir::default));
+
+        app.insert_resource(progress)
+            .add_system(level_select_setup.in_schedule(OnEnter(GameState::LevelSelect)))
+            .add_system(button_system.in_set(OnUpdate(GameState::LevelSelect)))
+            .add_system(
+                despawn_screen::<OnLevelSelectScreen>.in_schedule(OnExit(GameState::LevelSelect)),
+            )
+            .add_system(finish_level.in_schedule(OnEnter(GameState::EndGame)))
+            .add_system(leave_campaign.in_schedule(OnEnter(GameState::Menu)));
+    }
+}
+
+// A campaign level is always the same maze, so times on it can be compared. Some are shaped
+// by hand, the rest are picked seeds, and each one brings in a little more.
+#[derive(Debug, Clone, Copy)]
+pub struct Level {
+    pub name: &'static str,
+    pub grid: Grid,
+    pub floors: usize,
+    pub size: usize,
+    pub shape: Option<&'static str>,
+    pub terrain: bool,
+    pub hazards: Hazards,
+    pub power_ups: bool,
+    pub seed: u64,
+}
+
+impl Level {
+    const fn new(name: &'static str, grid: Grid, size: usize, seed: u64) -> Level {
+        Level {
+            name,
+            grid,
+            floors: 1,
+            size,
+            shape: None,
+            terrain: false,
+            hazards: Hazards::Off,
+            power_ups: false,
+            seed,
+        }
+    }
+
+    pub fn maze(&self) -> Maze {
+        let generator = match self.shape {
+            Some(text) => {
+                let mask = Mask::from_text(text).expect("level shapes have cells");
+                Generator::shaped(self.grid, self.floors, &mask.fit(self.size), self.seed)
+            }
+            None => Generator::new(self.grid, self.floors, self.size, self.seed),
+        };
+
+        generator
+            .with_terrain(self.terrain)
+            .with_hazards(self.hazards)
+            .with_power_ups(self.power_ups)
+            .finish(coins_for(self.size))
+    }
+}
+
+const HEART: &str = include_str!("../assets/masks/heart.txt");
+
+const CROSS: &str = "
+   #####
+   #####
+   #####
+#############
+#############
+#############
+   #####
+   #####
+   #####
+";
+
+pub const LEVELS: [Level; 12] = [
+    Level::new("First steps", Grid::Square, 5, 1),
+    Level::new("Left and right", Grid::Square, 7, 7),
+    Level::new("Honeycomb", Grid::Hex, 9, 12),
+    Level {
+        shape: Some(HEART),
+        ..Level::new("Heart", Grid::Square, 9, 3)
+    },
+    Level {
+        terrain: true,
+        ..Level::new("Mud season", Grid::Square, 11, 21)
+    },
+    Level::new("Round and round", Grid::Polar, 11, 5),
+    Level {
+        floors: 2,
+        ..Level::new("Upstairs", Grid::Square, 11, 8)
+    },
+    Level {
+        hazards: Hazards::OffPath,
+        ..Level::new("Watch your step", Grid::Square, 13, 44)
+    },
+    Level::new("Over and under", Grid::Weave, 13, 9),
+    Level {
+        power_ups: true,
+        ..Level::new("Shortcuts", Grid::Square, 15, 16)
+    },
+    Level {
+        shape: Some(CROSS),
+        terrain: true,
+        hazards: Hazards::OffPath,
+        ..Level::new("Crossroads", Grid::Weave, 17, 27)
+    },
+    Level {
+        floors: 2,
+        terrain: true,
+        hazards: Hazards::OnPath,
+        power_ups: true,
+        ..Level::new("The long way", Grid::Weave, 21, 33)
+    },
+];
+
+// The level being played, while playing one
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct CurrentLevel(pub usize);
+
+// The most stars won on each level so far. Finishing a level unlocks the next.
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Progress {
+    pub stars: Vec<u8>,
+}
+
+impl Progress {
+    pub fn load(data: &DataDir) -> Progress {
+        fs::read_to_string(data.path(CAMPAIGN_FILE))
+            .ok()
+            .and_then(|contents| ron::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn write(&self, data: &DataDir) {
+        let result = ron::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                fs::write(data.path(CAMPAIGN_FILE), contents).map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            warn!("Could not write campaign file {CAMPAIGN_FILE}: {err}");
+        }
+    }
+
+    pub fn stars(&self, level: usize) -> u8 {
+        self.stars.get(level).copied().unwrap_or(0)
+    }
+
+    pub fn unlocked(&self, level: usize) -> bool {
+        level == 0 || self.stars(level - 1) > 0
+    }
+
+    pub fn record(&mut self, level: usize, stars: u8) {
+        if self.stars.len() <= level {
+            self.stars.resize(level + 1, 0);
+        }
+        self.stars[level] = self.stars[level].max(stars);
+    }
+}
+
+// How long walking out through every coin takes without a wrong turn, in seconds. The
+// player crosses a plain cell in 2 / `PLAYER_SPEED` seconds, and the pads add about one
+// cell at either end.
+pub fn par_time(maze: &Maze) -> f32 {
+    let coins: Vec<(usize, usize)> = maze
+        .rows()
+        .enumerate()
+        .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, cell)| (x, y, *cell)))
+        .filter(|(_, _, cell)| cell.coin())
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    let route = plan_route(maze, maze.entrance(), maze.exit(), &coins);
+    let cells = maze.path_cost(&route) as f32 / 2. + 2.;
+
+    cells * 2. / PLAYER_SPEED
+}
+
+// One star for getting out, two for doing it within twice the par time and three for taking
+// no more than half as long again as par
+pub fn stars(maze: &Maze, seconds: f32) -> u8 {
+    let par = par_time(maze);
+
+    if seconds <= par * 1.5 {
+        3
+    } else if seconds <= par * 2. {
+        2
+    } else {
+        1
+    }
+}
+
+fn finish_level(
+    level: Option<Res<CurrentLevel>>,
+    maze_state: Res<MazeState>,
+    mut progress: ResMut<Progress>,
+    data: Res<DataDir>,
+) {
+    let (Some(level), Some(maze)) = (level, &maze_state.path) else {
+        return;
+    };
+
+    progress.record(level.0, stars(maze, maze_state.stopwatch.elapsed_secs()));
+    progress.write(&data);
+}
+
+fn leave_campaign(mut commands: Commands) {
+    commands.remove_resource::<CurrentLevel>();
+}
+
+#[derive(Component)]
+struct OnLevelSelectScreen;
+
+#[derive(Component)]
+enum ButtonAction {
+    Play(usize),
+    Back,
+}
+
+fn button_system(
+    mut commands: Commands,
+    mut interaction_query: ButtonInteractions<ButtonAction>,
+    mut maze_state: ResMut<MazeState>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, mut color, button_action) in &mut interaction_query {
+        *color = match *interaction {
+            Interaction::Clicked => PRESSED_BUTTON.into(),
+            Interaction::Hovered => HOVERED_BUTTON.into(),
+            Interaction::None => NORMAL_BUTTON.into(),
+        };
+
+        if *interaction == Interaction::Clicked {
+            match button_action {
+                ButtonAction::Play(level) => {
+                    maze_state.size = LEVELS[*level].size;
+                    maze_state.stopwatch.reset();
+                    maze_state.stopwatch.unpause();
+                    maze_state.ticks = 0;
+                    maze_state.path = Some(LEVELS[*level].maze());
+                    maze_state.saved = None;
+                    commands.insert_resource(CurrentLevel(*level));
+                    game_state.set(GameState::Game);
+                }
+                ButtonAction::Back => game_state.set(GameState::Menu),
+            }
+        }
+    }
+}
+
+fn stars_label(progress: &Progress, level: usize) -> String {
+    match (progress.unlocked(level), progress.stars(level)) {
+        (false, _) => "Locked".to_string(),
+        (true, 0) => "New".to_string(),
+        (true, stars) => format!("Stars {stars}/3"),
+    }
+}
+
+fn level_select_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    progress: Res<Progress>,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: BACKGROUND_COLOR.into(),
+                ..default()
+            },
+            OnLevelSelectScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Campaign",
+                        TextStyle {
+                            font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                            font_size: 60.,
+                            color: TEXT_COLOR,
+                        },
+                    ));
+
+                    // Four levels to a row
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                size: Size::width(Val::Px(960.)),
+                                flex_wrap: FlexWrap::Wrap,
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for (i, level) in LEVELS.iter().enumerate() {
+                                let unlocked = progress.unlocked(i);
+                                let mut button = parent.spawn(ButtonBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Px(220.), Val::Px(90.)),
+                                        margin: UiRect::all(Val::Px(10.0)),
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        flex_direction: FlexDirection::Column,
+                                        ..default()
+                                    },
+                                    background_color: if unlocked {
+                                        NORMAL_BUTTON.into()
+                                    } else {
+                                        LOCKED_BUTTON.into()
+                                    },
+                                    ..default()
+                                });
+
+                                if unlocked {
+                                    button.insert(ButtonAction::Play(i));
+                                }
+
+                                button.with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(
+                                        format!("{}. {}", i + 1, level.name),
+                                        TextStyle {
+                                            font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                            font_size: 20.,
+                                            color: TEXT_COLOR,
+                                        },
+                                    ));
+                                    parent.spawn(TextBundle::from_section(
+                                        stars_label(&progress, i),
+                                        TextStyle {
+                                            font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                            font_size: 20.,
+                                            color: TEXT_COLOR,
+                                        },
+                                    ));
+                                });
+                            }
+                        });
+
+                    parent
+                        .spawn(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(300.), Val::Px(50.)),
+                                margin: UiRect::all(Val::Px(20.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        })
+                        .insert(ButtonAction::Back)
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                "Back",
+                                TextStyle {
+                                    font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                    font_size: 30.,
+                                    color: TEXT_COLOR,
+                                },
+                            ));
+                        });
+                });
+        });
+}
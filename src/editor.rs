@@ -0,0 +1,518 @@
+use crate::game::Layout;
+use crate::import;
+use crate::maze::{Direction, Maze};
+use crate::solver::Algorithm;
+use crate::utils::{
+    colors::{
+        HOVERED_BUTTON, NORMAL_BUTTON, OPEN_WALL_COLOR, PRESSED_BUTTON, TEXT_COLOR, WALL_COLOR,
+    },
+    despawn_screen,
+    vars::{DataDir, GameState, MazeState, IMPORT_FILE, MAZE_BORDER_WIDTH, MAZE_FILE},
+    ButtonInteractions,
+};
+use bevy::{prelude::*, window::PrimaryWindow};
+use std::fs;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Editor>()
+            .add_event::<EditorClick>()
+            .add_systems((editor_setup, draw_maze).in_schedule(OnEnter(GameState::Editor)))
+            .add_systems(
+                (
+                    click_maze,
+                    edit_maze,
+                    button_system,
+                    draw_maze.run_if(resource_changed::<Editor>()),
+                    status_text,
+                )
+                    .chain()
+                    .in_set(OnUpdate(GameState::Editor)),
+            )
+            .add_system(despawn_screen::<OnEditorScreen>.in_schedule(OnExit(GameState::Editor)))
+            .add_system(stop_play_test.in_schedule(OnEnter(GameState::Editor)))
+            .add_system(stop_play_test.in_schedule(OnEnter(GameState::Menu)));
+    }
+}
+
+// The maze is drawn a little smaller than in the game and off to the left, leaving room for
+// the buttons on the right
+const MAZE_SCALE: f32 = 0.8;
+const MAZE_OFFSET: Vec2 = Vec2::new(-110., 0.);
+const MIN_SIZE: usize = 7;
+const MAX_SIZE: usize = 31;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Walls,
+    Coins,
+    Entrance,
+    Exit,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 4] = [Tool::Walls, Tool::Coins, Tool::Entrance, Tool::Exit];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Walls => "Walls",
+            Tool::Coins => "Coins",
+            Tool::Entrance => "Entrance",
+            Tool::Exit => "Exit",
+        }
+    }
+
+    fn hint(&self) -> &'static str {
+        match self {
+            Tool::Walls => "Click between two cells to put up or take down the wall",
+            Tool::Coins => "Click a cell to put down or pick up a coin",
+            Tool::Entrance => "Click the outside wall of a cell to put the entrance there",
+            Tool::Exit => "Click the outside wall of a cell to put the exit there",
+        }
+    }
+}
+
+// The maze being drawn, which is kept while it is play-tested and until the game is closed
+#[derive(Resource)]
+pub struct Editor {
+    pub maze: Maze,
+    pub tool: Tool,
+    pub status: String,
+}
+
+impl Default for Editor {
+    fn default() -> Editor {
+        Editor {
+            maze: Maze::blank(11),
+            tool: Tool::Walls,
+            status: Tool::Walls.hint().to_string(),
+        }
+    }
+}
+
+// A click on the maze, in the same coordinates `Layout` puts its cells in
+pub struct EditorClick(pub Vec2);
+
+// Set while a maze from the editor is being played, so that leaving it goes back there
+#[derive(Resource)]
+pub struct PlayTest;
+
+#[derive(Component)]
+struct OnEditorScreen;
+
+#[derive(Component)]
+struct EditorMaze;
+
+#[derive(Component)]
+struct StatusText;
+
+#[derive(Component)]
+enum ButtonAction {
+    Tool(Tool),
+    Check,
+    PlayTest,
+    Save,
+    Load,
+    Import,
+    Resize(isize),
+    Clear,
+    Back,
+}
+
+// Whether the maze can be finished, which needs the solver to find a way from the entrance
+// to the exit and to every coin. Gives the length of the way out.
+pub fn check(maze: &Maze) -> Result<usize, String> {
+    let solve = |to: (usize, usize)| Algorithm::AStar.solve(maze, maze.entrance(), to).path;
+    let path = solve(maze.exit()).ok_or("There is no way from the entrance to the exit")?;
+    let stranded = (0..maze.height())
+        .flat_map(|y| (0..maze.width()).map(move |x| (x, y)))
+        .filter(|&(x, y)| maze.cell(x, y).coin() && solve((x, y)).is_none())
+        .count();
+
+    match stranded {
+        0 => Ok(path.len()),
+        stranded => Err(format!("{stranded} of the coins can't be reached")),
+    }
+}
+
+// The cell a point is in and the side of it the point is closest to
+pub fn clicked_side(maze: &Maze, point: Vec2) -> Option<((usize, usize), Direction)> {
+    let layout = Layout::new(maze);
+    let half = layout.coord_size / 2. + MAZE_BORDER_WIDTH / 2.;
+
+    (0..maze.height())
+        .flat_map(|y| (0..maze.width()).map(move |x| (x, y)))
+        .find_map(|(x, y)| {
+            let offset = point - layout.cell_center(x, y);
+
+            (offset.x.abs() <= half && offset.y.abs() <= half).then(|| {
+                let side = match offset.x.abs() > offset.y.abs() {
+                    true if offset.x > 0. => Direction::East,
+                    true => Direction::West,
+                    false if offset.y > 0. => Direction::North,
+                    false => Direction::South,
+                };
+
+                ((x, y), side)
+            })
+        })
+}
+
+// Uses `tool` on the `side` of (x, y), saying what came of it
+fn edit(maze: &mut Maze, tool: Tool, (x, y): (usize, usize), side: Direction) -> String {
+    let outside = maze.neighbour(x, y, side).is_none();
+
+    match tool {
+        Tool::Walls if outside => "The outside wall stays up, besides the doors".to_string(),
+        Tool::Walls => {
+            let wall = maze.cell(x, y).is_open(side);
+            maze.set_wall(x, y, side, wall);
+
+            match wall {
+                true => "Put up a wall".to_string(),
+                false => "Took down a wall".to_string(),
+            }
+        }
+        Tool::Coins => {
+            let coin = !maze.cell(x, y).coin();
+            maze.cell_mut(x, y).set_coin(coin);
+
+            format!("{} coins", maze.coins())
+        }
+        Tool::Entrance | Tool::Exit if !outside => "Doors go in the outside wall".to_string(),
+        Tool::Entrance | Tool::Exit => {
+            let exit = tool == Tool::Exit;
+            let other = match exit {
+                true => (maze.entrance(), maze.entrance_side()),
+                false => (maze.exit(), maze.exit_side()),
+            };
+
+            if other == ((x, y), side) {
+                return "The entrance and exit can't share a door".to_string();
+            }
+
+            maze.set_door(exit, (x, y), side);
+            format!("Moved the {}", tool.name().to_lowercase())
+        }
+    }
+}
+
+fn stop_play_test(mut commands: Commands) {
+    commands.remove_resource::<PlayTest>();
+}
+
+// Turns left clicks in the window into clicks on the maze
+fn click_maze(
+    mouse_input: Res<Input<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut clicks: EventWriter<EditorClick>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    if let Some(cursor) = window.cursor_position() {
+        let point = cursor - Vec2::new(window.width(), window.height()) / 2.;
+        clicks.send(EditorClick((point - MAZE_OFFSET) / MAZE_SCALE));
+    }
+}
+
+fn edit_maze(mut clicks: EventReader<EditorClick>, mut editor: ResMut<Editor>) {
+    for click in clicks.iter() {
+        if let Some((cell, side)) = clicked_side(&editor.maze, click.0) {
+            let editor = &mut *editor;
+            editor.status = edit(&mut editor.maze, editor.tool, cell, side);
+        }
+    }
+}
+
+fn button_system(
+    mut commands: Commands,
+    mut interaction_query: ButtonInteractions<ButtonAction>,
+    mut editor: ResMut<Editor>,
+    mut maze_state: ResMut<MazeState>,
+    mut game_state: ResMut<NextState<GameState>>,
+    data: Res<DataDir>,
+) {
+    for (interaction, mut color, button_action) in &mut interaction_query {
+        *color = match *interaction {
+            Interaction::Clicked => PRESSED_BUTTON.into(),
+            Interaction::Hovered => HOVERED_BUTTON.into(),
+            Interaction::None => NORMAL_BUTTON.into(),
+        };
+
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button_action {
+            ButtonAction::Tool(tool) => {
+                editor.tool = *tool;
+                editor.status = tool.hint().to_string();
+            }
+            ButtonAction::Check => {
+                editor.status = match check(&editor.maze) {
+                    Ok(steps) => format!("Solvable in {steps} steps"),
+                    Err(err) => err,
+                };
+            }
+            ButtonAction::PlayTest => match check(&editor.maze) {
+                Ok(_) => {
+                    let maze = editor.maze.clone();
+
+                    maze_state.size = maze.width().max(maze.height());
+                    maze_state.grid = maze.grid();
+                    maze_state.floors = maze.floors();
+                    maze_state.coins = maze.coins();
+                    maze_state.stopwatch.reset();
+                    maze_state.stopwatch.unpause();
+                    maze_state.ticks = 0;
+                    maze_state.path = Some(maze);
+                    maze_state.saved = None;
+                    commands.insert_resource(PlayTest);
+                    game_state.set(GameState::Game);
+                }
+                Err(err) => editor.status = err,
+            },
+            ButtonAction::Save => {
+                editor.status = match fs::write(data.path(MAZE_FILE), editor.maze.to_text()) {
+                    Ok(()) => format!("Saved to {MAZE_FILE}"),
+                    Err(err) => format!("Could not save {MAZE_FILE}: {err}"),
+                };
+            }
+            ButtonAction::Load => {
+                let loaded = fs::read_to_string(data.path(MAZE_FILE))
+                    .map_err(|err| err.to_string())
+                    .and_then(|text| Maze::from_text(&text).map_err(|err| err.to_string()));
+
+                editor.status = match loaded {
+                    Ok(maze) => {
+                        editor.maze = maze;
+                        format!("Loaded {MAZE_FILE}")
+                    }
+                    Err(err) => format!("Could not load {MAZE_FILE}: {err}"),
+                };
+            }
+            ButtonAction::Import => {
+                editor.status = match import::load(&data.path(IMPORT_FILE)) {
+                    Ok(maze) => {
+                        editor.maze = maze;
+                        format!("Imported {IMPORT_FILE}")
+                    }
+                    Err(err) => format!("Could not import {IMPORT_FILE}: {err}"),
+                };
+            }
+            ButtonAction::Resize(by) => {
+                let size = editor
+                    .maze
+                    .width()
+                    .saturating_add_signed(*by)
+                    .clamp(MIN_SIZE, MAX_SIZE);
+
+                editor.maze = Maze::blank(size);
+                editor.status = format!("Started over at {size} x {size}");
+            }
+            ButtonAction::Clear => {
+                editor.maze = Maze::blank(editor.maze.width());
+                editor.status = "Started over".to_string();
+            }
+            ButtonAction::Back => game_state.set(GameState::Menu),
+        }
+    }
+}
+
+fn status_text(editor: Res<Editor>, mut text_query: Query<&mut Text, With<StatusText>>) {
+    for mut text in &mut text_query {
+        if text.sections[0].value != editor.status {
+            text.sections[0].value = editor.status.clone();
+        }
+    }
+}
+
+// Draws the whole maze over again, with faint lines where walls could go
+fn draw_maze(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    editor: Res<Editor>,
+    maze_query: Query<Entity, With<EditorMaze>>,
+) {
+    for entity in &maze_query {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let maze = &editor.maze;
+    let layout = Layout::new(maze);
+
+    commands
+        .spawn((
+            SpatialBundle {
+                transform: Transform {
+                    translation: MAZE_OFFSET.extend(0.),
+                    scale: Vec3::new(MAZE_SCALE, MAZE_SCALE, 1.),
+                    ..default()
+                },
+                ..default()
+            },
+            EditorMaze,
+            OnEditorScreen,
+        ))
+        .with_children(|parent| {
+            for (x, y) in (0..maze.height()).flat_map(|y| (0..maze.width()).map(move |x| (x, y))) {
+                let cell = maze.cell(x, y);
+
+                if cell.coin() {
+                    parent.spawn(SpriteBundle {
+                        texture: asset_server.load("images/coin.png"),
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::ONE),
+                            ..default()
+                        },
+                        transform: layout.coin_transform(x, y),
+                        ..default()
+                    });
+                }
+
+                // Each wall inside the maze belongs to the cells left of and above it
+                let sides = [
+                    Direction::North,
+                    Direction::West,
+                    Direction::South,
+                    Direction::East,
+                ];
+                for side in sides {
+                    let inside = maze.neighbour(x, y, side).is_some();
+                    if inside && matches!(side, Direction::South | Direction::East) {
+                        continue;
+                    }
+
+                    let color = match cell.is_open(side) {
+                        true if inside => OPEN_WALL_COLOR,
+                        true => continue,
+                        false => WALL_COLOR,
+                    };
+
+                    for (mut transform, _) in layout.wall_pieces(x, y, side) {
+                        transform.translation.z = if cell.is_open(side) { 0.5 } else { 1. };
+                        parent.spawn(SpriteBundle {
+                            sprite: Sprite { color, ..default() },
+                            transform,
+                            ..default()
+                        });
+                    }
+                }
+            }
+
+            parent.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::LIME_GREEN,
+                    ..default()
+                },
+                transform: layout.start_transform(),
+                ..default()
+            });
+            parent.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::TOMATO,
+                    ..default()
+                },
+                transform: layout.end_transform(),
+                ..default()
+            });
+        });
+}
+
+fn editor_setup(mut commands: Commands, asset_server: Res<AssetServer>, editor: Res<Editor>) {
+    let button_style = Style {
+        size: Size::new(Val::Px(100.), Val::Px(36.)),
+        margin: UiRect::all(Val::Px(4.)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+        font_size: 18.,
+        color: TEXT_COLOR,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    justify_content: JustifyContent::FlexEnd,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            OnEditorScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::width(Val::Px(224.)),
+                        margin: UiRect::right(Val::Px(10.)),
+                        flex_direction: FlexDirection::Row,
+                        flex_wrap: FlexWrap::Wrap,
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Editor",
+                        TextStyle {
+                            font_size: 40.,
+                            ..text_style.clone()
+                        },
+                    ));
+
+                    let tools = Tool::ALL
+                        .map(|tool| (ButtonAction::Tool(tool), tool.name()))
+                        .into_iter();
+                    let actions = [
+                        (ButtonAction::Check, "Check"),
+                        (ButtonAction::PlayTest, "Play-test"),
+                        (ButtonAction::Save, "Save"),
+                        (ButtonAction::Load, "Load"),
+                        (ButtonAction::Import, "Import"),
+                        (ButtonAction::Resize(-2), "Smaller"),
+                        (ButtonAction::Resize(2), "Bigger"),
+                        (ButtonAction::Clear, "Clear"),
+                        (ButtonAction::Back, "Main menu"),
+                    ];
+
+                    for (action, label) in tools.chain(actions) {
+                        parent
+                            .spawn(ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            })
+                            .insert(action)
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(label, text_style.clone()));
+                            });
+                    }
+
+                    parent.spawn((
+                        TextBundle::from_section(editor.status.clone(), text_style.clone())
+                            .with_style(Style {
+                                max_size: Size::width(Val::Px(216.)),
+                                margin: UiRect::all(Val::Px(4.)),
+                                ..default()
+                            }),
+                        StatusText,
+                    ));
+                });
+        });
+}
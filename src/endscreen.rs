@@ -1,7 +1,10 @@
+use crate::campaign::{self, CurrentLevel, LEVELS};
+use crate::editor::PlayTest;
 use crate::utils::{
     colors::{BACKGROUND_COLOR, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON, TEXT_COLOR},
     despawn_screen,
     vars::{GameState, MazeState},
+    ButtonInteractions,
 };
 use bevy::prelude::*;
 
@@ -11,7 +14,10 @@ impl Plugin for EndScreenPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(endscreen_setup.in_schedule(OnEnter(GameState::EndGame)))
             .add_system(button_system.in_set(OnUpdate(GameState::EndGame)))
-            .add_system(despawn_screen::<OnEndScreen>.in_schedule(OnExit(GameState::EndGame)));
+            .add_system(despawn_screen::<OnEndScreen>.in_schedule(OnExit(GameState::EndGame)))
+            .add_system(gameover_setup.in_schedule(OnEnter(GameState::GameOver)))
+            .add_system(button_system.in_set(OnUpdate(GameState::GameOver)))
+            .add_system(despawn_screen::<OnEndScreen>.in_schedule(OnExit(GameState::GameOver)));
     }
 }
 
@@ -22,10 +28,7 @@ struct OnEndScreen;
 struct ButtonAction(GameState);
 
 fn button_system(
-    mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor, &ButtonAction),
-        (Changed<Interaction>, With<Button>),
-    >,
+    mut interaction_query: ButtonInteractions<ButtonAction>,
     mut game_state: ResMut<NextState<GameState>>,
     mut maze_state: ResMut<MazeState>,
 ) {
@@ -39,16 +42,32 @@ fn button_system(
         if *interaction == Interaction::Clicked {
             maze_state.stopwatch.reset();
             maze_state.stopwatch.unpause();
+            maze_state.ticks = 0;
             game_state.set(state.0);
         }
     }
 }
 
+// In the campaign, the way out leads back to the levels instead of the main menu, and a
+// play-test goes back to the editor
+fn back_button(level: Option<&CurrentLevel>, play_test: bool) -> (GameState, &'static str) {
+    match (level, play_test) {
+        (Some(_), _) => (GameState::LevelSelect, "Levels"),
+        (None, true) => (GameState::Editor, "Editor"),
+        (None, false) => (GameState::Menu, "Main menu"),
+    }
+}
+
 fn endscreen_setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     maze_state: Res<MazeState>,
+    level: Option<Res<CurrentLevel>>,
+    play_test: Option<Res<PlayTest>>,
 ) {
+    let (back, back_label) = back_button(level.as_deref(), play_test.is_some());
+    let seconds = maze_state.stopwatch.elapsed_secs();
+
     commands
         .spawn((
             NodeBundle {
@@ -95,7 +114,7 @@ fn endscreen_setup(
                     ));
 
                     parent.spawn(TextBundle::from_section(
-                        format!("{:.3} seconds!", maze_state.stopwatch.elapsed_secs()),
+                        format!("{seconds:.3} seconds!"),
                         TextStyle {
                             font: asset_server.load("fonts/PixeloidSansBold.ttf"),
                             font_size: 40.,
@@ -103,11 +122,26 @@ fn endscreen_setup(
                         },
                     ));
 
+                    if let (Some(level), Some(maze)) = (&level, &maze_state.path) {
+                        parent.spawn(TextBundle::from_section(
+                            format!(
+                                "{}: {} of 3 stars",
+                                LEVELS[level.0].name,
+                                campaign::stars(maze, seconds)
+                            ),
+                            TextStyle {
+                                font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                font_size: 30.,
+                                color: TEXT_COLOR,
+                            },
+                        ));
+                    }
+
                     parent
                         .spawn(ButtonBundle {
                             style: Style {
                                 size: Size::new(Val::Px(300.), Val::Px(65.)),
-                                margin: UiRect::all(Val::Px(20.0)),
+                                margin: UiRect::all(Val::Px(10.0)),
                                 justify_content: JustifyContent::Center,
                                 align_items: AlignItems::Center,
                                 flex_direction: FlexDirection::Column,
@@ -116,10 +150,10 @@ fn endscreen_setup(
                             background_color: NORMAL_BUTTON.into(),
                             ..default()
                         })
-                        .insert(ButtonAction(GameState::Menu))
+                        .insert(ButtonAction(back))
                         .with_children(|parent| {
                             parent.spawn(TextBundle::from_section(
-                                "Main menu",
+                                back_label,
                                 TextStyle {
                                     font: asset_server.load("fonts/PixeloidSansBold.ttf"),
                                     font_size: 30.,
@@ -132,7 +166,7 @@ fn endscreen_setup(
                         .spawn(ButtonBundle {
                             style: Style {
                                 size: Size::new(Val::Px(300.), Val::Px(65.)),
-                                margin: UiRect::all(Val::Px(20.0)),
+                                margin: UiRect::all(Val::Px(10.0)),
                                 justify_content: JustifyContent::Center,
                                 align_items: AlignItems::Center,
                                 flex_direction: FlexDirection::Column,
@@ -152,6 +186,164 @@ fn endscreen_setup(
                                 },
                             ));
                         });
+
+                    parent
+                        .spawn(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(300.), Val::Px(65.)),
+                                margin: UiRect::all(Val::Px(10.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                flex_direction: FlexDirection::Column,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        })
+                        .insert(ButtonAction(GameState::Replay))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                "Watch replay",
+                                TextStyle {
+                                    font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                    font_size: 30.,
+                                    color: TEXT_COLOR,
+                                },
+                            ));
+                        });
+                    parent
+                        .spawn(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(300.), Val::Px(65.)),
+                                margin: UiRect::all(Val::Px(10.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                flex_direction: FlexDirection::Column,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        })
+                        .insert(ButtonAction(GameState::Visualize))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                "Solvers",
+                                TextStyle {
+                                    font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                    font_size: 30.,
+                                    color: TEXT_COLOR,
+                                },
+                            ));
+                        });
+                });
+        });
+}
+
+// Shown instead of the end screen when the last life is lost. Trying again plays the same
+// maze from the start.
+fn gameover_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    maze_state: Res<MazeState>,
+    level: Option<Res<CurrentLevel>>,
+    play_test: Option<Res<PlayTest>>,
+) {
+    let (back, back_label) = back_button(level.as_deref(), play_test.is_some());
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: BACKGROUND_COLOR.into(),
+                ..default()
+            },
+            OnEndScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Game over",
+                        TextStyle {
+                            font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                            font_size: 60.,
+                            color: TEXT_COLOR,
+                        },
+                    ));
+                    parent.spawn(TextBundle::from_section(
+                        format!(
+                            "You ran out of lives after {:.3} seconds",
+                            maze_state.stopwatch.elapsed_secs()
+                        ),
+                        TextStyle {
+                            font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                            font_size: 30.,
+                            color: TEXT_COLOR,
+                        },
+                    ));
+
+                    parent
+                        .spawn(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(300.), Val::Px(65.)),
+                                margin: UiRect::all(Val::Px(10.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                flex_direction: FlexDirection::Column,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        })
+                        .insert(ButtonAction(GameState::Game))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                "Try again",
+                                TextStyle {
+                                    font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                    font_size: 30.,
+                                    color: TEXT_COLOR,
+                                },
+                            ));
+                        });
+
+                    parent
+                        .spawn(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(300.), Val::Px(65.)),
+                                margin: UiRect::all(Val::Px(10.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                flex_direction: FlexDirection::Column,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        })
+                        .insert(ButtonAction(back))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                back_label,
+                                TextStyle {
+                                    font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                    font_size: 30.,
+                                    color: TEXT_COLOR,
+                                },
+                            ));
+                        });
                 });
         });
 }
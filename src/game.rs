@@ -1,37 +1,111 @@
-use crate::maze::{Direction, Maze};
+use crate::bot::Bot;
+use crate::campaign::CurrentLevel;
+use crate::maze::{
+    ring_len, Direction, Generator, Grid, Hazard, Hazards, Maze, PowerUp, Step, Terrain,
+};
+use crate::replay::{InputState, Recording};
+use crate::save::SaveGame;
+use crate::settings::Settings;
 use crate::utils::vars::{BUTTON_MARGIN, BUTTON_SIZE};
 use crate::utils::{
-    colors::{TEXT_COLOR, WALL_COLOR},
+    colors::{
+        BOOST_COLOR, BRIDGE_COLOR, CHECKPOINT_COLOR, CRUMBLE_COLOR, HOLE_COLOR, ICE_COLOR,
+        MUD_COLOR, PHASE_COLOR, REVEAL_COLOR, SPEED_COLOR, SPIKES_DOWN_COLOR, SPIKES_UP_COLOR,
+        STAIRS_DOWN_COLOR, STAIRS_UP_COLOR, TEXT_COLOR, TUNNEL_COLOR, WALL_COLOR,
+    },
     despawn_screen,
-    vars::{GameState, MazeState, HEIGHT, MAZE_BORDER_WIDTH, PLAYER_SPEED, WIDTH},
+    vars::{
+        DataDir, GameState, MazeState, CRUMBLE_TICKS, FALLEN_TICKS, FOG_CELLS, HEIGHT, LIVES,
+        MAZE_BORDER_WIDTH, PHASE_TICKS, PLAYER_SPEED, REVEAL_TICKS, SPEED_BOOST, SPEED_TICKS,
+        SPIKES_PERIOD, SPIKES_UP, TIMESTEP, WIDTH,
+    },
+    ButtonInteractions,
 };
 use bevy::{
+    app::AppExit,
+    ecs::system::SystemParam,
     prelude::*,
     sprite::{
         collide_aabb::{collide, Collision},
         Anchor,
     },
 };
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+use std::time::Duration;
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(menu_setup.in_schedule(OnEnter(GameState::Game)))
+        app.init_resource::<InputState>()
+            .add_system(menu_setup.in_schedule(OnEnter(GameState::Game)))
+            .add_system(menu_setup.in_schedule(OnEnter(GameState::Replay)))
+            .add_system(menu_setup.in_schedule(OnEnter(GameState::Demo)))
+            .add_system(
+                read_input
+                    .in_set(InputSet)
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .run_if(in_state(GameState::Game))
+                    .run_if(in_play),
+            )
             .add_systems(
                 (
+                    store_previous_position,
                     move_player,
-                    coin_check.after(move_player),
-                    time_check.after(move_player),
-                    coin_count.after(move_player),
+                    cross_bridges,
+                    climb_stairs,
+                    coin_check,
+                    power_up_check,
+                    update_hazards,
+                    hazard_check,
+                    time_check,
+                    advance_tick,
+                )
+                    .chain()
+                    .in_set(SimulationSet)
+                    .after(InputSet)
+                    .distributive_run_if(in_play)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (
+                    interpolate_positions,
+                    timer_board,
+                    coin_count,
+                    life_count,
+                    power_up_board,
+                    fog,
+                    button_system,
                 )
-                    .distributive_run_if(in_state(GameState::Game)),
+                    .distributive_run_if(in_play),
             )
-            .add_system(button_system.run_if(in_state(GameState::Game)))
-            .add_system(despawn_screen::<OnGameScreen>.in_schedule(OnExit(GameState::Game)));
+            .add_system(show_floor.run_if(on_game_screen))
+            .add_system(
+                intro
+                    .run_if(in_state(GameState::Game))
+                    .run_if(resource_exists::<Intro>()),
+            )
+            .add_system(
+                save_on_exit
+                    .in_base_set(CoreSet::Last)
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_system(despawn_screen::<OnGameScreen>.in_schedule(OnExit(GameState::Game)))
+            .add_system(despawn_screen::<OnGameScreen>.in_schedule(OnExit(GameState::Replay)))
+            .add_system(despawn_screen::<OnGameScreen>.in_schedule(OnExit(GameState::Demo)));
     }
 }
 
+// Systems that decide the `InputState` for the current tick
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InputSet;
+
+// Systems that move the player one tick and react to where it ends up
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimulationSet;
+
 #[derive(Component)]
 enum ButtonAction {
     Pause,
@@ -39,59 +113,372 @@ enum ButtonAction {
     Quit,
 }
 
+// Where a moving entity is in the simulation as of the last two fixed ticks.
+// Its `Transform` is only drawn in between them so movement looks smooth at any frame rate.
+#[derive(Component, Clone, Copy)]
+pub struct Position {
+    pub previous: Vec3,
+    pub current: Vec3,
+}
+
+impl Position {
+    pub fn new(translation: Vec3) -> Position {
+        Position {
+            previous: translation,
+            current: translation,
+        }
+    }
+}
+
 #[derive(Component)]
-enum ShowDuring {
+pub enum ShowDuring {
     Paused,
     Running,
 }
 
 #[derive(Component)]
-struct OnGameScreen;
+pub struct OnGameScreen;
+
+#[derive(Component)]
+pub struct Player;
+
+#[derive(Component)]
+pub struct Collider;
+
+// A wall on the outside of the maze or around the pads, which even phasing can't get through
+#[derive(Component)]
+pub struct Border;
+
+// A wall that isn't lined up with the axes, or a straight piece of a curved one
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Wall {
+    pub start: Vec2,
+    pub end: Vec2,
+}
+
+#[derive(Component)]
+pub struct Start;
+
+#[derive(Component)]
+pub struct End;
+
+#[derive(Component)]
+pub struct Coin {
+    pub x: usize,
+    pub y: usize,
+}
+
+#[derive(Component)]
+pub struct EndGate;
+
+// The floor of a multi-floor maze that a wall, coin, pad or staircase is on
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Floor(pub usize);
+
+// The boxes that can be bumped into, with whether each is a border and the floor it is on
+pub type Colliders<'w, 's, F> = Query<
+    'w,
+    's,
+    (
+        (&'static Transform, Option<&'static Border>),
+        &'static Floor,
+    ),
+    (With<Collider>, F),
+>;
+
+// The walls pushed against as lines, with whether each is a border and the floor it is on
+pub type Walls<'w, 's, F> =
+    Query<'w, 's, ((&'static Wall, Option<&'static Border>), &'static Floor), F>;
+
+// Everything on a floor hangs off its root, so only the floor the player is on is drawn
+#[derive(Component)]
+pub struct FloorRoot(pub usize);
+
+// Stepping onto stairs takes whoever climbs them to floor `to`
+#[derive(Component)]
+pub struct Stairs {
+    pub to: usize,
+}
+
+// The floor the player or ghost is on. `on_stairs` stays set after arriving until they step
+// off the stairs again, so they aren't sent straight back.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Climber {
+    pub floor: usize,
+    pub on_stairs: bool,
+}
+
+impl Climber {
+    pub fn new(floor: usize) -> Climber {
+        Climber {
+            floor,
+            on_stairs: true,
+        }
+    }
+}
 
+// The deck of a weave maze crossing, with a tunnel running under it along `tunnel`
 #[derive(Component)]
-struct Player;
+pub struct Bridge {
+    pub tunnel: [Direction; 2],
+}
 
+// A rail along a bridge, or a wall of the tunnel under it, which only stops whoever is
+// crossing `bridge` on the same level
 #[derive(Component)]
-struct Collider;
+pub struct BridgeWall {
+    pub bridge: Entity,
+    pub under: bool,
+}
 
+// A patch of mud, ice or boost pad covering a cell
 #[derive(Component)]
-struct Start;
+pub struct TerrainTile(pub Terrain);
+
+// The way the player or ghost last moved, which ice keeps them going in
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Momentum(pub Vec3);
 
+// The bridge the player or ghost is on or under. Which one is settled by the side they
+// came in from, and kept until they are off the crossing again.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Crossing {
+    pub bridge: Option<Entity>,
+    pub under: bool,
+}
+
+// Spikes that are up for `SPIKES_UP` ticks in every `SPIKES_PERIOD`, starting `phase`
+// ticks into it
 #[derive(Component)]
-struct End;
+pub struct Spikes {
+    pub phase: u32,
+}
+
+impl Spikes {
+    pub fn up(&self, ticks: u32) -> bool {
+        (ticks + self.phase) % SPIKES_PERIOD < SPIKES_UP
+    }
+}
+
+// A floor that starts to crumble when stepped on, is gone `CRUMBLE_TICKS` later and comes
+// back after another `FALLEN_TICKS`. `ticks` counts from the first step, or is 0 while whole.
+#[derive(Component, Default)]
+pub struct Collapsing {
+    pub ticks: u32,
+}
+
+impl Collapsing {
+    pub fn fallen(&self) -> bool {
+        self.ticks >= CRUMBLE_TICKS
+    }
+}
 
 #[derive(Component)]
-struct Coin;
+pub struct CheckpointTile {
+    pub x: usize,
+    pub y: usize,
+}
+
+// Where the player comes back after losing a life: the start pad until a checkpoint is
+// reached, which `cell` then names
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Respawn {
+    pub translation: Vec3,
+    pub floor: usize,
+    pub cell: Option<(usize, usize)>,
+}
 
 #[derive(Component)]
-struct EndGate;
+pub struct PowerUpTile(pub PowerUp);
+
+// How many more ticks each power-up lasts, in the order of `PowerUp::ALL`
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct PowerUps(pub [u32; 3]);
+
+impl PowerUps {
+    pub fn start(&mut self, power_up: PowerUp) {
+        self.0[power_up as usize] = match power_up {
+            PowerUp::Phase => PHASE_TICKS,
+            PowerUp::Speed => SPEED_TICKS,
+            PowerUp::Reveal => REVEAL_TICKS,
+        };
+    }
+
+    pub fn remaining(&self, power_up: PowerUp) -> u32 {
+        self.0[power_up as usize]
+    }
+
+    pub fn phasing(&self) -> bool {
+        self.remaining(PowerUp::Phase) > 0
+    }
+
+    pub fn speed(&self) -> f32 {
+        match self.remaining(PowerUp::Speed) > 0 {
+            true => SPEED_BOOST,
+            false => 1.,
+        }
+    }
+
+    // Phasing runs on until the player is out of the wall it is in
+    pub fn tick(&mut self, in_wall: bool) {
+        for (power_up, ticks) in PowerUp::ALL.iter().zip(&mut self.0) {
+            if !(in_wall && *power_up == PowerUp::Phase && *ticks == 1) {
+                *ticks = ticks.saturating_sub(1);
+            }
+        }
+    }
+}
 
 #[derive(Component)]
 struct TimerBoard;
 
+#[derive(Component)]
+pub struct FloorBoard;
+
+// Animates a freshly generated maze being carved out before play starts.
+// The generator replays the exact steps that produced the maze, and every wall it
+// knocks down is removed from a fully closed grid drawn on top of the real walls.
+#[derive(Resource)]
+struct Intro {
+    generator: Generator,
+    layout: Layout,
+    walls: HashMap<(usize, usize, Direction), Vec<Entity>>,
+    floor: usize,
+    speed: f32,
+    progress: f32,
+}
+
+#[derive(Component)]
+struct OnIntro;
+
+#[derive(Component)]
+struct IntroCursor;
+
+#[derive(Component)]
+struct IntroHint;
+
 #[derive(Component)]
 struct CoinCounter;
 
-fn menu_setup(
+#[derive(Component)]
+struct LifeCounter;
+
+#[derive(Component)]
+struct PowerUpBoard;
+
+fn on_game_screen(state: Res<State<GameState>>) -> bool {
+    matches!(
+        state.0,
+        GameState::Game | GameState::Replay | GameState::Demo
+    )
+}
+
+// The maze is simulated while playing, watching a replay or showing the demo,
+// but not while it is being built
+fn in_play(state: Res<State<GameState>>, intro: Option<Res<Intro>>) -> bool {
+    on_game_screen(state) && intro.is_none()
+}
+
+// Bigger mazes hide more coins
+pub fn coins_for(size: usize) -> usize {
+    (size + 9) / 4
+}
+
+// How many steps an animation over the cells of a maze takes in `seconds`. At 1x that is as
+// many steps a second as the maze has cells, so one step per cell takes about a second in all
+// whatever the size and shape of the maze.
+pub fn animation_steps(maze: &Maze, seconds: f32, speed: f32) -> f32 {
+    let cells = maze.rows().flatten().filter(|cell| cell.exists()).count();
+
+    seconds * speed * cells as f32
+}
+
+pub fn menu_setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut maze_state: ResMut<MazeState>,
+    state: Res<State<GameState>>,
+    settings: Res<Settings>,
 ) {
     let size = maze_state.size;
-    let coins = (size + 9) / 4;
+    let coins = coins_for(size);
 
-    maze_state.coins = coins;
+    maze_state.lives = LIVES;
 
     let path = &maze_state.path;
+    let show_intro = settings.intro && path.is_none() && state.0 == GameState::Game;
+    // The intro builds the maze up before the rest of it is shown
+    let visibility = match show_intro {
+        true => Visibility::Hidden,
+        false => Visibility::Inherited,
+    };
 
     let m = match path.clone() {
         Some(path) => path,
-        None => Maze::new(size, coins),
+        None => {
+            let seed = thread_rng().gen();
+            let generator = match &maze_state.shape {
+                Some((_, mask)) => {
+                    Generator::shaped(maze_state.grid, maze_state.floors, &mask.fit(size), seed)
+                }
+                None => Generator::new(maze_state.grid, maze_state.floors, size, seed),
+            };
+
+            generator
+                .with_doors(settings.doors)
+                .with_coin_placement(settings.coin_placement)
+                .with_terrain(settings.terrain)
+                .with_hazards(settings.hazards)
+                .with_power_ups(settings.power_ups)
+                .finish(coins)
+        }
     };
 
+    // A maze from the editor has as many coins as were put in it
+    let coins = match m.edited() {
+        true => m.coins(),
+        false => coins,
+    };
+    maze_state.coins = coins;
     maze_state.path = Some(m.clone());
 
-    let coord_size = (HEIGHT - MAZE_BORDER_WIDTH * (size as f32 + 1.)) / size as f32;
+    let saved = maze_state.saved.take();
+
+    if matches!(state.0, GameState::Game | GameState::Demo) {
+        commands.insert_resource(match &saved {
+            Some(save) => save.recording.clone(),
+            None => Recording::new(&m, size, coins),
+        });
+    }
+
+    let layout = Layout::new(&m);
+    let coord_size = layout.coord_size;
+
+    let player_translation = match &saved {
+        Some(save) => Vec3::new(save.player.0, save.player.1, 1.),
+        None => layout.start_translation(),
+    };
+    let player_floor = saved.as_ref().map_or(0, |save| save.floor);
+    let respawn = match saved.as_ref().and_then(|save| save.checkpoint) {
+        Some((x, y)) => Respawn {
+            translation: layout.cell_center(x, y).extend(1.),
+            floor: m.floor(y),
+            cell: Some((x, y)),
+        },
+        None => Respawn {
+            translation: layout.start_translation(),
+            floor: 0,
+            cell: None,
+        },
+    };
+
+    if let Some(save) = &saved {
+        maze_state.lives = save.lives;
+    }
+
+    let roots = spawn_floor_roots(&mut commands, m.floors(), player_floor);
+    let entrance_floor = m.floor(m.entrance().1);
+    let exit_floor = m.floor(m.exit().1);
 
     // Spawn player
     commands.spawn((
@@ -102,151 +489,105 @@ fn menu_setup(
                 ..default()
             },
             transform: Transform {
-                translation: Vec3::new(-300. - coord_size, 0., 1.),
+                translation: player_translation,
                 scale: Vec3::new(coord_size / 2., coord_size / 2., 1.),
                 ..default()
             },
             ..default()
         },
         Player,
+        Position::new(player_translation),
+        Climber::new(player_floor),
+        Crossing::default(),
+        Momentum::default(),
+        PowerUps::default(),
+        respawn,
         OnGameScreen,
         ShowDuring::Running,
     ));
 
-    // Spawn walls and coins for the maze
-    for (i, row) in m.path.iter().enumerate() {
-        for (j, cell) in row.iter().enumerate() {
-            let x = get_cell_coord(coord_size, j);
-            let y = -get_cell_coord(coord_size, i);
-
-            if cell.coin {
-                commands.spawn((
-                    SpriteBundle {
-                        texture: asset_server.load("images/coin.png"),
-                        sprite: Sprite {
-                            custom_size: Some(Vec2::ONE),
-                            ..default()
-                        },
-                        transform: Transform {
-                            translation: Vec3::new(x, y, 0.),
-                            scale: Vec3::new(coord_size * 0.75, coord_size * 0.75, 1.),
-                            ..default()
-                        },
-                        ..default()
-                    },
-                    Coin,
-                    OnGameScreen,
-                    ShowDuring::Running,
-                ));
-            }
-
-            let mut dirs = vec![
-                Direction::North,
-                Direction::South,
-                Direction::East,
-                Direction::West,
-            ];
-
-            dirs.retain(|e| !cell.directions.contains(e));
-
-            for dir in dirs {
-                let d = coord_size / 2. + MAZE_BORDER_WIDTH / 2.;
-                let mut dx = 0.;
-                let mut dy = 0.;
-                match dir {
-                    Direction::North => dy = d,
-                    Direction::South => dy = -d,
-                    Direction::East => dx = d,
-                    Direction::West => dx = -d,
-                };
-
-                let scale = match dir {
-                    Direction::North | Direction::South => {
-                        Vec3::new(coord_size + 2. * MAZE_BORDER_WIDTH, MAZE_BORDER_WIDTH, 1.)
-                    }
-                    Direction::East | Direction::West => {
-                        Vec3::new(MAZE_BORDER_WIDTH, coord_size + 2. * MAZE_BORDER_WIDTH, 1.)
-                    }
-                };
+    // Spawn walls, coins and stairs for the maze
+    let sprites = CellSprites {
+        maze: &m,
+        layout: &layout,
+        roots,
+        visibility,
+    };
+    let coin_texture = asset_server.load("images/coin.png");
+    for (i, row) in m.rows().enumerate() {
+        for (j, cell) in row.iter().enumerate().filter(|(_, cell)| cell.exists()) {
+            let collected = saved
+                .as_ref()
+                .is_some_and(|save| !save.remaining_coins.contains(&(j, i)));
 
-                commands.spawn((
-                    SpriteBundle {
-                        sprite: Sprite {
-                            color: WALL_COLOR,
-                            ..default()
-                        },
-                        transform: Transform {
-                            translation: Vec3::new(x + dx, y + dy, 1.),
-                            scale,
-                            ..default()
-                        },
-                        ..default()
-                    },
-                    Collider,
-                    OnGameScreen,
-                    ShowDuring::Running,
-                ));
+            if cell.coin() && !collected {
+                sprites.spawn_coin(&mut commands, coin_texture.clone(), (j, i));
             }
+            sprites.spawn_tiles(&mut commands, (j, i));
+            sprites.spawn_crossings(&mut commands, (j, i));
+            sprites.spawn_walls(&mut commands, (j, i));
         }
     }
+    let roots = sprites.roots;
 
     // Spawn start
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color: Color::LIME_GREEN,
-                ..default()
-            },
-            transform: Transform {
-                translation: Vec3::new(-300. - coord_size, 0., 0.),
-                scale: Vec3::new(coord_size * 2., coord_size * 2., 0.),
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::LIME_GREEN,
+                    ..default()
+                },
+                transform: layout.start_transform(),
                 ..default()
             },
-            ..default()
-        },
-        Start,
-        OnGameScreen,
-        ShowDuring::Running,
-    ));
+            Start,
+            Floor(entrance_floor),
+            ShowDuring::Running,
+        ))
+        .set_parent(roots[entrance_floor]);
 
     // Spawn end
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color: Color::TOMATO,
-                ..default()
-            },
-            transform: Transform {
-                translation: Vec3::new(300. + coord_size, 0., 0.),
-                scale: Vec3::new(coord_size * 2., coord_size * 2., 0.),
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::TOMATO,
+                    ..default()
+                },
+                transform: layout.end_transform(),
                 ..default()
             },
-            ..default()
-        },
-        End,
-        OnGameScreen,
-        ShowDuring::Running,
-    ));
+            End,
+            Floor(exit_floor),
+            ShowDuring::Running,
+        ))
+        .set_parent(roots[exit_floor]);
 
     // Spawn end gate
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color: WALL_COLOR,
-                ..default()
-            },
-            transform: Transform {
-                translation: Vec3::new(300. - MAZE_BORDER_WIDTH / 2., 0., 1.),
-                scale: Vec3::new(MAZE_BORDER_WIDTH, coord_size, 0.),
+    if coins > 0 && !saved.as_ref().is_some_and(|save| save.gate_open) {
+        let (transform, segment) = layout.end_gate_piece();
+        let mut gate = commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: WALL_COLOR,
+                    ..default()
+                },
+                transform,
                 ..default()
             },
-            ..default()
-        },
-        Collider,
-        EndGate,
-        OnGameScreen,
-        ShowDuring::Running,
-    ));
+            EndGate,
+            Border,
+            Floor(exit_floor),
+            ShowDuring::Running,
+        ));
+        gate.set_parent(roots[exit_floor]);
+
+        match segment {
+            Some(segment) => gate.insert(segment),
+            None => gate.insert(Collider),
+        };
+    }
 
     // Spawn timer
     commands.spawn((
@@ -290,6 +631,79 @@ fn menu_setup(
         OnGameScreen,
     ));
 
+    // Spawn life counter
+    if m.hazards() != Hazards::Off {
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    format!("Lives {}", maze_state.lives),
+                    TextStyle {
+                        font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                        font_size: 30.,
+                        color: TEXT_COLOR,
+                    },
+                ),
+                transform: Transform::from_translation(Vec3::new(
+                    (WIDTH / 2.) - 5.,
+                    (HEIGHT / 2.) - 85.,
+                    1.,
+                )),
+                text_anchor: Anchor::TopRight,
+                ..default()
+            },
+            LifeCounter,
+            OnGameScreen,
+        ));
+    }
+
+    // Spawn power-up indicator, under the life counter if there is one
+    if m.has_power_ups() {
+        let below = if m.hazards() != Hazards::Off { 35. } else { 0. };
+
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                        font_size: 25.,
+                        color: TEXT_COLOR,
+                    },
+                ),
+                transform: Transform::from_translation(Vec3::new(
+                    (WIDTH / 2.) - 5.,
+                    (HEIGHT / 2.) - 85. - below,
+                    1.,
+                )),
+                text_anchor: Anchor::TopRight,
+                ..default()
+            },
+            PowerUpBoard,
+            OnGameScreen,
+        ));
+    }
+
+    // Spawn floor indicator
+    if m.floors() > 1 {
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    format!("Floor {}/{}", player_floor + 1, m.floors()),
+                    TextStyle {
+                        font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                        font_size: 40.,
+                        color: TEXT_COLOR,
+                    },
+                ),
+                transform: Transform::from_translation(Vec3::new(0., HEIGHT / 2., 1.)),
+                text_anchor: Anchor::TopCenter,
+                ..default()
+            },
+            FloorBoard,
+            OnGameScreen,
+        ));
+    }
+
     // Spawn coin
     commands.spawn((
         SpriteBundle {
@@ -400,111 +814,1269 @@ fn menu_setup(
     ));
 
     // Spawn walls surrounding the start and end
-    for i in [-1., 1.] {
-        // Spawn left and right walls
-        commands.spawn((
-            SpriteBundle {
-                sprite: Sprite {
-                    color: WALL_COLOR,
-                    ..default()
-                },
-                transform: Transform {
-                    translation: Vec3::new(i * (300. + coord_size * 2.), 0., 1.),
-                    scale: Vec3::new(MAZE_BORDER_WIDTH, coord_size * 2. + MAZE_BORDER_WIDTH, 0.),
-                    ..default()
-                },
-                ..default()
-            },
-            Collider,
-            OnGameScreen,
-            ShowDuring::Running,
-        ));
+    for (transform, y) in layout.pad_wall_transforms() {
+        let floor = m.floor(y);
 
-        // Spawn upper and lower walls
-        for j in [-1., 1.] {
-            commands.spawn((
+        commands
+            .spawn((
                 SpriteBundle {
                     sprite: Sprite {
                         color: WALL_COLOR,
                         ..default()
                     },
-                    transform: Transform {
-                        translation: Vec3::new(i * (300. + coord_size), j * coord_size, 1.),
-                        scale: Vec3::new(
-                            coord_size * 2. + MAZE_BORDER_WIDTH,
-                            MAZE_BORDER_WIDTH,
-                            0.,
-                        ),
-                        ..default()
-                    },
+                    transform,
                     ..default()
                 },
                 Collider,
-                OnGameScreen,
+                Border,
+                Floor(floor),
                 ShowDuring::Running,
-            ));
-        }
+            ))
+            .set_parent(roots[floor]);
     }
-}
 
-fn get_cell_coord(coord_size: f32, i: usize) -> f32 {
-    MAZE_BORDER_WIDTH + (coord_size + MAZE_BORDER_WIDTH) * i as f32 - (HEIGHT - coord_size) / 2.
+    if show_intro {
+        intro_setup(&mut commands, &asset_server, &m, &layout, &roots);
+    }
 }
 
-fn move_player(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut player_query: Query<&mut Transform, With<Player>>,
-    collider_query: Query<&Transform, (With<Collider>, Without<Player>)>,
+// One root per floor for everything on it, where only the player's floor is shown
+fn spawn_floor_roots(commands: &mut Commands, floors: usize, shown: usize) -> Vec<Entity> {
+    (0..floors)
+        .map(|floor| {
+            commands
+                .spawn((
+                    SpatialBundle {
+                        visibility: if floor == shown {
+                            Visibility::Inherited
+                        } else {
+                            Visibility::Hidden
+                        },
+                        ..default()
+                    },
+                    FloorRoot(floor),
+                    OnGameScreen,
+                ))
+                .id()
+        })
+        .collect()
+}
+
+// Spawns what is drawn in each cell of a maze under the root of its floor
+struct CellSprites<'a> {
+    maze: &'a Maze,
+    layout: &'a Layout,
+    roots: Vec<Entity>,
+    visibility: Visibility,
+}
+
+impl CellSprites<'_> {
+    // Spawns a sprite on the floor of row `y`
+    fn spawn(&self, commands: &mut Commands, y: usize, sprite: SpriteBundle) -> Entity {
+        let floor = self.maze.floor(y);
+
+        commands
+            .spawn((
+                SpriteBundle {
+                    visibility: self.visibility,
+                    ..sprite
+                },
+                Floor(floor),
+                ShowDuring::Running,
+            ))
+            .set_parent(self.roots[floor])
+            .id()
+    }
+
+    fn spawn_coin(&self, commands: &mut Commands, texture: Handle<Image>, (x, y): (usize, usize)) {
+        let coin = self.spawn(
+            commands,
+            y,
+            SpriteBundle {
+                texture,
+                sprite: Sprite {
+                    custom_size: Some(Vec2::ONE),
+                    ..default()
+                },
+                transform: self.layout.coin_transform(x, y),
+                ..default()
+            },
+        );
+        commands.entity(coin).insert(Coin { x, y });
+    }
+
+    // Power-ups, terrain and hazards
+    fn spawn_tiles(&self, commands: &mut Commands, (x, y): (usize, usize)) {
+        let cell = self.maze.cell(x, y);
+
+        if let Some(power_up) = cell.power_up() {
+            let color = match power_up {
+                PowerUp::Phase => PHASE_COLOR,
+                PowerUp::Speed => SPEED_COLOR,
+                PowerUp::Reveal => REVEAL_COLOR,
+            };
+            let mut transform = self.layout.coin_transform(x, y);
+            transform.scale *= 0.6;
+            transform.rotate_z(PI / 4.);
+
+            let tile = self.spawn(
+                commands,
+                y,
+                SpriteBundle {
+                    sprite: Sprite { color, ..default() },
+                    transform,
+                    ..default()
+                },
+            );
+            commands.entity(tile).insert(PowerUpTile(power_up));
+        }
+
+        let color = match cell.terrain() {
+            Terrain::Plain => None,
+            Terrain::Mud => Some(MUD_COLOR),
+            Terrain::Ice => Some(ICE_COLOR),
+            Terrain::Boost => Some(BOOST_COLOR),
+        };
+        if let Some(color) = color {
+            let tile = self.spawn(
+                commands,
+                y,
+                SpriteBundle {
+                    sprite: Sprite { color, ..default() },
+                    transform: self.layout.terrain_transform(x, y),
+                    ..default()
+                },
+            );
+            commands.entity(tile).insert(TerrainTile(cell.terrain()));
+        }
+
+        let color = match cell.hazard() {
+            Hazard::Spikes => Some(SPIKES_DOWN_COLOR),
+            Hazard::Collapsing => Some(CRUMBLE_COLOR),
+            Hazard::Safe if cell.checkpoint() => Some(CHECKPOINT_COLOR),
+            Hazard::Safe => None,
+        };
+        if let Some(color) = color {
+            let tile = self.spawn(
+                commands,
+                y,
+                SpriteBundle {
+                    sprite: Sprite { color, ..default() },
+                    transform: self.layout.terrain_transform(x, y),
+                    ..default()
+                },
+            );
+            let mut tile = commands.entity(tile);
+
+            // Neighbouring spikes are out of step, so there is a way past a row of them
+            match cell.hazard() {
+                Hazard::Spikes => tile.insert(Spikes {
+                    phase: (x as u32 * 7 + y as u32 * 13) * 10 % SPIKES_PERIOD,
+                }),
+                Hazard::Collapsing => tile.insert(Collapsing::default()),
+                Hazard::Safe => tile.insert(CheckpointTile { x, y }),
+            };
+        }
+    }
+
+    // Stairs to other floors and bridges over crossings
+    fn spawn_crossings(&self, commands: &mut Commands, (x, y): (usize, usize)) {
+        let cell = self.maze.cell(x, y);
+
+        for (dir, color) in [
+            (Direction::Up, STAIRS_UP_COLOR),
+            (Direction::Down, STAIRS_DOWN_COLOR),
+        ] {
+            if let Some((_, to)) = self.maze.neighbour(x, y, dir).filter(|_| cell.is_open(dir)) {
+                let stairs = self.spawn(
+                    commands,
+                    y,
+                    SpriteBundle {
+                        sprite: Sprite { color, ..default() },
+                        transform: self.layout.stairs_transform(x, y),
+                        ..default()
+                    },
+                );
+                commands.entity(stairs).insert(Stairs {
+                    to: self.maze.floor(to),
+                });
+            }
+        }
+
+        if let Some(tunnel) = cell.tunnel() {
+            let bridge = self.spawn(
+                commands,
+                y,
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: BRIDGE_COLOR,
+                        ..default()
+                    },
+                    transform: self.layout.bridge_transform(x, y),
+                    ..default()
+                },
+            );
+            commands.entity(bridge).insert(Bridge { tunnel });
+
+            for (transform, under) in self.layout.crossing_walls(x, y, tunnel) {
+                let wall = self.spawn(
+                    commands,
+                    y,
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: if under { TUNNEL_COLOR } else { WALL_COLOR },
+                            ..default()
+                        },
+                        transform,
+                        ..default()
+                    },
+                );
+                commands.entity(wall).insert(BridgeWall { bridge, under });
+            }
+        }
+    }
+
+    // The walls are shown from the start, as the intro takes them down to build the maze
+    fn spawn_walls(&self, commands: &mut Commands, (x, y): (usize, usize)) {
+        let cell = self.maze.cell(x, y);
+        let floor = self.maze.floor(y);
+
+        for dir in self.maze.sides(x, y).filter(|dir| !cell.is_open(*dir)) {
+            for (transform, segment) in self.layout.wall_pieces(x, y, dir) {
+                let mut wall = commands.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: WALL_COLOR,
+                            ..default()
+                        },
+                        transform,
+                        ..default()
+                    },
+                    Floor(floor),
+                    ShowDuring::Running,
+                ));
+                wall.set_parent(self.roots[floor]);
+
+                if self.maze.neighbour(x, y, dir).is_none() {
+                    wall.insert(Border);
+                }
+
+                // Angled and curved walls can't be boxes, so they are pushed against as lines
+                match segment {
+                    Some(segment) => wall.insert(segment),
+                    None => wall.insert(Collider),
+                };
+            }
+        }
+    }
+}
+
+fn intro_setup(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    m: &Maze,
+    layout: &Layout,
+    roots: &[Entity],
+) {
+    let mut walls = HashMap::new();
+
+    // Every wall of a closed grid, each one shared by the two cells it separates
+    for y in 0..m.height() {
+        for x in (0..m.row_len(y)).filter(|&x| m.cell(x, y).exists()) {
+            for dir in m.sides(x, y) {
+                let key = get_wall_key(m, x, y, dir);
+
+                if walls.contains_key(&key) {
+                    continue;
+                }
+
+                let pieces = layout
+                    .wall_pieces(x, y, dir)
+                    .into_iter()
+                    .map(|(transform, _)| {
+                        commands
+                            .spawn((
+                                SpriteBundle {
+                                    sprite: Sprite {
+                                        color: WALL_COLOR,
+                                        ..default()
+                                    },
+                                    transform,
+                                    ..default()
+                                },
+                                OnIntro,
+                            ))
+                            .set_parent(roots[m.floor(y)])
+                            .id()
+                    })
+                    .collect();
+
+                walls.insert(key, pieces);
+            }
+        }
+    }
+
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.35, 0.75, 0.35, 0.8),
+                ..default()
+            },
+            transform: Transform {
+                translation: Vec3::new(0., 0., 0.5),
+                scale: Vec3::new(layout.coord_size, layout.coord_size, 1.),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        IntroCursor,
+        OnIntro,
+        OnGameScreen,
+    ));
+
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                    font_size: 20.,
+                    color: TEXT_COLOR,
+                },
+            ),
+            transform: Transform::from_translation(Vec3::new(0., -HEIGHT / 2., 2.)),
+            text_anchor: Anchor::BottomCenter,
+            ..default()
+        },
+        IntroHint,
+        OnIntro,
+        OnGameScreen,
+    ));
+
+    commands.insert_resource(Intro {
+        generator: Generator::replaying(m),
+        layout: *layout,
+        walls,
+        floor: 0,
+        speed: 1.,
+        progress: 0.,
+    });
+}
+
+// Everything in a maze besides its walls, which stays hidden until the intro is done
+type Furnishings = Or<(
+    With<Coin>,
+    With<Stairs>,
+    With<Bridge>,
+    With<BridgeWall>,
+    With<TerrainTile>,
+    With<Spikes>,
+    With<Collapsing>,
+    With<CheckpointTile>,
+    With<PowerUpTile>,
+)>;
+
+// What the intro shows over the maze it builds, and what it hides
+#[derive(SystemParam)]
+struct IntroScreen<'w, 's> {
+    cursor_query:
+        Query<'w, 's, (&'static mut Transform, &'static mut Visibility), With<IntroCursor>>,
+    hint_query: Query<'w, 's, &'static mut Text, With<IntroHint>>,
+    coin_query: Query<'w, 's, &'static mut Visibility, (Furnishings, Without<IntroCursor>)>,
+    overlay_query: Query<'w, 's, Entity, With<OnIntro>>,
+}
+
+fn intro(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
     time: Res<Time>,
+    mut intro: ResMut<Intro>,
+    screen: IntroScreen,
+) {
+    let IntroScreen {
+        mut cursor_query,
+        mut hint_query,
+        mut coin_query,
+        overlay_query,
+    } = screen;
+    let intro = &mut *intro;
+    let (mut cursor, mut cursor_visibility) = cursor_query.single_mut();
+
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        intro.speed = (intro.speed * 2.).min(64.);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        intro.speed = (intro.speed / 2.).max(0.25);
+    }
+
+    let skip = keyboard_input.any_just_pressed([KeyCode::Space, KeyCode::Return]);
+
+    intro.progress += animation_steps(intro.generator.maze(), time.delta_seconds(), intro.speed);
+
+    let mut done = false;
+
+    while skip || intro.progress >= 1. {
+        intro.progress -= 1.;
+
+        match intro.generator.next() {
+            Some(Step::Visit((x, y)) | Step::Backtrack((x, y))) => {
+                let center = intro.layout.cell_center(x, y);
+                cursor.translation.x = center.x;
+                cursor.translation.y = center.y;
+                *cursor_visibility = Visibility::Inherited;
+                intro.floor = intro.generator.maze().floor(y);
+            }
+            Some(Step::Carve((x, y), dir)) => {
+                let maze = intro.generator.maze();
+                let mut cell = Some((x, y));
+
+                // A passage tunnelled under a corridor opens the walls on both sides of it
+                while let Some((x, y)) = cell {
+                    let key = get_wall_key(maze, x, y, dir);
+
+                    for entity in intro.walls.remove(&key).into_iter().flatten() {
+                        commands.entity(entity).despawn_recursive();
+                    }
+
+                    cell = maze.neighbour(x, y, dir).filter(|&(x, y)| {
+                        maze.cell(x, y)
+                            .tunnel()
+                            .is_some_and(|tunnel| tunnel.contains(&dir))
+                    });
+                }
+            }
+            None => {
+                done = true;
+                break;
+            }
+        }
+    }
+
+    if done {
+        for entity in &overlay_query {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        for mut visibility in &mut coin_query {
+            *visibility = Visibility::Inherited;
+        }
+
+        commands.remove_resource::<Intro>();
+        return;
+    }
+
+    hint_query.single_mut().sections[0].value = format!(
+        "Building maze {}x  [Up/Down] speed  [Space] skip",
+        intro.speed
+    );
+}
+
+pub fn get_coord_size(size: usize) -> f32 {
+    (HEIGHT - MAZE_BORDER_WIDTH * (size as f32 + 1.)) / size as f32
+}
+
+pub fn get_cell_coord(coord_size: f32, i: usize) -> f32 {
+    MAZE_BORDER_WIDTH + (coord_size + MAZE_BORDER_WIDTH) * i as f32 - (HEIGHT - coord_size) / 2.
+}
+
+// Where a maze's cells, walls and pads go on screen. Square cells are `coord_size` across,
+// hex cells have sides `coord_size` long with odd rows pushed half a cell to the right,
+// and polar rings are `coord_size` thick around a middle `coord_size` wide in radius.
+// Either way the maze fills the height of the window and the start pad sits outside its
+// entrance, with an opening at least `coord_size` wide. The end pad sits outside the
+// exit, or in the middle of a polar maze. Square mazes with a door on the top or bottom
+// leave `margin` cells free all round for the pads. Every floor is drawn in the same
+// place, so `height` is the height of one floor.
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub grid: Grid,
+    pub width: usize,
+    pub height: usize,
+    pub coord_size: f32,
+    pub margin: usize,
+    pub entrance: (usize, usize),
+    pub entrance_side: Direction,
+    pub exit: (usize, usize),
+    pub exit_side: Direction,
+}
+
+// How long the straight pieces of a curved wall are at most
+const ARC_PIECE: f32 = 8.;
+
+impl Layout {
+    pub fn new(maze: &Maze) -> Layout {
+        let width = maze.width() as f32;
+        let height = maze.floor_height() as f32;
+        let vertical = |side: Direction| matches!(side, Direction::North | Direction::South);
+        let margin = match maze.grid() {
+            Grid::Square | Grid::Weave
+                if vertical(maze.entrance_side()) || vertical(maze.exit_side()) =>
+            {
+                2
+            }
+            _ => 0,
+        };
+
+        Layout {
+            grid: maze.grid(),
+            width: maze.width(),
+            height: maze.floor_height(),
+            coord_size: match maze.grid() {
+                Grid::Square | Grid::Weave => {
+                    get_coord_size(maze.width().max(maze.floor_height()) + 2 * margin)
+                }
+                Grid::Hex => {
+                    (HEIGHT / (3f32.sqrt() * (width + 0.5))).min(HEIGHT / (1.5 * height + 0.5))
+                }
+                Grid::Polar => HEIGHT / (2. * (height + 1.)),
+            },
+            margin,
+            entrance: maze.entrance(),
+            entrance_side: maze.entrance_side(),
+            exit: maze.exit(),
+            exit_side: maze.exit_side(),
+        }
+    }
+
+    pub fn cell_center(&self, x: usize, y: usize) -> Vec2 {
+        let y = y % self.height;
+
+        match self.grid {
+            Grid::Square | Grid::Weave => Vec2::new(
+                get_cell_coord(self.coord_size, x + self.margin),
+                -get_cell_coord(self.coord_size, y + self.margin),
+            ),
+            Grid::Hex => {
+                let across = 3f32.sqrt() * self.coord_size;
+                let offset = (y % 2) as f32 / 2.;
+
+                Vec2::new(
+                    across * (x as f32 + offset + 0.5 - (self.width as f32 + 0.5) / 2.),
+                    1.5 * self.coord_size * ((self.height - 1) as f32 / 2. - y as f32),
+                )
+            }
+            Grid::Polar => {
+                let (from, to) = self.ring_span(x, y);
+                Vec2::from_angle((from + to) / 2.) * (y as f32 + 1.5) * self.coord_size
+            }
+        }
+    }
+
+    // The angles a polar cell lies between. The rings are turned so that the entrance
+    // faces left, towards the start pad.
+    fn ring_span(&self, x: usize, y: usize) -> (f32, f32) {
+        let cells = ring_len(y % self.height) as f32;
+        let start = PI - TAU * (self.entrance.0 as f32 + 0.5) / ring_len(self.height - 1) as f32;
+
+        (
+            start + TAU * x as f32 / cells,
+            start + TAU * (x + 1) as f32 / cells,
+        )
+    }
+
+    // Points to walk through from the middle of one cell to the middle of a neighbouring one,
+    // close enough together that going straight between them keeps clear of the walls
+    pub fn route(&self, from: (usize, usize), to: (usize, usize)) -> Vec<Vec2> {
+        let start = self.cell_center(from.0, from.1);
+        let end = self.cell_center(to.0, to.1);
+
+        // Stairs lead to the same spot on another floor
+        if self.grid != Grid::Polar || from.1 / self.height != to.1 / self.height {
+            return self.straight_route(start, end);
+        }
+
+        // Follow the ring round rather than cut across it
+        if from.1 == to.1 {
+            let turn = start.angle_between(end);
+            let pieces = (start.length() * turn.abs() / (self.coord_size / 2.))
+                .ceil()
+                .max(1.);
+
+            return (1..=pieces as usize)
+                .map(|i| Vec2::from_angle(turn * i as f32 / pieces).rotate(start))
+                .collect();
+        }
+
+        // Between rings, go through the middle of the opening, which lines up with the
+        // outer cell. The inner one can be in line with the wall between two outer cells.
+        let outer = if from.1 > to.1 { start } else { end };
+        let ring = from.1.max(to.1) % self.height;
+        let door = outer.normalize() * (ring + 1) as f32 * self.coord_size;
+        let mut route = self.straight_route(start, door);
+        route.extend(self.straight_route(door, end));
+
+        route
+    }
+
+    pub fn straight_route(&self, from: Vec2, to: Vec2) -> Vec<Vec2> {
+        if matches!(self.grid, Grid::Square | Grid::Weave) {
+            return vec![to];
+        }
+
+        let pieces = (from.distance(to) / (self.coord_size / 2.)).ceil().max(1.);
+
+        (1..=pieces as usize)
+            .map(|i| from.lerp(to, i as f32 / pieces))
+            .collect()
+    }
+
+    pub fn coin_transform(&self, x: usize, y: usize) -> Transform {
+        Transform {
+            translation: self.cell_center(x, y).extend(0.),
+            scale: Vec3::new(self.coord_size * 0.75, self.coord_size * 0.75, 1.),
+            ..default()
+        }
+    }
+
+    // The straight lines a hex or polar wall is made of, with curves split into short pieces
+    pub fn wall_segments(&self, x: usize, y: usize, dir: Direction) -> Vec<(Vec2, Vec2)> {
+        match self.grid {
+            Grid::Square | Grid::Weave => Vec::new(),
+            Grid::Hex => {
+                let corner = |i: f32| {
+                    let angle = (60. * i - 30.).to_radians();
+                    self.cell_center(x, y) + Vec2::from_angle(angle) * self.coord_size
+                };
+
+                let first = match dir {
+                    Direction::East => 0.,
+                    Direction::NorthEast | Direction::North => 1.,
+                    Direction::NorthWest => 2.,
+                    Direction::West => 3.,
+                    Direction::SouthWest | Direction::South => 4.,
+                    Direction::SouthEast => 5.,
+                    Direction::Up | Direction::Down => return Vec::new(),
+                };
+
+                vec![(corner(first), corner(first + 1.))]
+            }
+            Grid::Polar => {
+                let y = y % self.height;
+                let (from, to) = self.ring_span(x, y);
+                let middle = (from + to) / 2.;
+                let inner = (y + 1) as f32 * self.coord_size;
+                let outer = inner + self.coord_size;
+                let split = y + 1 < self.height && ring_len(y + 1) > ring_len(y);
+
+                match dir {
+                    Direction::North | Direction::NorthWest => arc(inner, from, to),
+                    Direction::South if split => arc(outer, from, middle),
+                    Direction::South => arc(outer, from, to),
+                    Direction::SouthEast => arc(outer, middle, to),
+                    Direction::West => {
+                        vec![(
+                            Vec2::from_angle(from) * inner,
+                            Vec2::from_angle(from) * outer,
+                        )]
+                    }
+                    Direction::East => {
+                        vec![(Vec2::from_angle(to) * inner, Vec2::from_angle(to) * outer)]
+                    }
+                    _ => Vec::new(),
+                }
+            }
+        }
+    }
+
+    pub fn stairs_transform(&self, x: usize, y: usize) -> Transform {
+        Transform {
+            translation: self.cell_center(x, y).extend(0.),
+            scale: Vec3::new(self.coord_size * 0.4, self.coord_size * 0.4, 1.),
+            ..default()
+        }
+    }
+
+    // Just behind the coins, so that those stay on top
+    pub fn terrain_transform(&self, x: usize, y: usize) -> Transform {
+        Transform {
+            translation: self.cell_center(x, y).extend(-0.05),
+            scale: Vec3::new(self.coord_size, self.coord_size, 1.),
+            ..default()
+        }
+    }
+
+    pub fn bridge_transform(&self, x: usize, y: usize) -> Transform {
+        Transform {
+            translation: self.cell_center(x, y).extend(0.3),
+            scale: Vec3::new(self.coord_size, self.coord_size, 1.),
+            ..default()
+        }
+    }
+
+    // The rails along either side of a bridge, which are at the ends of the tunnel, and the
+    // walls of the tunnel beneath it, along with whether each is under the bridge
+    pub fn crossing_walls(
+        &self,
+        x: usize,
+        y: usize,
+        tunnel: [Direction; 2],
+    ) -> Vec<(Transform, bool)> {
+        let bridge = match tunnel[0] {
+            Direction::North => [Direction::East, Direction::West],
+            _ => [Direction::North, Direction::South],
+        };
+        let pieces = |sides: [Direction; 2], under: bool| {
+            sides
+                .into_iter()
+                .flat_map(move |dir| self.wall_pieces(x, y, dir))
+                .map(move |(mut transform, _)| {
+                    if under {
+                        transform.translation.z = 0.1;
+                    }
+                    (transform, under)
+                })
+        };
+
+        pieces(tunnel, false).chain(pieces(bridge, true)).collect()
+    }
+
+    // The sprites a wall is drawn with. Square walls are boxes that collide as they are,
+    // the rest come with the `Wall` segment they are pushed against as.
+    pub fn wall_pieces(
+        &self,
+        x: usize,
+        y: usize,
+        dir: Direction,
+    ) -> Vec<(Transform, Option<Wall>)> {
+        if !matches!(self.grid, Grid::Square | Grid::Weave) {
+            return self
+                .wall_segments(x, y, dir)
+                .into_iter()
+                .map(|(start, end)| (get_segment_transform(start, end), Some(Wall { start, end })))
+                .collect();
+        }
+
+        let coord_size = self.coord_size;
+        let d = coord_size / 2. + MAZE_BORDER_WIDTH / 2.;
+        let mut translation = self.cell_center(x, y).extend(1.);
+
+        let scale = match dir {
+            Direction::North => {
+                translation.y += d;
+                Vec3::new(coord_size + 2. * MAZE_BORDER_WIDTH, MAZE_BORDER_WIDTH, 1.)
+            }
+            Direction::South => {
+                translation.y -= d;
+                Vec3::new(coord_size + 2. * MAZE_BORDER_WIDTH, MAZE_BORDER_WIDTH, 1.)
+            }
+            Direction::East => {
+                translation.x += d;
+                Vec3::new(MAZE_BORDER_WIDTH, coord_size + 2. * MAZE_BORDER_WIDTH, 1.)
+            }
+            Direction::West => {
+                translation.x -= d;
+                Vec3::new(MAZE_BORDER_WIDTH, coord_size + 2. * MAZE_BORDER_WIDTH, 1.)
+            }
+            _ => Vec3::ZERO,
+        };
+
+        vec![(
+            Transform {
+                translation,
+                scale,
+                ..default()
+            },
+            None,
+        )]
+    }
+
+    // How far the middle of a west or east wall is from the middle of its cell
+    fn half_span(&self) -> f32 {
+        match self.grid {
+            Grid::Square | Grid::Weave => self.coord_size / 2. + MAZE_BORDER_WIDTH / 2.,
+            Grid::Hex => 3f32.sqrt() * self.coord_size / 2.,
+            Grid::Polar => self.coord_size / 2.,
+        }
+    }
+
+    fn pad_size(&self) -> Vec2 {
+        match self.grid {
+            Grid::Hex => Vec2::new(self.coord_size * 2., self.coord_size),
+            _ => Vec2::new(self.coord_size * 2., self.coord_size * 2.),
+        }
+    }
+
+    // Which way is out of the maze through a door on `side`. The polar entrance is turned to
+    // face left.
+    fn outward(&self, side: Direction) -> Vec2 {
+        match (self.grid, side) {
+            (Grid::Polar, _) | (_, Direction::West) => -Vec2::X,
+            (_, Direction::East) => Vec2::X,
+            (_, Direction::North) => Vec2::Y,
+            _ => -Vec2::Y,
+        }
+    }
+
+    // A size given along and across `outward`, turned to line up with it
+    fn turned(outward: Vec2, along: f32, across: f32) -> Vec2 {
+        match outward.x == 0. {
+            true => Vec2::new(across, along),
+            false => Vec2::new(along, across),
+        }
+    }
+
+    // The outside edge of the entrance and exit walls
+    fn entrance_edge(&self) -> Vec2 {
+        self.cell_center(self.entrance.0, self.entrance.1)
+            + self.outward(self.entrance_side) * (self.half_span() + MAZE_BORDER_WIDTH / 2.)
+    }
+
+    fn exit_edge(&self) -> Vec2 {
+        self.cell_center(self.exit.0, self.exit.1)
+            + self.outward(self.exit_side) * (self.half_span() + MAZE_BORDER_WIDTH / 2.)
+    }
+
+    pub fn start_translation(&self) -> Vec3 {
+        (self.entrance_edge() + self.outward(self.entrance_side) * self.pad_size().x / 2.)
+            .extend(1.)
+    }
+
+    pub fn end_translation(&self) -> Vec3 {
+        match self.grid {
+            Grid::Polar => Vec3::new(0., 0., 1.),
+            _ => (self.exit_edge() + self.outward(self.exit_side) * self.pad_size().x / 2.)
+                .extend(1.),
+        }
+    }
+
+    pub fn start_transform(&self) -> Transform {
+        let pad = self.pad_size();
+
+        Transform {
+            translation: self.start_translation() * Vec3::new(1., 1., 0.),
+            scale: Layout::turned(self.outward(self.entrance_side), pad.x, pad.y).extend(0.),
+            ..default()
+        }
+    }
+
+    pub fn end_transform(&self) -> Transform {
+        let pad = self.pad_size();
+
+        Transform {
+            translation: self.end_translation() * Vec3::new(1., 1., 0.),
+            scale: match self.grid {
+                Grid::Polar => Vec3::new(self.coord_size, self.coord_size, 0.),
+                _ => Layout::turned(self.outward(self.exit_side), pad.x, pad.y).extend(0.),
+            },
+            ..default()
+        }
+    }
+
+    // The gate across the exit, which in a polar maze is a straight line across the opening
+    // into the middle
+    pub fn end_gate_piece(&self) -> (Transform, Option<Wall>) {
+        if self.grid == Grid::Polar {
+            let (from, to) = self.ring_span(self.exit.0, self.exit.1);
+            let start = Vec2::from_angle(from) * self.coord_size;
+            let end = Vec2::from_angle(to) * self.coord_size;
+
+            return (get_segment_transform(start, end), Some(Wall { start, end }));
+        }
+
+        let outward = self.outward(self.exit_side);
+
+        (
+            Transform {
+                translation: (self.exit_edge() - outward * MAZE_BORDER_WIDTH / 2.).extend(1.),
+                scale: Layout::turned(outward, MAZE_BORDER_WIDTH, self.coord_size).extend(0.),
+                ..default()
+            },
+            None,
+        )
+    }
+
+    // The three walls around each pad, along with the row of the cell the pad is next to
+    pub fn pad_wall_transforms(&self) -> Vec<(Transform, usize)> {
+        let pad = self.pad_size();
+        let mut sides = vec![(
+            self.entrance_edge(),
+            self.outward(self.entrance_side),
+            self.entrance.1,
+        )];
+
+        if self.grid != Grid::Polar {
+            sides.push((self.exit_edge(), self.outward(self.exit_side), self.exit.1));
+        }
+
+        sides
+            .into_iter()
+            .flat_map(|(edge, outward, y)| {
+                // The far wall, then the walls on either side, each given along and across
+                // the way out of the maze
+                [
+                    (pad.x, 0., MAZE_BORDER_WIDTH, pad.y + MAZE_BORDER_WIDTH),
+                    (
+                        pad.x / 2.,
+                        -pad.y / 2.,
+                        pad.x + MAZE_BORDER_WIDTH,
+                        MAZE_BORDER_WIDTH,
+                    ),
+                    (
+                        pad.x / 2.,
+                        pad.y / 2.,
+                        pad.x + MAZE_BORDER_WIDTH,
+                        MAZE_BORDER_WIDTH,
+                    ),
+                ]
+                .map(|(along, across, length, width)| {
+                    let translation = edge + outward * along + outward.perp() * across;
+
+                    (
+                        Transform {
+                            translation: translation.extend(1.),
+                            scale: Layout::turned(outward, length, width).extend(0.),
+                            ..default()
+                        },
+                        y,
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+// A circle of `radius` around the middle from angle `from` to `to`, as short straight pieces
+fn arc(radius: f32, from: f32, to: f32) -> Vec<(Vec2, Vec2)> {
+    let pieces = (radius * (to - from) / ARC_PIECE).ceil().max(1.);
+    let point = |i: f32| Vec2::from_angle(from + (to - from) * i / pieces) * radius;
+
+    (0..pieces as usize)
+        .map(|i| (point(i as f32), point(i as f32 + 1.)))
+        .collect()
+}
+
+// A wall sprite along a line, long enough to meet the walls at either end
+pub fn get_segment_transform(start: Vec2, end: Vec2) -> Transform {
+    let along = end - start;
+
+    Transform {
+        translation: ((start + end) / 2.).extend(1.),
+        rotation: Quat::from_rotation_z(along.y.atan2(along.x)),
+        scale: Vec3::new(along.length() + MAZE_BORDER_WIDTH, MAZE_BORDER_WIDTH, 1.),
+    }
+}
+
+// The same wall seen from either of the cells it separates
+fn get_wall_key(m: &Maze, x: usize, y: usize, dir: Direction) -> (usize, usize, Direction) {
+    match m.neighbour(x, y, dir) {
+        Some((nx, ny)) if (ny, nx) < (y, x) => (nx, ny, dir.opposite()),
+        _ => (x, y, dir),
+    }
+}
+
+// A bot playing the game sets the input itself, and it is recorded just the same
+pub fn read_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut input: ResMut<InputState>,
+    mut recording: ResMut<Recording>,
     maze_state: Res<MazeState>,
+    bot: Option<Res<Bot>>,
 ) {
-    let mut player_transform = player_query.single_mut();
-    let player_scale = player_transform.scale;
-    let mut direction = Vec3::new(0., 0., 0.);
+    if bot.is_none() {
+        *input = InputState::from_keyboard(&keyboard_input);
+    }
 
-    if keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up) {
-        direction.y += 1.;
+    if !maze_state.stopwatch.paused() {
+        recording.record(maze_state.ticks, *input);
     }
+}
 
-    if keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down) {
-        direction.y -= 1.;
+fn store_previous_position(mut position_query: Query<&mut Position>) {
+    for mut position in &mut position_query {
+        position.previous = position.current;
     }
+}
+
+// The player, with what steers it and what it moves through
+type PlayerMotion<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Position,
+        &'static mut Momentum,
+        &'static Transform,
+        &'static Climber,
+        &'static Crossing,
+        &'static PowerUps,
+    ),
+    With<Player>,
+>;
 
-    if keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left) {
-        direction.x -= 1.;
+fn move_player(
+    input: Res<InputState>,
+    mut player_query: PlayerMotion,
+    collider_query: Colliders<Without<Player>>,
+    bridge_wall_query: Query<(&Transform, &BridgeWall), Without<Player>>,
+    wall_query: Walls<()>,
+    tile_query: Query<((&Transform, &TerrainTile), &Floor), Without<Player>>,
+    maze_state: Res<MazeState>,
+) {
+    let (mut position, mut momentum, player_transform, climber, crossing, power_ups) =
+        player_query.single_mut();
+    let floor = Floor(climber.floor);
+    let phasing = power_ups.phasing();
+
+    if !maze_state.stopwatch.paused() {
+        let terrain = terrain_at(position.current, on_floor(&tile_query, floor));
+
+        momentum.0 = step_player(
+            &mut position.current,
+            player_transform.scale,
+            steer(input.direction(), *momentum, terrain),
+            terrain.speed() * power_ups.speed(),
+            solid(on_floor(&collider_query, floor), phasing)
+                .chain(bridge_walls(&bridge_wall_query, *crossing).filter(|_| !phasing)),
+        );
+        push_out_of_walls(
+            &mut position.current,
+            player_transform.scale,
+            solid(on_floor(&wall_query, floor), phasing),
+        );
+    }
+}
+
+// The walls in `walls` that stop whoever is or isn't `phasing`
+pub fn solid<'a, T: 'a>(
+    walls: impl IntoIterator<Item = (T, Option<&'a Border>)> + 'a,
+    phasing: bool,
+) -> impl Iterator<Item = T> + 'a {
+    walls
+        .into_iter()
+        .filter(move |(_, border)| !phasing || border.is_some())
+        .map(|(wall, _)| wall)
+}
+
+// The things in `items` that are on `floor`
+pub fn on_floor<'a, T, I>(items: I, floor: Floor) -> impl Iterator<Item = T> + 'a
+where
+    I: IntoIterator<Item = (T, &'a Floor)>,
+    I::IntoIter: 'a,
+{
+    items
+        .into_iter()
+        .filter(move |(_, on)| **on == floor)
+        .map(|(item, _)| item)
+}
+
+// The terrain under the middle of a box at `translation`, or plain if there is none
+pub fn terrain_at<'a>(
+    translation: Vec3,
+    tiles: impl IntoIterator<Item = (&'a Transform, &'a TerrainTile)>,
+) -> Terrain {
+    tiles
+        .into_iter()
+        .find(|(transform, _)| {
+            let offset = (translation - transform.translation).truncate().abs();
+            offset.x <= transform.scale.x / 2. && offset.y <= transform.scale.y / 2.
+        })
+        .map_or(Terrain::Plain, |(_, tile)| tile.0)
+}
+
+// On ice, letting go keeps going the way `momentum` says
+pub fn steer(direction: Vec3, momentum: Momentum, terrain: Terrain) -> Vec3 {
+    match terrain {
+        Terrain::Ice if direction == Vec3::ZERO => momentum.0,
+        _ => direction,
+    }
+}
+
+// The rails of the bridge being crossed, or the walls of the tunnel being gone through
+pub fn bridge_walls<'a>(
+    walls: impl IntoIterator<Item = (&'a Transform, &'a BridgeWall)>,
+    crossing: Crossing,
+) -> impl Iterator<Item = &'a Transform> {
+    walls
+        .into_iter()
+        .filter(move |(_, wall)| {
+            Some(wall.bridge) == crossing.bridge && wall.under == crossing.under
+        })
+        .map(|(transform, _)| transform)
+}
+
+fn cross_bridges(
+    mut player_query: Query<(&mut Position, &Transform, &Climber, &mut Crossing), With<Player>>,
+    bridge_query: Query<(Entity, &Transform, &Bridge, &Floor), Without<Player>>,
+) {
+    let (mut position, player_transform, climber, mut crossing) = player_query.single_mut();
+
+    cross(
+        position.current,
+        player_transform.scale,
+        Floor(climber.floor),
+        &mut crossing,
+        &bridge_query,
+    );
+    position.current.z = if crossing.under { 0.2 } else { 1. };
+}
+
+// Works out whether a box at `translation` that has just come onto a crossing is going
+// over the bridge or under it. Going under is coming in from either end of the tunnel.
+pub fn cross<'a>(
+    translation: Vec3,
+    scale: Vec3,
+    floor: Floor,
+    crossing: &mut Crossing,
+    bridges: impl IntoIterator<Item = (Entity, &'a Transform, &'a Bridge, &'a Floor)>,
+) {
+    let touching: Vec<_> = bridges
+        .into_iter()
+        .filter(|(_, transform, _, on)| {
+            **on == floor
+                && collide(
+                    transform.translation,
+                    transform.scale.truncate(),
+                    translation,
+                    scale.truncate(),
+                )
+                .is_some()
+        })
+        .collect();
+
+    if touching
+        .iter()
+        .any(|(entity, ..)| Some(*entity) == crossing.bridge)
+    {
+        return;
+    }
+
+    *crossing = match touching.first() {
+        Some((entity, transform, bridge, _)) => {
+            let from = translation - transform.translation;
+            let along_x = from.x.abs() > from.y.abs();
+
+            Crossing {
+                bridge: Some(*entity),
+                under: along_x == (bridge.tunnel[0] == Direction::East),
+            }
+        }
+        None => Crossing::default(),
+    };
+}
+
+fn climb_stairs(
+    mut player_query: Query<(&Position, &mut Climber), With<Player>>,
+    stairs_query: Query<(&Transform, &Floor, &Stairs)>,
+) {
+    let (position, mut climber) = player_query.single_mut();
+
+    climb(position.current, &mut climber, &stairs_query);
+}
+
+// Takes the stairs that `translation` has just stepped onto. Stairs are only taken from
+// close to the middle of their cell, where the player is clear of the walls on either floor.
+pub fn climb<'a>(
+    translation: Vec3,
+    climber: &mut Climber,
+    stairs: impl IntoIterator<Item = (&'a Transform, &'a Floor, &'a Stairs)>,
+) {
+    let touching = stairs.into_iter().find(|(transform, floor, _)| {
+        floor.0 == climber.floor
+            && translation
+                .truncate()
+                .distance(transform.translation.truncate())
+                < transform.scale.x / 2.
+    });
+
+    match touching {
+        Some((_, _, stairs)) if !climber.on_stairs => {
+            climber.floor = stairs.to;
+            climber.on_stairs = true;
+        }
+        Some(_) => {}
+        None => climber.on_stairs = false,
     }
+}
 
-    if keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right) {
-        direction.x += 1.;
+// Shows the floor the player is on, or the one being carved while the maze is built
+fn show_floor(
+    intro: Option<Res<Intro>>,
+    player_query: Query<&Climber, With<Player>>,
+    mut root_query: Query<(&FloorRoot, &mut Visibility)>,
+    mut board_query: Query<&mut Text, With<FloorBoard>>,
+) {
+    let floor = match (intro, player_query.get_single()) {
+        (Some(intro), _) => intro.floor,
+        (None, Ok(climber)) => climber.floor,
+        (None, Err(_)) => return,
+    };
+
+    for (root, mut visibility) in &mut root_query {
+        let shown = match root.0 == floor {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+
+        if *visibility != shown {
+            *visibility = shown;
+        }
     }
 
-    let mul_const = time.delta_seconds() * PLAYER_SPEED;
+    let floors = root_query.iter().count();
+
+    for mut text in &mut board_query {
+        text.sections[0].value = format!("Floor {}/{floors}", floor + 1);
+    }
+}
+
+// Treats the player as a circle and pushes it back out of any angled wall it moved into
+pub fn push_out_of_walls<'a>(
+    translation: &mut Vec3,
+    player_scale: Vec3,
+    walls: impl IntoIterator<Item = &'a Wall>,
+) {
+    let radius = player_scale.x / 2. + MAZE_BORDER_WIDTH / 2.;
+
+    for wall in walls {
+        let point = translation.truncate();
+        let along = wall.end - wall.start;
+        let t = ((point - wall.start).dot(along) / along.length_squared()).clamp(0., 1.);
+        let away = point - (wall.start + along * t);
+        let distance = away.length();
+
+        if distance > 0. && distance < radius {
+            *translation += (away / distance * (radius - distance)).extend(0.);
+        }
+    }
+}
+
+// Moves a player-sized box one tick in `direction` at `speed` times the usual pace, stopping
+// it flush against any collider. Gives back the way it ended up moving.
+pub fn step_player<'a>(
+    translation: &mut Vec3,
+    player_scale: Vec3,
+    mut direction: Vec3,
+    speed: f32,
+    colliders: impl IntoIterator<Item = &'a Transform>,
+) -> Vec3 {
+    let mul_const = TIMESTEP * PLAYER_SPEED * speed;
 
-    for transform in &collider_query {
+    for transform in colliders {
         let collision_x = collide(
             transform.translation,
             transform.scale.truncate(),
-            player_transform.translation + (Vec3::new(direction.x, 0., 0.) * mul_const),
+            *translation + (Vec3::new(direction.x, 0., 0.) * mul_const),
             player_scale.truncate(),
         );
 
         let collision_y = collide(
             transform.translation,
             transform.scale.truncate(),
-            player_transform.translation + (Vec3::new(0., direction.y, 0.) * mul_const),
+            *translation + (Vec3::new(0., direction.y, 0.) * mul_const),
             player_scale.truncate(),
         );
 
         if let Some(cx) = collision_x {
             if direction.x < 0. && cx == Collision::Left {
                 direction.x = 0.;
-                player_transform.translation.x =
+                translation.x =
                     transform.translation.x + transform.scale.x / 2. + player_scale.x / 2.;
             }
 
             if direction.x > 0. && cx == Collision::Right {
                 direction.x = 0.;
-                player_transform.translation.x =
+                translation.x =
                     transform.translation.x - transform.scale.x / 2. - player_scale.x / 2.;
             }
         }
@@ -512,83 +2084,280 @@ fn move_player(
         if let Some(cy) = collision_y {
             if direction.y < 0. && cy == Collision::Bottom {
                 direction.y = 0.;
-                player_transform.translation.y =
+                translation.y =
                     transform.translation.y + transform.scale.y / 2. + player_scale.y / 2.;
             }
 
             if direction.y > 0. && cy == Collision::Top {
                 direction.y = 0.;
-                player_transform.translation.y =
+                translation.y =
                     transform.translation.y - transform.scale.y / 2. - player_scale.y / 2.;
             }
         }
     }
 
-    if !maze_state.stopwatch.paused() {
-        player_transform.translation += direction * mul_const * player_scale;
-    }
+    *translation += direction * mul_const * player_scale;
+    direction
 }
 
 fn coin_check(
     mut commands: Commands,
-    player_query: Query<&Transform, With<Player>>,
-    coin_query: Query<(Entity, &Transform), With<Coin>>,
+    player_query: Query<(&Position, &Transform, &Climber), With<Player>>,
+    coin_query: Query<(Entity, &Transform, &Floor), With<Coin>>,
     end_gate_query: Query<Entity, With<EndGate>>,
 ) {
-    let player_transform = player_query.single();
+    let (position, player_transform, climber) = player_query.single();
+
+    for (entity, transform, floor) in coin_query.iter() {
+        if floor.0 == climber.floor
+            && collide(
+                transform.translation,
+                transform.scale.truncate(),
+                position.current,
+                player_transform.scale.truncate(),
+            )
+            .is_some()
+        {
+            commands.entity(entity).despawn_recursive();
+            if coin_query.iter().count() == 1 {
+                commands.entity(end_gate_query.single()).despawn_recursive();
+            }
+        }
+    }
+}
+
+// Counts down the power-ups running and starts the one the player has just picked up
+fn power_up_check(
+    mut commands: Commands,
+    mut player_query: Query<(&Position, &Transform, &Climber, &mut PowerUps), With<Player>>,
+    tile_query: Query<(Entity, &Transform, &Floor, &PowerUpTile)>,
+    collider_query: Colliders<Without<Player>>,
+    maze_state: Res<MazeState>,
+) {
+    if maze_state.stopwatch.paused() {
+        return;
+    }
 
-    for (entity, transform) in coin_query.iter() {
-        if collide(
+    let (position, player_transform, climber, mut power_ups) = player_query.single_mut();
+    let floor = Floor(climber.floor);
+    let touching = |transform: &Transform| {
+        collide(
             transform.translation,
             transform.scale.truncate(),
-            player_transform.translation,
+            position.current,
             player_transform.scale.truncate(),
         )
         .is_some()
-        {
-            commands.entity(entity).despawn();
-            if coin_query.iter().count() == 1 {
-                commands.entity(end_gate_query.single()).despawn();
-            }
+    };
+
+    power_ups.tick(
+        on_floor(&collider_query, floor).any(|(wall, border)| border.is_none() && touching(wall)),
+    );
+
+    for (entity, transform, on, tile) in &tile_query {
+        if *on == floor && touching(transform) {
+            commands.entity(entity).despawn_recursive();
+            power_ups.start(tile.0);
+        }
+    }
+}
+
+// The collapsing floors, on their floors of the maze
+type CollapsingTiles<'w, 's> = Query<
+    'w,
+    's,
+    (
+        (
+            &'static Transform,
+            &'static mut Collapsing,
+            &'static mut Sprite,
+        ),
+        &'static Floor,
+    ),
+    Without<Spikes>,
+>;
+
+// Raises and lowers the spikes, and crumbles floors once the player steps on them
+fn update_hazards(
+    player_query: Query<(&Position, &Climber), With<Player>>,
+    mut spikes_query: Query<(&Spikes, &mut Sprite)>,
+    mut collapsing_query: CollapsingTiles,
+    maze_state: Res<MazeState>,
+) {
+    if maze_state.stopwatch.paused() {
+        return;
+    }
+
+    let (position, climber) = player_query.single();
+
+    for (spikes, mut sprite) in &mut spikes_query {
+        sprite.color = match spikes.up(maze_state.ticks) {
+            true => SPIKES_UP_COLOR,
+            false => SPIKES_DOWN_COLOR,
+        };
+    }
+
+    for ((transform, mut collapsing, mut sprite), floor) in &mut collapsing_query {
+        if collapsing.ticks > 0 || floor.0 == climber.floor && under(position.current, transform) {
+            collapsing.ticks = (collapsing.ticks + 1) % (CRUMBLE_TICKS + FALLEN_TICKS);
         }
+
+        sprite.color = match collapsing.fallen() {
+            true => HOLE_COLOR,
+            false => CRUMBLE_COLOR
+                .with_a(0.8 * (1. - collapsing.ticks as f32 / CRUMBLE_TICKS as f32 / 2.)),
+        };
+    }
+}
+
+// Whether `translation` is over the square `transform` covers
+fn under(translation: Vec3, transform: &Transform) -> bool {
+    let offset = (translation - transform.translation).truncate().abs();
+    offset.x <= transform.scale.x / 2. && offset.y <= transform.scale.y / 2.
+}
+
+// The tiles that can cost the player a life or save where they come back
+#[derive(SystemParam)]
+struct HazardTiles<'w, 's> {
+    spikes_query: Query<'w, 's, ((&'static Transform, &'static Spikes), &'static Floor)>,
+    collapsing_query: Query<'w, 's, ((&'static Transform, &'static Collapsing), &'static Floor)>,
+    checkpoint_query: Query<
+        'w,
+        's,
+        (
+            (&'static Transform, &'static CheckpointTile),
+            &'static Floor,
+        ),
+    >,
+}
+
+// Moves the respawn point up to checkpoints as they are reached, and sends the player back
+// to it, a life down, when they are caught on raised spikes or fall through the floor.
+// Running out of lives ends the game, though replays and the demo carry on regardless.
+fn hazard_check(
+    mut player_query: Query<
+        (
+            &mut Position,
+            &mut Climber,
+            &mut Crossing,
+            &mut Momentum,
+            &mut Respawn,
+        ),
+        With<Player>,
+    >,
+    tiles: HazardTiles,
+    mut maze_state: ResMut<MazeState>,
+    state: Res<State<GameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    bot: Option<ResMut<Bot>>,
+) {
+    let HazardTiles {
+        spikes_query,
+        collapsing_query,
+        checkpoint_query,
+    } = tiles;
+
+    if maze_state.stopwatch.paused() {
+        return;
+    }
+
+    let (mut position, mut climber, mut crossing, mut momentum, mut respawn) =
+        player_query.single_mut();
+    let floor = Floor(climber.floor);
+    let on = |transform: &Transform| under(position.current, transform);
+
+    if let Some((transform, tile)) = on_floor(&checkpoint_query, floor).find(|(t, _)| on(t)) {
+        *respawn = Respawn {
+            translation: transform.translation.truncate().extend(1.),
+            floor: floor.0,
+            cell: Some((tile.x, tile.y)),
+        };
+    }
+
+    let caught = on_floor(&spikes_query, floor)
+        .any(|(transform, spikes)| spikes.up(maze_state.ticks) && on(transform))
+        || on_floor(&collapsing_query, floor)
+            .any(|(transform, collapsing)| collapsing.fallen() && on(transform));
+
+    if !caught {
+        return;
+    }
+
+    maze_state.lives = maze_state.lives.saturating_sub(1);
+
+    if maze_state.lives == 0 && state.0 == GameState::Game {
+        maze_state.stopwatch.pause();
+        game_state.set(GameState::GameOver);
+        return;
+    }
+
+    *position = Position::new(respawn.translation);
+    *climber = Climber::new(respawn.floor);
+    *crossing = Crossing::default();
+    *momentum = Momentum::default();
+
+    if let Some(mut bot) = bot {
+        bot.respawn(respawn.cell);
     }
 }
 
 fn time_check(
-    mut scoreboard_query: Query<&mut Text, With<TimerBoard>>,
-    time: Res<Time>,
-    start_query: Query<&Transform, With<Start>>,
-    end_query: Query<&Transform, With<End>>,
-    player_query: Query<&Transform, With<Player>>,
+    start_query: Query<(&Transform, &Floor), With<Start>>,
+    end_query: Query<(&Transform, &Floor), With<End>>,
+    player_query: Query<(&Position, &Transform, &Climber), With<Player>>,
     mut maze_state: ResMut<MazeState>,
+    state: Res<State<GameState>>,
     mut game_state: ResMut<NextState<GameState>>,
 ) {
-    let mut text = scoreboard_query.single_mut();
+    let (position, player_transform, climber) = player_query.single();
+    let touching = |(transform, floor): (&Transform, &Floor)| {
+        floor.0 == climber.floor
+            && collide(
+                position.current,
+                player_transform.scale.truncate(),
+                transform.translation,
+                transform.scale.truncate(),
+            )
+            .is_some()
+    };
 
     if !(maze_state.stopwatch.paused()
-        || collide(
-            player_query.single().translation,
-            player_query.single().scale.truncate(),
-            start_query.single().translation,
-            start_query.single().scale.truncate(),
-        )
-        .is_some()
-            && maze_state.stopwatch.elapsed_secs() == 0.)
+        || touching(start_query.single()) && maze_state.stopwatch.elapsed_secs() == 0.)
     {
-        maze_state.stopwatch.tick(time.delta());
+        maze_state.stopwatch.tick(Duration::from_secs_f32(TIMESTEP));
     }
 
-    if collide(
-        player_query.single().translation,
-        player_query.single().scale.truncate(),
-        end_query.single().translation,
-        end_query.single().scale.truncate(),
-    )
-    .is_some()
-    {
+    if touching(end_query.single()) {
         maze_state.stopwatch.pause();
-        game_state.set(GameState::EndGame);
+
+        if state.0 == GameState::Game {
+            game_state.set(GameState::EndGame);
+        }
+    }
+}
+
+fn advance_tick(mut maze_state: ResMut<MazeState>) {
+    if !maze_state.stopwatch.paused() {
+        maze_state.ticks += 1;
     }
+}
+
+fn interpolate_positions(
+    mut position_query: Query<(&mut Transform, &Position)>,
+    fixed_time: Res<FixedTime>,
+) {
+    let alpha = (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.);
+
+    for (mut transform, position) in &mut position_query {
+        transform.translation = position.previous.lerp(position.current, alpha);
+    }
+}
+
+fn timer_board(
+    mut scoreboard_query: Query<&mut Text, With<TimerBoard>>,
+    maze_state: Res<MazeState>,
+) {
+    let mut text = scoreboard_query.single_mut();
 
     text.sections[0].value = format!("{:.3}", maze_state.stopwatch.elapsed_secs());
 }
@@ -605,16 +2374,82 @@ fn coin_count(
     text.sections[0].value = format!("{}/{}", maze_state.coins - coins, maze_state.coins);
 }
 
+fn power_up_board(
+    mut text_query: Query<&mut Text, With<PowerUpBoard>>,
+    player_query: Query<&PowerUps, With<Player>>,
+) {
+    let Ok(power_ups) = player_query.get_single() else {
+        return;
+    };
+
+    for mut text in &mut text_query {
+        text.sections[0].value = PowerUp::ALL
+            .iter()
+            .filter(|power_up| power_ups.remaining(**power_up) > 0)
+            .map(|power_up| {
+                let seconds = power_ups.remaining(*power_up) as f32 * TIMESTEP;
+                format!("{} {seconds:.1}", power_up.name())
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+}
+
+// Whatever is on a floor of the maze, which fog can hide
+type Fogged<'w, 's> =
+    Query<'w, 's, (&'static Transform, &'static mut Visibility), (With<Floor>, Without<Player>)>;
+
+// In fog only what is close to the player can be seen, unless a reveal is running
+fn fog(
+    settings: Res<Settings>,
+    maze_state: Res<MazeState>,
+    player_query: Query<(&Position, &Transform, &PowerUps), With<Player>>,
+    mut fog_query: Fogged,
+) {
+    if !settings.fog || maze_state.stopwatch.paused() {
+        return;
+    }
+
+    let Ok((position, player_transform, power_ups)) = player_query.get_single() else {
+        return;
+    };
+    // The player is half a cell wide
+    let radius = FOG_CELLS * player_transform.scale.x * 2.;
+    let revealed = power_ups.remaining(PowerUp::Reveal) > 0;
+
+    for (transform, mut visibility) in &mut fog_query {
+        let near = position
+            .current
+            .truncate()
+            .distance(transform.translation.truncate())
+            <= radius;
+        let shown = match revealed || near {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+}
+
+fn life_count(mut text_query: Query<&mut Text, With<LifeCounter>>, maze_state: Res<MazeState>) {
+    for mut text in &mut text_query {
+        text.sections[0].value = format!("Lives {}", maze_state.lives);
+    }
+}
+
 fn button_system(
-    mut interaction_query: Query<
-        (&Interaction, &ButtonAction),
-        (Changed<Interaction>, With<Button>),
-    >,
+    mut interaction_query: ButtonInteractions<ButtonAction>,
     mut collider_query: Query<(&mut Visibility, &ShowDuring), With<ShowDuring>>,
     mut maze_state: ResMut<MazeState>,
+    state: Res<State<GameState>>,
     mut game_state: ResMut<NextState<GameState>>,
+    progress: GameProgress,
+    data: Res<DataDir>,
 ) {
-    for (interaction, button_action) in interaction_query.iter_mut() {
+    for (interaction, _, button_action) in interaction_query.iter_mut() {
         if interaction == &Interaction::Clicked {
             match button_action {
                 ButtonAction::Pause => {
@@ -637,17 +2472,76 @@ fn button_system(
                             ShowDuring::Paused => {
                                 *visibility = Visibility::Hidden;
                             }
+                            // Inherited, so floors that aren't shown stay hidden
                             ShowDuring::Running => {
-                                *visibility = Visibility::Visible;
+                                *visibility = Visibility::Inherited;
                             }
                         }
                     }
                 }
                 ButtonAction::Quit => {
                     maze_state.stopwatch.pause();
+
+                    if state.0 == GameState::Game {
+                        if let Some(save) = save_game(&maze_state, &progress) {
+                            save.write(&data);
+                        }
+                    }
+
                     game_state.set(GameState::Menu);
                 }
             }
         }
     }
 }
+
+fn save_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    maze_state: Res<MazeState>,
+    progress: GameProgress,
+    data: Res<DataDir>,
+) {
+    if exit_events.iter().next().is_some() {
+        if let Some(save) = save_game(&maze_state, &progress) {
+            save.write(&data);
+        }
+    }
+}
+
+// What a game in progress is saved from, besides the maze state
+#[derive(SystemParam)]
+struct GameProgress<'w, 's> {
+    recording: Res<'w, Recording>,
+    player_query:
+        Query<'w, 's, (&'static Position, &'static Climber, &'static Respawn), With<Player>>,
+    coin_query: Query<'w, 's, &'static Coin>,
+    end_gate_query: Query<'w, 's, &'static EndGate>,
+    level: Option<Res<'w, CurrentLevel>>,
+}
+
+// Nothing is saved unless there is a maze with the player in it
+fn save_game(maze_state: &MazeState, progress: &GameProgress) -> Option<SaveGame> {
+    let maze = maze_state.path.clone()?;
+    let (position, climber, respawn) = progress.player_query.get_single().ok()?;
+    let player = position.current;
+
+    Some(SaveGame {
+        size: maze_state.size,
+        coins: maze_state.coins,
+        maze,
+        player: (player.x, player.y),
+        floor: climber.floor,
+        remaining_coins: progress
+            .coin_query
+            .iter()
+            .map(|coin| (coin.x, coin.y))
+            .collect(),
+        gate_open: progress.end_gate_query.is_empty(),
+        elapsed: maze_state.stopwatch.elapsed_secs(),
+        ticks: maze_state.ticks,
+        recording: progress.recording.clone(),
+        lives: maze_state.lives,
+        checkpoint: respawn.cell,
+        level: progress.level.as_ref().map(|level| level.0),
+    })
+}
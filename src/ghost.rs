@@ -0,0 +1,270 @@
+use crate::game::{
+    self, bridge_walls, climb, cross, on_floor, push_out_of_walls, solid, steer, step_player,
+    terrain_at, Bridge, BridgeWall, Climber, Colliders, Crossing, EndGate, Floor, Layout, Momentum,
+    OnGameScreen, Player, Position, PowerUps, ShowDuring, SimulationSet, Stairs, TerrainTile, Wall,
+    Walls,
+};
+use crate::maze::PowerUp;
+use crate::replay::Recording;
+use crate::settings::Settings;
+use crate::utils::vars::{DataDir, GameState, MazeState};
+use bevy::{ecs::system::SystemParam, prelude::*, sprite::collide_aabb::collide};
+
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            spawn_ghost
+                .after(game::menu_setup)
+                .in_schedule(OnEnter(GameState::Game)),
+        )
+        .add_system(
+            move_ghost
+                .after(SimulationSet)
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .run_if(in_state(GameState::Game)),
+        )
+        .add_system(fade_ghost.run_if(in_state(GameState::Game)));
+    }
+}
+
+// The ghost keeps its own copy of the coins, power-ups and gate so it retraces its run
+// exactly, no matter what the player has collected. Hazards don't touch it, so a run that lost a
+// life goes its own way from there.
+#[derive(Component)]
+pub struct Ghost {
+    pub recording: Recording,
+    tick: u32,
+    coins: Vec<(Transform, Floor)>,
+    power_ups: Vec<(Transform, Floor, PowerUp)>,
+    gate: Option<(Transform, Option<Wall>)>,
+    exit_floor: Floor,
+}
+
+fn spawn_ghost(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    maze_state: Res<MazeState>,
+    settings: Res<Settings>,
+    data: Res<DataDir>,
+) {
+    if !settings.ghost {
+        return;
+    }
+
+    // Set up just before by the game, along with the coins it has
+    let maze = maze_state
+        .path
+        .as_ref()
+        .expect("the maze is set up before the ghost");
+
+    let recording = match Recording::best(&data, maze, maze_state.size, maze_state.coins) {
+        Some(recording) => recording,
+        None => return,
+    };
+
+    let layout = Layout::new(maze);
+    let coord_size = layout.coord_size;
+
+    let mut coins = Vec::new();
+    let mut power_ups = Vec::new();
+    for (i, row) in maze.rows().enumerate() {
+        for (j, cell) in row.iter().enumerate() {
+            if cell.coin() {
+                coins.push((layout.coin_transform(j, i), Floor(maze.floor(i))));
+            }
+
+            // Only where it is matters, so the tile's scale and turn are left out
+            if let Some(power_up) = cell.power_up() {
+                let mut transform = layout.coin_transform(j, i);
+                transform.scale *= 0.6;
+                power_ups.push((transform, Floor(maze.floor(i)), power_up));
+            }
+        }
+    }
+
+    let translation = layout.start_translation() - Vec3::Z * 0.5;
+    // A maze without coins has no gate to open
+    let gate = (!coins.is_empty()).then(|| layout.end_gate_piece());
+
+    commands.spawn((
+        SpriteBundle {
+            texture: asset_server.load("images/player.png"),
+            sprite: Sprite {
+                color: Color::rgba(1., 1., 1., 0.4),
+                custom_size: Some(Vec2::ONE),
+                ..default()
+            },
+            transform: Transform {
+                translation,
+                scale: Vec3::new(coord_size / 2., coord_size / 2., 1.),
+                ..default()
+            },
+            ..default()
+        },
+        Ghost {
+            recording,
+            tick: 0,
+            coins,
+            power_ups,
+            gate,
+            exit_floor: Floor(maze.floor(maze.exit().1)),
+        },
+        Position::new(translation),
+        Climber::new(0),
+        Crossing::default(),
+        Momentum::default(),
+        PowerUps::default(),
+        OnGameScreen,
+        ShowDuring::Running,
+    ));
+}
+
+// A ghost, with what steers it and what it moves through
+type GhostMotion<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Position,
+        &'static Transform,
+        &'static mut Ghost,
+        &'static mut Climber,
+        &'static mut Crossing,
+        &'static mut Momentum,
+        &'static mut PowerUps,
+    ),
+>;
+
+// The bridges a ghost can pass over or under and the stairs it can climb
+#[derive(SystemParam)]
+struct Crossings<'w, 's> {
+    bridge_query: Query<
+        'w,
+        's,
+        (Entity, &'static Transform, &'static Bridge, &'static Floor),
+        Without<Ghost>,
+    >,
+    bridge_wall_query: Query<'w, 's, (&'static Transform, &'static BridgeWall), Without<Ghost>>,
+    stairs_query: Query<'w, 's, (&'static Transform, &'static Floor, &'static Stairs)>,
+}
+
+// Catches up to the player's tick, which also fast-forwards the ghost of a resumed game
+fn move_ghost(
+    mut ghost_query: GhostMotion,
+    collider_query: Colliders<(Without<EndGate>, Without<Ghost>)>,
+    crossings: Crossings,
+    wall_query: Walls<Without<EndGate>>,
+    tile_query: Query<((&Transform, &TerrainTile), &Floor), Without<Ghost>>,
+    maze_state: Res<MazeState>,
+) {
+    let Crossings {
+        bridge_query,
+        bridge_wall_query,
+        stairs_query,
+    } = crossings;
+
+    for (
+        mut position,
+        transform,
+        mut ghost,
+        mut climber,
+        mut crossing,
+        mut momentum,
+        mut power_ups,
+    ) in &mut ghost_query
+    {
+        let ghost = &mut *ghost;
+        let scale = transform.scale;
+
+        while ghost.tick < maze_state.ticks {
+            let direction = ghost.recording.input_at(ghost.tick).direction();
+            let floor = Floor(climber.floor);
+            let gate = ghost.gate.iter().filter(|_| floor == ghost.exit_floor);
+            let phasing = power_ups.phasing();
+
+            let terrain = terrain_at(position.current, on_floor(&tile_query, floor));
+
+            momentum.0 = step_player(
+                &mut position.current,
+                scale,
+                steer(direction, *momentum, terrain),
+                terrain.speed() * power_ups.speed(),
+                solid(on_floor(&collider_query, floor), phasing)
+                    .chain(bridge_walls(&bridge_wall_query, *crossing).filter(|_| !phasing))
+                    .chain(
+                        gate.clone()
+                            .filter(|(_, segment)| segment.is_none())
+                            .map(|(gate, _)| gate),
+                    ),
+            );
+            push_out_of_walls(
+                &mut position.current,
+                scale,
+                solid(on_floor(&wall_query, floor), phasing)
+                    .chain(gate.filter_map(|(_, segment)| segment.as_ref())),
+            );
+            cross(position.current, scale, floor, &mut crossing, &bridge_query);
+            position.current.z = if crossing.under { 0.15 } else { 0.5 };
+            climb(position.current, &mut climber, &stairs_query);
+
+            let translation = position.current;
+            let coins_before = ghost.coins.len();
+            ghost.coins.retain(|(coin, on)| {
+                *on != floor
+                    || collide(
+                        coin.translation,
+                        coin.scale.truncate(),
+                        translation,
+                        scale.truncate(),
+                    )
+                    .is_none()
+            });
+
+            if coins_before > 0 && ghost.coins.is_empty() {
+                ghost.gate = None;
+            }
+
+            let touching = |other: &Transform| {
+                collide(
+                    other.translation,
+                    other.scale.truncate(),
+                    translation,
+                    scale.truncate(),
+                )
+                .is_some()
+            };
+            power_ups.tick(
+                on_floor(&collider_query, floor)
+                    .any(|(wall, border)| border.is_none() && touching(wall)),
+            );
+            ghost.power_ups.retain(|(tile, on, power_up)| {
+                let picked = *on == floor && touching(tile);
+                if picked {
+                    power_ups.start(*power_up);
+                }
+                !picked
+            });
+
+            ghost.tick += 1;
+        }
+    }
+}
+
+// The ghost is only seen on the floor the player is on
+fn fade_ghost(
+    mut ghost_query: Query<(&mut Sprite, &Climber), With<Ghost>>,
+    player_query: Query<&Climber, With<Player>>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+
+    for (mut sprite, climber) in &mut ghost_query {
+        sprite.color.set_a(if climber.floor == player.floor {
+            0.4
+        } else {
+            0.
+        });
+    }
+}
@@ -0,0 +1,267 @@
+use crate::mask::{dark_pixels, MaskError};
+use crate::maze::{Direction, Maze};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportError {
+    Io(String),
+    Image(String),
+    Blank,
+    Grid,
+    Doors(usize),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "could not read maze image: {err}"),
+            ImportError::Image(err) => write!(f, "could not decode maze image: {err}"),
+            ImportError::Blank => write!(f, "image has no walls"),
+            ImportError::Grid => write!(f, "walls don't line up on a grid of cells"),
+            ImportError::Doors(gaps) => write!(
+                f,
+                "outside wall has {gaps} gaps instead of an entrance and an exit"
+            ),
+        }
+    }
+}
+
+impl From<MaskError> for ImportError {
+    fn from(err: MaskError) -> ImportError {
+        match err {
+            MaskError::Io(err) => ImportError::Io(err),
+            MaskError::Image(err) => ImportError::Image(err),
+            MaskError::Empty => ImportError::Blank,
+        }
+    }
+}
+
+// The dark pixels of an image, cropped to the box around them
+struct Walls {
+    width: usize,
+    height: usize,
+    dark: Vec<bool>,
+}
+
+impl Walls {
+    fn new(width: usize, height: usize, dark: Vec<bool>) -> Option<Walls> {
+        let filled = |x: usize, y: usize| dark[y * width + x];
+        let left = (0..width).find(|&x| (0..height).any(|y| filled(x, y)))?;
+        let right = (0..width).rfind(|&x| (0..height).any(|y| filled(x, y)))?;
+        let top = (0..height).find(|&y| (0..width).any(|x| filled(x, y)))?;
+        let bottom = (0..height).rfind(|&y| (0..width).any(|x| filled(x, y)))?;
+
+        Some(Walls {
+            width: right - left + 1,
+            height: bottom - top + 1,
+            dark: (top..=bottom)
+                .flat_map(|y| (left..=right).map(move |x| (x, y)))
+                .map(|(x, y)| filled(x, y))
+                .collect(),
+        })
+    }
+
+    fn at(&self, x: usize, y: usize) -> bool {
+        self.dark[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+    }
+
+    fn at_point(&self, x: f32, y: f32) -> bool {
+        self.at(x.round().max(0.) as usize, y.round().max(0.) as usize)
+    }
+
+    fn row(&self, y: usize) -> impl Iterator<Item = bool> + '_ {
+        (0..self.width).map(move |x| self.at(x, y))
+    }
+
+    fn column(&self, x: usize) -> impl Iterator<Item = bool> + '_ {
+        (0..self.height).map(move |y| self.at(x, y))
+    }
+}
+
+// How many of each length of light run there are between two dark pixels
+fn count_runs(line: impl Iterator<Item = bool>, counts: &mut HashMap<usize, usize>) {
+    let mut run = None;
+
+    for dark in line {
+        run = match (dark, run) {
+            (true, Some(length)) if length > 0 => {
+                *counts.entry(length).or_default() += 1;
+                Some(0)
+            }
+            (true, _) => Some(0),
+            (false, Some(length)) => Some(length + 1),
+            (false, None) => None,
+        };
+    }
+}
+
+// How thick the walls are, which is as far in from the edge as the dark goes where no wall
+// leads off the outside one
+fn thickness(lines: impl Iterator<Item = usize>) -> usize {
+    lines.filter(|&run| run > 0).min().unwrap_or(1)
+}
+
+// How many cells across a side `length` pixels long could be, given how long the light runs
+// are. Most runs span one cell, less the width of a wall, but the most common ones could
+// span a few. Runs the other way are looked at too, as cells are mostly square and a maze
+// one cell wide has no runs across it.
+fn cell_counts(length: usize, wall: usize, runs: [&HashMap<usize, usize>; 2]) -> Vec<usize> {
+    let mut counts: Vec<usize> = runs
+        .iter()
+        .flat_map(|runs| {
+            let mut common: Vec<(usize, usize)> =
+                runs.iter().map(|(&run, &count)| (run, count)).collect();
+            common.sort_by_key(|&(run, count)| (Reverse(count), run));
+            common.into_iter().take(3)
+        })
+        .flat_map(|(run, _)| {
+            (1..=3).map(move |cells| {
+                (cells as f32 * (length - wall) as f32 / (run + wall) as f32).round() as usize
+            })
+        })
+        .filter(|&cells| cells > 0 && cells * (wall + 1) < length)
+        .collect();
+    counts.sort();
+    counts.dedup();
+
+    counts
+}
+
+// Reads a maze drawn as a grid of cells with dark walls on a light background. The grid is one
+// that puts the middle of nearly every cell in the light and nearly every corner in the dark,
+// as a corner with no wall touching it can only be in the middle of a loop. Grids of bigger
+// cells or of long thin ones can pass too, so the one with the squarest cells wins, then the
+// finest. The two gaps in the outside wall are the doors, the first going down the left and
+// then along the top being the entrance.
+pub fn from_png(bytes: &[u8]) -> Result<Maze, ImportError> {
+    let (width, height, dark) = dark_pixels(bytes)?;
+    let walls = Walls::new(width, height, dark).ok_or(ImportError::Blank)?;
+
+    // Walls are taken to be as thick one way as the other, so a maze with a door in each side
+    // wall and nothing in between can still be measured
+    let run_in = |line: &mut dyn Iterator<Item = bool>| line.take_while(|dark| *dark).count();
+    let wall = thickness(
+        (0..walls.height)
+            .map(|y| run_in(&mut walls.row(y)))
+            .chain((0..walls.width).map(|x| run_in(&mut walls.column(x)))),
+    );
+
+    let mut runs_x = HashMap::new();
+    let mut runs_y = HashMap::new();
+    for y in 0..walls.height {
+        count_runs(walls.row(y), &mut runs_x);
+    }
+    for x in 0..walls.width {
+        count_runs(walls.column(x), &mut runs_y);
+    }
+
+    let score = |columns: usize, rows: usize| {
+        let pitch_x = (walls.width - wall) as f32 / columns as f32;
+        let pitch_y = (walls.height - wall) as f32 / rows as f32;
+        let line_x = |i: f32| i * pitch_x + (wall - 1) as f32 / 2.;
+        let line_y = |i: f32| i * pitch_y + (wall - 1) as f32 / 2.;
+
+        let light = (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| (x, y)))
+            .filter(|&(x, y)| !walls.at_point(line_x(x as f32 + 0.5), line_y(y as f32 + 0.5)))
+            .count() as f32
+            / (columns * rows) as f32;
+        let corners = (0..=rows)
+            .flat_map(|y| (0..=columns).map(move |x| (x, y)))
+            .filter(|&(x, y)| walls.at_point(line_x(x as f32), line_y(y as f32)))
+            .count() as f32
+            / ((columns + 1) * (rows + 1)) as f32;
+
+        (light >= 0.9 && corners >= 0.9).then_some(light + corners)
+    };
+
+    let rows_options = cell_counts(walls.height, wall, [&runs_y, &runs_x]);
+    let (columns, rows) = cell_counts(walls.width, wall, [&runs_x, &runs_y])
+        .into_iter()
+        .flat_map(|columns| rows_options.iter().map(move |&rows| (columns, rows)))
+        .filter_map(|(columns, rows)| Some((score(columns, rows)?, columns, rows)))
+        .max_by(|a, b| {
+            let squareness = |(_, columns, rows): &(f32, usize, usize)| {
+                let pitch_x = (walls.width - wall) as f32 / *columns as f32;
+                let pitch_y = (walls.height - wall) as f32 / *rows as f32;
+                Reverse(((pitch_x / pitch_y).ln().abs() * 10.).round() as usize)
+            };
+
+            squareness(a)
+                .cmp(&squareness(b))
+                .then((a.1 * a.2).cmp(&(b.1 * b.2)))
+                .then(a.0.total_cmp(&b.0))
+        })
+        .map(|(_, columns, rows)| (columns, rows))
+        .ok_or(ImportError::Grid)?;
+
+    let pitch_x = (walls.width - wall) as f32 / columns as f32;
+    let pitch_y = (walls.height - wall) as f32 / rows as f32;
+    let line_x = |i: usize| i as f32 * pitch_x + (wall - 1) as f32 / 2.;
+    let line_y = |i: usize| i as f32 * pitch_y + (wall - 1) as f32 / 2.;
+
+    // Whether the wall on the `direction` side of (x, y) is there, going by the middle of it
+    let closed = |x: usize, y: usize, direction: Direction| {
+        let ((from_x, from_y), (to_x, to_y)) = match direction {
+            Direction::North => ((line_x(x), line_y(y)), (line_x(x + 1), line_y(y))),
+            Direction::South => ((line_x(x), line_y(y + 1)), (line_x(x + 1), line_y(y + 1))),
+            Direction::West => ((line_x(x), line_y(y)), (line_x(x), line_y(y + 1))),
+            _ => ((line_x(x + 1), line_y(y)), (line_x(x + 1), line_y(y + 1))),
+        };
+        let dark = (0..5)
+            .map(|i| 0.3 + 0.1 * i as f32)
+            .filter(|t| walls.at_point(from_x + (to_x - from_x) * t, from_y + (to_y - from_y) * t))
+            .count();
+
+        dark >= 3
+    };
+
+    let mut maze = Maze::closed(columns, rows);
+    for (x, y) in (0..rows).flat_map(|y| (0..columns).map(move |x| (x, y))) {
+        for direction in [Direction::East, Direction::South] {
+            if !closed(x, y, direction) {
+                maze.set_wall(x, y, direction, false);
+            }
+        }
+    }
+
+    let edges = [
+        (
+            Direction::West,
+            (0..rows).map(|y| (0, y)).collect::<Vec<_>>(),
+        ),
+        (Direction::North, (0..columns).map(|x| (x, 0)).collect()),
+        (
+            Direction::East,
+            (0..rows).map(|y| (columns - 1, y)).collect(),
+        ),
+        (
+            Direction::South,
+            (0..columns).map(|x| (x, rows - 1)).collect(),
+        ),
+    ];
+    let doors: Vec<((usize, usize), Direction)> = edges
+        .iter()
+        .flat_map(|(side, cells)| cells.iter().map(move |&cell| (cell, *side)))
+        .filter(|&((x, y), side)| !closed(x, y, side))
+        .collect();
+
+    match doors[..] {
+        [entrance, exit] => {
+            maze.set_door(false, entrance.0, entrance.1);
+            maze.set_door(true, exit.0, exit.1);
+            Ok(maze)
+        }
+        _ => Err(ImportError::Doors(doors.len())),
+    }
+}
+
+pub fn load(path: &Path) -> Result<Maze, ImportError> {
+    let bytes = fs::read(path).map_err(|err| ImportError::Io(err.to_string()))?;
+
+    from_png(&bytes)
+}
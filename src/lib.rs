@@ -0,0 +1,53 @@
+pub mod bot;
+pub mod campaign;
+pub mod editor;
+pub mod endscreen;
+pub mod game;
+pub mod ghost;
+pub mod import;
+pub mod mask;
+pub mod maze;
+pub mod menu;
+pub mod replay;
+pub mod save;
+pub mod settings;
+pub mod solver;
+pub mod tui;
+pub mod utils;
+pub mod visualizer;
+
+use bevy::{prelude::*, time::Stopwatch};
+
+// All of the game's states, resources and screens. It needs no window or GPU, so it runs
+// under `MinimalPlugins` as long as assets and input are available.
+pub struct MazeGamePlugin;
+
+impl Plugin for MazeGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<utils::vars::DataDir>()
+            .add_state::<utils::vars::GameState>()
+            .insert_resource(FixedTime::new_from_secs(utils::vars::TIMESTEP))
+            .insert_resource(utils::vars::MazeState {
+                stopwatch: Stopwatch::new(),
+                size: 0,
+                grid: maze::Grid::Square,
+                floors: 1,
+                shape: None,
+                path: None,
+                coins: 0,
+                ticks: 0,
+                lives: utils::vars::LIVES,
+                saved: None,
+            })
+            .add_plugin(menu::MenuPlugin)
+            .add_plugin(game::GamePlugin)
+            .add_plugin(endscreen::EndScreenPlugin)
+            .add_plugin(replay::ReplayPlugin)
+            .add_plugin(settings::SettingsPlugin)
+            .add_plugin(ghost::GhostPlugin)
+            .add_plugin(visualizer::VisualizerPlugin)
+            .add_plugin(bot::BotPlugin)
+            .add_plugin(campaign::CampaignPlugin)
+            .add_plugin(editor::EditorPlugin);
+    }
+}
@@ -1,12 +1,7 @@
 #![windows_subsystem = "windows"]
 
-pub mod endscreen;
-pub mod game;
-pub mod maze;
-pub mod menu;
-pub mod utils;
-
-use bevy::{prelude::*, time::Stopwatch};
+use bevy::prelude::*;
+use maze_game::{utils, MazeGamePlugin};
 
 fn main() {
     App::new()
@@ -24,16 +19,7 @@ fn main() {
                 .set(ImagePlugin::default_nearest()),
         )
         .insert_resource(ClearColor(utils::colors::BACKGROUND_COLOR))
-        .add_state::<utils::vars::GameState>()
-        .insert_resource(utils::vars::MazeState {
-            stopwatch: Stopwatch::new(),
-            size: 0,
-            path: None,
-            coins: 0,
-        })
-        .add_plugin(menu::MenuPlugin)
-        .add_plugin(game::GamePlugin)
-        .add_plugin(endscreen::EndScreenPlugin)
+        .add_plugin(MazeGamePlugin)
         .add_startup_system(setup)
         .add_system(bevy::window::close_on_esc)
         .run();
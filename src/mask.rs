@@ -0,0 +1,275 @@
+use crate::utils::vars::MASK_DIR;
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::TextureFormat,
+        texture::{CompressedImageFormats, ImageType},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Which cells of a grid a shaped maze has, row by row from the top
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    cells: Vec<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaskError {
+    Io(String),
+    Image(String),
+    Empty,
+}
+
+impl fmt::Display for MaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MaskError::Io(err) => write!(f, "could not read mask: {err}"),
+            MaskError::Image(err) => write!(f, "could not decode mask image: {err}"),
+            MaskError::Empty => write!(f, "mask has no cells"),
+        }
+    }
+}
+
+impl Mask {
+    pub fn new(width: usize, height: usize, cells: Vec<bool>) -> Mask {
+        assert_eq!(cells.len(), width * height);
+
+        Mask {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    // Every cell of a `width` by `height` grid
+    pub fn full(width: usize, height: usize) -> Mask {
+        Mask {
+            width,
+            height,
+            cells: vec![true; width * height],
+        }
+    }
+
+    // Spaces and dots are holes, anything else is a cell. Short lines are padded with holes.
+    pub fn from_text(text: &str) -> Result<Mask, MaskError> {
+        let lines: Vec<Vec<bool>> = text
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .map(|c| !matches!(c, ' ' | '.' | '\t'))
+                    .collect()
+            })
+            .collect();
+        let width = lines.iter().map(Vec::len).max().unwrap_or(0);
+        let cells = lines
+            .iter()
+            .flat_map(|line| (0..width).map(|x| line.get(x).copied().unwrap_or(false)))
+            .collect();
+
+        Mask::new(width, lines.len(), cells).cropped()
+    }
+
+    // Dark, opaque pixels are cells
+    pub fn from_png(bytes: &[u8]) -> Result<Mask, MaskError> {
+        let (width, height, cells) = dark_pixels(bytes)?;
+
+        Mask::new(width, height, cells).cropped()
+    }
+
+    // Reads a `.png` image or a text file of any other name
+    pub fn load(path: &Path) -> Result<Mask, MaskError> {
+        let bytes = fs::read(path).map_err(|err| MaskError::Io(err.to_string()))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("png") => Mask::from_png(&bytes),
+            _ => Mask::from_text(&String::from_utf8_lossy(&bytes)),
+        }
+    }
+
+    // Every mask in the mask folder that can be read, named after its file and sorted by name
+    pub fn load_all() -> Vec<(String, Mask)> {
+        let mut paths: Vec<_> = match fs::read_dir(MASK_DIR) {
+            Ok(entries) => entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .collect(),
+            Err(_) => return Vec::new(),
+        };
+        paths.sort();
+
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let name = path.file_stem()?.to_string_lossy().into_owned();
+
+                match Mask::load(&path) {
+                    Ok(mask) => Some((name, mask)),
+                    Err(err) => {
+                        warn!("Skipping mask {}: {err}", path.display());
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.cells[y * self.width + x]
+    }
+
+    // Scales the mask so that its longer side is `size` cells, keeping only its largest
+    // connected part, as cells cut off from the rest could never be reached
+    pub fn fit(&self, size: usize) -> Mask {
+        let scale = size as f32 / self.width.max(self.height) as f32;
+        let width = ((self.width as f32 * scale).round() as usize).clamp(1, size);
+        let height = ((self.height as f32 * scale).round() as usize).clamp(1, size);
+        let sample = |i: usize, cells: usize, from: usize| {
+            (((i as f32 + 0.5) * from as f32 / cells as f32) as usize).min(from - 1)
+        };
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                self.contains(sample(x, width, self.width), sample(y, height, self.height))
+            })
+            .collect();
+
+        let fitted = Mask {
+            width,
+            height,
+            cells,
+        };
+
+        // Scaling down can lose every cell of a thin shape, in which case it is kept whole
+        fitted
+            .largest_region()
+            .unwrap_or_else(|| Mask::full(width, height))
+    }
+
+    // The biggest group of cells joined through their sides
+    pub fn largest_region(&self) -> Option<Mask> {
+        let mut region = vec![usize::MAX; self.cells.len()];
+        let mut sizes = Vec::new();
+
+        for start in 0..self.cells.len() {
+            if !self.cells[start] || region[start] != usize::MAX {
+                continue;
+            }
+
+            let mut queue = VecDeque::from([start]);
+            region[start] = sizes.len();
+            let mut size = 0;
+
+            while let Some(i) = queue.pop_front() {
+                size += 1;
+                let (x, y) = (i % self.width, i / self.width);
+                let sides = [
+                    (x > 0).then(|| i - 1),
+                    (x + 1 < self.width).then(|| i + 1),
+                    (y > 0).then(|| i - self.width),
+                    (y + 1 < self.height).then(|| i + self.width),
+                ];
+
+                for next in sides.into_iter().flatten() {
+                    if self.cells[next] && region[next] == usize::MAX {
+                        region[next] = sizes.len();
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            sizes.push(size);
+        }
+
+        let largest = (0..sizes.len()).max_by_key(|&i| (sizes[i], usize::MAX - i))?;
+
+        Some(Mask {
+            width: self.width,
+            height: self.height,
+            cells: region.iter().map(|&r| r == largest).collect(),
+        })
+    }
+
+    // Drops the empty rows and columns around the cells
+    pub fn cropped(self) -> Result<Mask, MaskError> {
+        let filled = |x: usize, y: usize| self.cells[y * self.width + x];
+        let columns: Vec<usize> = (0..self.width)
+            .filter(|&x| (0..self.height).any(|y| filled(x, y)))
+            .collect();
+        let rows: Vec<usize> = (0..self.height)
+            .filter(|&y| (0..self.width).any(|x| filled(x, y)))
+            .collect();
+
+        let (Some(&left), Some(&right), Some(&top), Some(&bottom)) =
+            (columns.first(), columns.last(), rows.first(), rows.last())
+        else {
+            return Err(MaskError::Empty);
+        };
+
+        Ok(Mask {
+            width: right - left + 1,
+            height: bottom - top + 1,
+            cells: (top..=bottom)
+                .flat_map(|y| (left..=right).map(move |x| (x, y)))
+                .map(|(x, y)| filled(x, y))
+                .collect(),
+        })
+    }
+}
+
+// Which pixels of a PNG image are dark and opaque, row by row from the top, along with its
+// width and height
+pub fn dark_pixels(bytes: &[u8]) -> Result<(usize, usize, Vec<bool>), MaskError> {
+    let image = Image::from_buffer(
+        bytes,
+        ImageType::Extension("png"),
+        CompressedImageFormats::NONE,
+        false,
+    )
+    .map_err(|err| MaskError::Image(err.to_string()))?;
+    let size = image.size();
+    let (width, height) = (size.x as usize, size.y as usize);
+
+    if width * height == 0 {
+        return Err(MaskError::Empty);
+    }
+
+    // 8 bit images are all widened to RGBA, while 16 bit grey ones keep their channels.
+    // Those are little endian, so only the high byte of each is looked at.
+    let (channels, bytes) = match image.texture_descriptor.format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => (4, 1),
+        TextureFormat::R16Uint => (1, 2),
+        TextureFormat::Rg16Uint => (2, 2),
+        TextureFormat::Rgba16Uint => (4, 2),
+        format => return Err(MaskError::Image(format!("unsupported format {format:?}"))),
+    };
+    let cells = image
+        .data
+        .chunks(channels * bytes)
+        .map(|pixel| {
+            let value = |i: usize| pixel[i * bytes + bytes - 1] as u32;
+            let (light, alpha) = match channels {
+                1 => (value(0), 255),
+                2 => (value(0), value(1)),
+                _ => ((value(0) + value(1) + value(2)) / 3, value(3)),
+            };
+
+            light < 128 && alpha >= 128
+        })
+        .collect();
+
+    Ok((width, height, cells))
+}
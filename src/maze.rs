@@ -1,137 +1,1752 @@
+use crate::mask::Mask;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::fmt;
 
-#[derive(Debug, Clone)]
+// Cells are stored row by row in one buffer, so a cell is `cells[y * width + x]`. Floors are
+// stacked on top of each other in that buffer, each `floor_height` rows tall. Shaped mazes
+// mark the cells outside their mask as void, which are treated as outside the maze.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Maze {
-    pub path: Vec<Vec<Cell>>,
+    width: usize,
+    height: usize,
+    #[serde(default)]
+    grid: Grid,
+    #[serde(default = "single_floor")]
+    floors: usize,
+    cells: Vec<Cell>,
+    // The entrance and exit cells with the side each is open on, if they were placed
+    #[serde(default)]
+    doors: Option<[((usize, usize), Direction); 2]>,
+    // How they were chosen, so that the same maze can be generated again
+    #[serde(default)]
+    placement: Doors,
+    #[serde(default)]
+    coin_placement: CoinPlacement,
+    #[serde(default)]
+    hazards: Hazards,
+    pub seed: u64,
+    // Drawn in the editor rather than generated, so it can't be made again from its seed
+    #[serde(default)]
+    edited: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MazeTextError {
+    Empty,
+    Grid,
+    Door(char),
+}
+
+impl fmt::Display for MazeTextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MazeTextError::Empty => write!(f, "maze has no cells"),
+            MazeTextError::Grid => write!(f, "walls don't line up on a grid of cells"),
+            MazeTextError::Door(door) => {
+                write!(f, "maze needs one {door} in its outside wall")
+            }
+        }
+    }
+}
+
+// Where the entrance and exit go. Sides puts them on the middle of those edges of the bottom
+// and top floors, or the nearest cells to it in a shaped maze, while the others pick from
+// all the cells on the outside. Hex mazes only have doors on their left and right edges, and
+// polar ones only on the outer rim and into the middle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Doors {
+    Sides(Direction, Direction),
+    Random,
+    Farthest,
+}
+
+impl Default for Doors {
+    fn default() -> Doors {
+        Doors::Sides(Direction::West, Direction::East)
+    }
+}
+
+impl Doors {
+    pub const ALL: [Doors; 6] = [
+        Doors::Sides(Direction::West, Direction::East),
+        Doors::Sides(Direction::East, Direction::West),
+        Doors::Sides(Direction::North, Direction::South),
+        Doors::Sides(Direction::South, Direction::North),
+        Doors::Random,
+        Doors::Farthest,
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            Doors::Sides(entrance, exit) => format!("{entrance:?} to {exit:?}"),
+            Doors::Random => "Random".to_string(),
+            Doors::Farthest => "Farthest".to_string(),
+        }
+    }
+}
+
+// Which dead ends get the coins. Shuffled takes them at random, a floor at a time. Spread
+// keeps them as far from each other and the entrance as it can, Detour takes the ends of the
+// longest branches off the solution and Spaced the branches leaving it at even steps along
+// it. Route picks branches so that collecting every coin takes about the given percentage
+// of the length of the solution.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CoinPlacement {
+    #[default]
+    Shuffled,
+    Spread,
+    Detour,
+    Spaced,
+    Route(usize),
+}
+
+impl CoinPlacement {
+    pub const ALL: [CoinPlacement; 7] = [
+        CoinPlacement::Shuffled,
+        CoinPlacement::Spread,
+        CoinPlacement::Detour,
+        CoinPlacement::Spaced,
+        CoinPlacement::Route(150),
+        CoinPlacement::Route(200),
+        CoinPlacement::Route(300),
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            CoinPlacement::Shuffled => "Random".to_string(),
+            CoinPlacement::Spread => "Spread out".to_string(),
+            CoinPlacement::Detour => "Detours".to_string(),
+            CoinPlacement::Spaced => "Evenly spaced".to_string(),
+            CoinPlacement::Route(percent) => format!("Route x{}", *percent as f32 / 100.),
+        }
+    }
+}
+
+// Where spikes and collapsing floors may go. OffPath keeps them out of the way through and
+// the branches leading to the coins, so a careful player never has to cross one, while
+// OnPath puts them anywhere.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Hazards {
+    #[default]
+    Off,
+    OffPath,
+    OnPath,
+}
+
+impl Hazards {
+    pub const ALL: [Hazards; 3] = [Hazards::Off, Hazards::OffPath, Hazards::OnPath];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hazards::Off => "Off",
+            Hazards::OffPath => "Off the path",
+            Hazards::OnPath => "On the path",
+        }
+    }
+}
+
+// The shape of the cells. Hex rows are offset so that odd rows sit half a cell to the right.
+// Polar rows are rings counted from the middle out, with `x` going round each ring. North
+// and South lead in and out, and where a ring has twice the cells of the one inside it,
+// odd cells lead in through NorthWest and their parent leads out to them through SouthEast.
+// Weave mazes are square, but passages may tunnel under straight corridors that cross them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Grid {
+    #[default]
+    Square,
+    Hex,
+    Polar,
+    Weave,
+}
+
+impl Grid {
+    pub const ALL: [Grid; 4] = [Grid::Square, Grid::Hex, Grid::Polar, Grid::Weave];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Grid::Square => "Square",
+            Grid::Hex => "Hex",
+            Grid::Polar => "Polar",
+            Grid::Weave => "Weave",
+        }
+    }
+
+    // The sides the entrance and exit can be on, with the ones they go on by default first
+    fn door_sides(&self, exit: bool) -> &'static [Direction] {
+        match (self, exit) {
+            (Grid::Polar, false) => &[Direction::South],
+            (Grid::Polar, true) => &[Direction::North],
+            (Grid::Hex, false) => &[Direction::West, Direction::East],
+            (Grid::Hex, true) => &[Direction::East, Direction::West],
+            (_, false) => &[
+                Direction::West,
+                Direction::East,
+                Direction::North,
+                Direction::South,
+            ],
+            (_, true) => &[
+                Direction::East,
+                Direction::West,
+                Direction::North,
+                Direction::South,
+            ],
+        }
+    }
+
+    // The sides a cell has on its own floor, in the order the generator tries them
+    pub fn directions(&self) -> &'static [Direction] {
+        match self {
+            Grid::Square | Grid::Weave => &Direction::ALL[..4],
+            Grid::Hex => &[
+                Direction::NorthEast,
+                Direction::East,
+                Direction::SouthEast,
+                Direction::SouthWest,
+                Direction::West,
+                Direction::NorthWest,
+            ],
+            Grid::Polar => &[
+                Direction::North,
+                Direction::NorthWest,
+                Direction::South,
+                Direction::SouthEast,
+                Direction::East,
+                Direction::West,
+            ],
+        }
+    }
+
+    // The sides to try, in order, to keep a hand on the right-hand wall while walking `facing`.
+    // That is every side going anticlockwise from the one just walked in through, with the
+    // stairs counted as the last two sides.
+    pub fn right_hand_turns(&self, facing: Direction) -> impl Iterator<Item = Direction> {
+        let mut clockwise = match self {
+            Grid::Square | Grid::Weave => vec![
+                Direction::North,
+                Direction::East,
+                Direction::South,
+                Direction::West,
+            ],
+            Grid::Hex => self.directions().to_vec(),
+            // As seen from a cell at the top of the rings, with `x` growing to the left
+            Grid::Polar => vec![
+                Direction::SouthEast,
+                Direction::South,
+                Direction::West,
+                Direction::North,
+                Direction::NorthWest,
+                Direction::East,
+            ],
+        };
+        clockwise.extend([Direction::Up, Direction::Down]);
+        let sides = clockwise.len();
+        let back = clockwise
+            .iter()
+            .position(|dir| *dir == facing.opposite())
+            .unwrap_or(0);
+
+        (0..sides).map(move |i| clockwise[(back + sides - 1 - i) % sides])
+    }
+}
+
+// The number of cells in a ring of a polar maze. Rings are as thick as the middle is wide,
+// and a ring splits its cells in two once they would get more than twice as long as that.
+pub fn ring_len(ring: usize) -> usize {
+    let mut cells = 6;
+
+    for ring in 1..=ring {
+        if std::f32::consts::TAU * (ring + 1) as f32 / cells as f32 > 2. {
+            cells *= 2;
+        }
+    }
+
+    cells
+}
+
+pub(crate) fn single_floor() -> usize {
+    1
 }
 
 impl Maze {
+    // A random square maze on one floor, as mazes were before there were other grids
     pub fn new(size: usize, coins: usize) -> Maze {
-        let mut visited: Vec<Point> = Vec::new();
-
-        let mut path: Vec<Vec<Cell>> = vec![vec![Cell::new(); size]; size];
-        let directions = vec![
-            Direction::North,
-            Direction::South,
-            Direction::East,
-            Direction::West,
-        ];
-        let mut rng = thread_rng();
+        Maze::generate(Grid::Square, 1, size, coins, thread_rng().gen())
+    }
 
-        visited.push(Point {
-            x: rng.gen_range(0..size) as i128,
-            y: rng.gen_range(0..size) as i128,
-        });
+    // The same size, coin count and seed always produce the same square maze
+    pub fn from_seed(size: usize, coins: usize, seed: u64) -> Maze {
+        Maze::generate(Grid::Square, 1, size, coins, seed)
+    }
+
+    pub fn generate(grid: Grid, floors: usize, size: usize, coins: usize, seed: u64) -> Maze {
+        Generator::new(grid, floors, size, seed).finish(coins)
+    }
+
+    pub fn shaped(grid: Grid, floors: usize, mask: &Mask, coins: usize, seed: u64) -> Maze {
+        Generator::shaped(grid, floors, mask, seed).finish(coins)
+    }
+
+    // A square grid on one floor with every wall up and no doors yet, to be opened up by hand
+    // or from a drawing of a maze
+    pub fn closed(width: usize, height: usize) -> Maze {
+        Maze {
+            width,
+            height,
+            grid: Grid::Square,
+            floors: 1,
+            cells: vec![Cell::new(); width * height],
+            doors: None,
+            placement: Doors::default(),
+            coin_placement: CoinPlacement::default(),
+            hazards: Hazards::default(),
+            seed: 0,
+            edited: true,
+        }
+    }
+
+    // A square room with walls only around the outside, to be drawn on in the editor. The
+    // doors go where they do in a generated maze.
+    pub fn blank(size: usize) -> Maze {
+        let mut maze = Maze::closed(size, size);
+
+        for (x, y) in (0..size).flat_map(|y| (0..size).map(move |x| (x, y))) {
+            maze.set_wall(x, y, Direction::East, false);
+            maze.set_wall(x, y, Direction::South, false);
+        }
+        maze.set_door(false, maze.entrance(), maze.entrance_side());
+        maze.set_door(true, maze.exit(), maze.exit_side());
+
+        maze
+    }
+
+    // Square mazes on one floor as text, two lines to a row of cells. Walls are `|` and
+    // `---` between `+` corners, a cell with a coin has an `o` in the middle, and an `S` and
+    // an `E` in the outside wall mark the entrance and exit. Anything else but a space is
+    // read as a wall too.
+    pub fn from_text(text: &str) -> Result<Maze, MazeTextError> {
+        let lines: Vec<Vec<char>> = text
+            .lines()
+            .map(|line| line.trim_end().chars().collect::<Vec<char>>())
+            .skip_while(|line| line.is_empty())
+            .collect();
+        let rows = lines
+            .iter()
+            .rposition(|line| !line.is_empty())
+            .map_or(0, |i| i + 1);
+        let columns = lines.iter().map(Vec::len).max().unwrap_or(0);
+
+        if rows < 3 || columns < 5 {
+            return Err(MazeTextError::Empty);
+        }
+        if rows % 2 == 0 || columns % 4 != 1 {
+            return Err(MazeTextError::Grid);
+        }
+
+        let at = |row: usize, column: usize| {
+            lines
+                .get(row)
+                .and_then(|line| line.get(column))
+                .copied()
+                .unwrap_or(' ')
+        };
+        let (width, height) = (columns / 4, rows / 2);
+        // The characters of the wall on the `direction` side of (x, y)
+        let wall = |x: usize, y: usize, direction: Direction| -> Vec<char> {
+            match direction {
+                Direction::North => (1..4).map(|i| at(2 * y, 4 * x + i)).collect(),
+                Direction::South => (1..4).map(|i| at(2 * y + 2, 4 * x + i)).collect(),
+                Direction::West => vec![at(2 * y + 1, 4 * x)],
+                _ => vec![at(2 * y + 1, 4 * x + 4)],
+            }
+        };
+        let mut maze = Maze::closed(width, height);
+        let mut doors = [Vec::new(), Vec::new()];
+
+        for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+            maze.cell_mut(x, y)
+                .set_coin(at(2 * y + 1, 4 * x + 2) == 'o');
+
+            for direction in Grid::Square.directions().iter().copied() {
+                let chars = wall(x, y, direction);
+                let door = ['S', 'E'].iter().position(|door| chars.contains(door));
+
+                match (maze.neighbour(x, y, direction), door) {
+                    (None, Some(door)) => doors[door].push(((x, y), direction)),
+                    (Some(_), Some(door)) => return Err(MazeTextError::Door(['S', 'E'][door])),
+                    (Some(_), None) if chars.iter().all(|c| *c == ' ') => {
+                        maze.cell_mut(x, y).carve(direction);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        match doors {
+            [entrance, exit] if entrance.len() == 1 && exit.len() == 1 => {
+                maze.set_door(false, entrance[0].0, entrance[0].1);
+                maze.set_door(true, exit[0].0, exit[0].1);
+            }
+            [entrance, _] if entrance.len() != 1 => return Err(MazeTextError::Door('S')),
+            _ => return Err(MazeTextError::Door('E')),
+        }
+
+        Ok(maze)
+    }
+
+    pub fn to_text(&self) -> String {
+        let door = |x: usize, y: usize, direction: Direction| {
+            if (x, y) == self.entrance() && direction == self.entrance_side() {
+                Some('S')
+            } else if (x, y) == self.exit() && direction == self.exit_side() {
+                Some('E')
+            } else {
+                None
+            }
+        };
+        let across = |x: usize, y: usize, direction: Direction| match door(x, y, direction) {
+            Some(door) => format!(" {door} "),
+            None if self.cell(x, y).is_open(direction) => "   ".to_string(),
+            None => "---".to_string(),
+        };
+        let down = |x: usize, y: usize, direction: Direction| match door(x, y, direction) {
+            Some(door) => door,
+            None if self.cell(x, y).is_open(direction) => ' ',
+            None => '|',
+        };
+        let mut text = String::new();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                text += &format!("+{}", across(x, y, Direction::North));
+            }
+            text += "+\n";
+
+            for x in 0..self.width {
+                let coin = if self.cell(x, y).coin() { 'o' } else { ' ' };
+                text += &format!("{} {coin} ", down(x, y, Direction::West));
+            }
+            text += &format!("{}\n", down(self.width - 1, y, Direction::East));
+        }
+
+        for x in 0..self.width {
+            text += &format!("+{}", across(x, self.height - 1, Direction::South));
+        }
+        text += "+\n";
+
+        text
+    }
+
+    // The cells of a shaped maze, which are the same on every floor. A mask that fills a
+    // rectangle still shapes the maze unless the rectangle is square.
+    pub fn mask(&self) -> Option<Mask> {
+        let floor = &self.cells[..self.width * self.floor_height()];
+        let square = self.grid == Grid::Polar || self.width == self.floor_height();
+
+        (!square || floor.iter().any(|cell| !cell.exists())).then(|| {
+            Mask::new(
+                self.width,
+                self.floor_height(),
+                floor.iter().map(Cell::exists).collect(),
+            )
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
 
-        while !visited.is_empty() {
-            let cell = visited.last().unwrap();
-            let mut directions_tmp = directions.clone();
-            directions_tmp.shuffle(&mut rng);
-
-            let mut found = false;
-
-            for dir in directions_tmp {
-                let nx = cell.x + dir.delta().x;
-                let ny = cell.y + dir.delta().y;
-
-                if nx >= 0
-                    && ny >= 0
-                    && nx < size as i128
-                    && ny < size as i128
-                    && path[ny as usize][nx as usize].is_zero()
-                {
-                    path[cell.y as usize][cell.x as usize].carve(dir);
-                    path[ny as usize][nx as usize].carve(dir.opposite());
-                    visited.push(Point { x: nx, y: ny });
-                    found = true;
+    // The number of rows on all floors together
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn floors(&self) -> usize {
+        self.floors
+    }
+
+    pub fn floor_height(&self) -> usize {
+        self.height / self.floors
+    }
+
+    pub fn floor(&self, y: usize) -> usize {
+        y / self.floor_height()
+    }
+
+    pub fn grid(&self) -> Grid {
+        self.grid
+    }
+
+    pub fn placement(&self) -> Doors {
+        self.placement
+    }
+
+    pub fn coin_placement(&self) -> CoinPlacement {
+        self.coin_placement
+    }
+
+    pub fn hazards(&self) -> Hazards {
+        self.hazards
+    }
+
+    pub fn edited(&self) -> bool {
+        self.edited
+    }
+
+    pub fn coins(&self) -> usize {
+        self.cells.iter().filter(|cell| cell.coin()).count()
+    }
+
+    pub fn has_power_ups(&self) -> bool {
+        self.cells.iter().any(|cell| cell.power_up().is_some())
+    }
+
+    pub fn has_terrain(&self) -> bool {
+        self.cells
+            .iter()
+            .any(|cell| cell.terrain() != Terrain::Plain)
+    }
+
+    // How long walking `path` takes, counting the cells it enters in half plain cells
+    pub fn path_cost(&self, path: &[(usize, usize)]) -> usize {
+        path.iter()
+            .skip(1)
+            .map(|&(x, y)| self.cell(x, y).terrain().cost())
+            .sum()
+    }
+
+    pub fn cell(&self, x: usize, y: usize) -> Cell {
+        self.cells[y * self.width + x]
+    }
+
+    pub fn cell_mut(&mut self, x: usize, y: usize) -> &mut Cell {
+        &mut self.cells[y * self.width + x]
+    }
+
+    // Puts up or takes down the wall on the `direction` side of (x, y), on both sides of it.
+    // There is always a wall round the outside, besides the doors.
+    pub fn set_wall(&mut self, x: usize, y: usize, direction: Direction, wall: bool) {
+        let Some((nx, ny)) = self.neighbour(x, y, direction) else {
+            return;
+        };
+
+        for ((x, y), direction) in [((x, y), direction), ((nx, ny), direction.opposite())] {
+            match wall {
+                true => self.cell_mut(x, y).close(direction),
+                false => self.cell_mut(x, y).carve(direction),
+            }
+        }
+    }
+
+    // Moves the entrance, or the exit, to the `side` of (x, y), which should face out of
+    // the maze
+    pub fn set_door(&mut self, exit: bool, (x, y): (usize, usize), side: Direction) {
+        let mut doors = self.doors.unwrap_or([
+            (self.entrance(), self.entrance_side()),
+            (self.exit(), self.exit_side()),
+        ]);
+        let (old, old_side) = doors[exit as usize];
+
+        if doors[!exit as usize] != (old, old_side) {
+            self.cell_mut(old.0, old.1).close(old_side);
+        }
+
+        doors[exit as usize] = ((x, y), side);
+        self.cell_mut(x, y).carve(side);
+        self.doors = Some(doors);
+    }
+
+    // How many cells row `y` has, which for polar mazes is less than `width` on inner rings
+    pub fn row_len(&self, y: usize) -> usize {
+        match self.grid {
+            Grid::Polar => ring_len(y % self.floor_height()),
+            _ => self.width,
+        }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Cell]> {
+        self.cells
+            .chunks(self.width)
+            .enumerate()
+            .map(|(y, row)| &row[..self.row_len(y)])
+    }
+
+    // The cells row by row, in the shape mazes used to keep them in
+    pub fn path(&self) -> Vec<Vec<Cell>> {
+        self.rows().map(|row| row.to_vec()).collect()
+    }
+
+    // The entrance is on the bottom floor and the exit on the top one
+    pub fn entrance(&self) -> (usize, usize) {
+        match self.doors {
+            Some([(entrance, _), _]) => entrance,
+            None => self.default_door(false),
+        }
+    }
+
+    pub fn exit(&self) -> (usize, usize) {
+        match self.doors {
+            Some([_, (exit, _)]) => exit,
+            None => self.default_door(true),
+        }
+    }
+
+    // Which side of the entrance and exit cells is open to the outside
+    pub fn entrance_side(&self) -> Direction {
+        match self.doors {
+            Some([(_, side), _]) => side,
+            None => self.grid.door_sides(false)[0],
+        }
+    }
+
+    pub fn exit_side(&self) -> Direction {
+        match self.doors {
+            Some([_, (_, side)]) => side,
+            None => self.grid.door_sides(true)[0],
+        }
+    }
+
+    // Mazes go from the middle of the left edge to the middle of the right, or from the outer
+    // rim into the middle
+    fn default_door(&self, exit: bool) -> (usize, usize) {
+        let floor_height = self.floor_height();
+        let top = (self.floors - 1) * floor_height;
+
+        match (self.grid, exit) {
+            (Grid::Polar, false) => (0, floor_height - 1),
+            (Grid::Polar, true) => (0, top),
+            (_, false) => self.edge_cell(0, 0..self.width),
+            (_, true) => self.edge_cell(top, (0..self.width).rev()),
+        }
+    }
+
+    // The outward sides of cells on `floor` that a door could go in
+    fn door_spots(&self, floor: usize, exit: bool) -> Vec<((usize, usize), Direction)> {
+        let floor_height = self.floor_height();
+        let base = floor * floor_height;
+
+        (base..base + floor_height)
+            .flat_map(|y| (0..self.row_len(y)).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.cell(x, y).exists())
+            .flat_map(|(x, y)| {
+                self.grid
+                    .door_sides(exit)
+                    .iter()
+                    .filter(move |&&dir| self.neighbour(x, y, dir).is_none())
+                    .map(move |&dir| ((x, y), dir))
+            })
+            .collect()
+    }
+
+    // Leaves out the spots on stairs, unless there is nowhere else
+    fn off_stairs(
+        &self,
+        spots: &[((usize, usize), Direction)],
+    ) -> Vec<((usize, usize), Direction)> {
+        let off_stairs: Vec<_> = spots
+            .iter()
+            .copied()
+            .filter(|((x, y), _)| {
+                let cell = self.cell(*x, *y);
+                !cell.is_open(Direction::Up) && !cell.is_open(Direction::Down)
+            })
+            .collect();
+
+        match off_stairs.is_empty() {
+            true => spots.to_vec(),
+            false => off_stairs,
+        }
+    }
+
+    // The door on `side` nearest the middle of that edge, counting rows and columns in from
+    // the edge first, which puts the default doors where `default_door` does
+    fn side_door(
+        &self,
+        spots: &[((usize, usize), Direction)],
+        side: Direction,
+    ) -> Option<((usize, usize), Direction)> {
+        let floor_height = self.floor_height();
+        let (width, height) = (self.width, floor_height);
+
+        spots
+            .iter()
+            .copied()
+            .filter(|(_, dir)| *dir == side)
+            .min_by_key(|&((x, y), _)| {
+                let y = y % floor_height;
+
+                match side {
+                    Direction::West => (x, y.abs_diff(height / 2)),
+                    Direction::East => (width - 1 - x, y.abs_diff(height / 2)),
+                    Direction::North => (y, x.abs_diff(width / 2)),
+                    _ => (height - 1 - y, x.abs_diff(width / 2)),
+                }
+            })
+    }
+
+    // How many steps every cell is from `from`, or `usize::MAX` if it can't be reached
+    fn distances(&self, from: (usize, usize)) -> Vec<usize> {
+        self.off_path(&[from])
+            .into_iter()
+            .map(|(distance, _)| distance)
+            .collect()
+    }
+
+    // How many steps every cell is from the nearest cell of `path`, and the index in `path`
+    // of that cell, or `usize::MAX` for both if it can't be reached
+    fn off_path(&self, path: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let index = |(x, y): (usize, usize)| y * self.width + x;
+        let mut off = vec![(usize::MAX, usize::MAX); self.cells.len()];
+        let mut queue = VecDeque::new();
+
+        for (i, &cell) in path.iter().enumerate() {
+            off[index(cell)] = (0, i);
+            queue.push_back(cell);
+        }
+
+        while let Some(current) = queue.pop_front() {
+            let (distance, branch) = off[index(current)];
+
+            for next in self.neighbours(current.0, current.1) {
+                if off[index(next)].0 == usize::MAX {
+                    off[index(next)] = (distance + 1, branch);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        off
+    }
+
+    // Lays short patches of mud, ice and boost pads along the passages, keeping clear of the
+    // doors, the stairs and the crossings
+    fn scatter_terrain(&mut self, rng: &mut StdRng) {
+        let doors = [self.entrance(), self.exit()];
+        let free = |maze: &Maze, (x, y): (usize, usize)| {
+            let cell = maze.cell(x, y);
+
+            cell.exists()
+                && cell.terrain() == Terrain::Plain
+                && cell.tunnel().is_none()
+                && !cell.is_open(Direction::Up)
+                && !cell.is_open(Direction::Down)
+                && !doors.contains(&(x, y))
+        };
+        let spots: Vec<(usize, usize)> = (0..self.height)
+            .flat_map(|y| (0..self.row_len(y)).map(move |x| (x, y)))
+            .filter(|&cell| free(self, cell))
+            .collect();
+
+        for _ in 0..spots.len() / 10 {
+            let mut cell = *spots.choose(rng).expect("there are free cells");
+            let terrain = *[Terrain::Mud, Terrain::Ice, Terrain::Boost]
+                .choose(rng)
+                .expect("there is terrain to choose from");
+
+            for _ in 0..rng.gen_range(1..=3) {
+                if !free(self, cell) {
                     break;
                 }
+
+                self.cell_mut(cell.0, cell.1).set_terrain(terrain);
+
+                let next: Vec<_> = self.neighbours(cell.0, cell.1).collect();
+                match next.choose(rng) {
+                    Some(&next) => cell = next,
+                    None => break,
+                }
+            }
+        }
+    }
+
+    // Puts up to `coins` coins in dead ends where `placement` says
+    fn place_coins(&mut self, coins: usize, placement: CoinPlacement, rng: &mut StdRng) {
+        let mut dead_ends: Vec<usize> = (0..self.cells.len())
+            .filter(|&i| self.cells[i].is_dead_end())
+            .collect();
+
+        // Shuffling first breaks ties at random for the other placements too
+        dead_ends.shuffle(rng);
+
+        let coins = coins.min(dead_ends.len());
+        let coord = |i: usize| (i % self.width, i / self.width);
+        let path = self.solve(self.entrance(), self.exit()).unwrap_or_default();
+        let off = self.off_path(&path);
+
+        let chosen = match placement {
+            CoinPlacement::Shuffled => {
+                let cells_per_floor = self.cells.len() / self.floors;
+                let mut taken = vec![0; self.floors];
+                let mut rank = vec![0; self.cells.len()];
+                for &i in &dead_ends {
+                    rank[i] = taken[i / cells_per_floor];
+                    taken[i / cells_per_floor] += 1;
+                }
+                dead_ends.sort_by_key(|&i| rank[i]);
+                dead_ends.truncate(coins);
+                dead_ends
+            }
+            CoinPlacement::Spread => {
+                let mut nearest = self.distances(self.entrance());
+                let mut chosen = Vec::new();
+
+                while chosen.len() < coins {
+                    let Some(&next) = dead_ends
+                        .iter()
+                        .filter(|i| !chosen.contains(*i))
+                        .max_by_key(|&&i| nearest[i])
+                    else {
+                        break;
+                    };
+
+                    for (nearest, distance) in nearest.iter_mut().zip(self.distances(coord(next))) {
+                        *nearest = (*nearest).min(distance);
+                    }
+                    chosen.push(next);
+                }
+
+                chosen
+            }
+            CoinPlacement::Detour => {
+                dead_ends.sort_by_key(|&i| Reverse(off[i].0));
+                dead_ends.truncate(coins);
+                dead_ends
+            }
+            CoinPlacement::Spaced => (0..coins)
+                .map(|k| {
+                    let target = (k + 1) * path.len() / (coins + 1);
+                    let (pos, _) = dead_ends
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, &i)| (off[i].1.abs_diff(target), off[i].0))
+                        .expect("there are enough dead ends");
+
+                    dead_ends.swap_remove(pos)
+                })
+                .collect(),
+            CoinPlacement::Route(percent) => {
+                // Collecting a coin means walking there and back from the part of the maze
+                // the route already covers, so each coin adds twice the cells it takes to
+                // reach it from there
+                let length = path.len().saturating_sub(1);
+                let mut extra = (length * percent / 100).saturating_sub(length);
+                let mut covered: Vec<bool> =
+                    off.iter().map(|(distance, _)| *distance == 0).collect();
+                let branch = |i: usize, covered: &[bool]| -> Vec<(usize, usize)> {
+                    self.way_back(coord(i), &off)
+                        .into_iter()
+                        .take_while(|&(x, y)| !covered[y * self.width + x])
+                        .collect()
+                };
+                let mut chosen = Vec::new();
+
+                while chosen.len() < coins {
+                    let want = extra / (coins - chosen.len());
+                    let (pos, cells) = dead_ends
+                        .iter()
+                        .map(|&i| branch(i, &covered))
+                        .enumerate()
+                        .min_by_key(|(_, cells)| (2 * cells.len()).abs_diff(want))
+                        .expect("there are enough dead ends");
+
+                    for &(x, y) in &cells {
+                        covered[y * self.width + x] = true;
+                    }
+                    extra = extra.saturating_sub(2 * cells.len());
+                    chosen.push(dead_ends.swap_remove(pos));
+                }
+
+                chosen
+            }
+        };
+
+        for i in chosen {
+            self.cells[i].set_coin(true);
+        }
+        self.coin_placement = placement;
+    }
+
+    // The cells from `from` back to the nearest cell of the path `off` was measured from,
+    // not counting that one
+    fn way_back(&self, from: (usize, usize), off: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        let mut current = from;
+
+        while off[current.1 * self.width + current.0].0 != 0 {
+            cells.push(current);
+            let distance = off[current.1 * self.width + current.0].0;
+            current = self
+                .neighbours(current.0, current.1)
+                .find(|&(x, y)| off[y * self.width + x].0 + 1 == distance)
+                .expect("every cell off the path leads back to it");
+        }
+
+        cells
+    }
+
+    // Marks checkpoints a third and two thirds of the way through, then turns one in every
+    // twelve of the cells left where `hazards` allows into spikes or a collapsing floor.
+    // Neither goes on doors, stairs, crossings, coins or terrain.
+    fn place_hazards(&mut self, hazards: Hazards, rng: &mut StdRng) {
+        self.hazards = hazards;
+        if hazards == Hazards::Off {
+            return;
+        }
+
+        let doors = [self.entrance(), self.exit()];
+        let free = |maze: &Maze, (x, y): (usize, usize)| {
+            let cell = maze.cell(x, y);
+
+            cell.exists()
+                && !cell.coin()
+                && !cell.checkpoint()
+                && cell.terrain() == Terrain::Plain
+                && cell.tunnel().is_none()
+                && !cell.is_open(Direction::Up)
+                && !cell.is_open(Direction::Down)
+                && !doors.contains(&(x, y))
+        };
+        let path = self.solve(self.entrance(), self.exit()).unwrap_or_default();
+
+        for k in 1..=2 {
+            if let Some(&(x, y)) = path[k * path.len() / 3..]
+                .iter()
+                .find(|&&cell| free(self, cell))
+            {
+                self.cell_mut(x, y).set_checkpoint(true);
+            }
+        }
+
+        let mut needed = vec![false; self.cells.len()];
+        if hazards == Hazards::OffPath {
+            let off = self.off_path(&path);
+            let coins = (0..self.cells.len()).filter(|&i| self.cells[i].coin());
+            let branches =
+                coins.flat_map(|i| self.way_back((i % self.width, i / self.width), &off));
+
+            for (x, y) in path.iter().copied().chain(branches) {
+                needed[y * self.width + x] = true;
             }
+        }
+
+        let mut spots: Vec<(usize, usize)> = (0..self.height)
+            .flat_map(|y| (0..self.row_len(y)).map(move |x| (x, y)))
+            .filter(|&(x, y)| free(self, (x, y)) && !needed[y * self.width + x])
+            .collect();
+        spots.shuffle(rng);
+
+        for _ in 0..spots.len() / 12 {
+            let (x, y) = spots.pop().expect("there are enough free cells");
+            let hazard = *[Hazard::Spikes, Hazard::Collapsing]
+                .choose(rng)
+                .expect("there are hazards to choose from");
+            self.cell_mut(x, y).set_hazard(hazard);
+        }
+    }
+
+    // Drops a power-up in one in every thirty of the cells that nothing else is on
+    fn place_power_ups(&mut self, rng: &mut StdRng) {
+        let doors = [self.entrance(), self.exit()];
+        let mut spots: Vec<(usize, usize)> = (0..self.height)
+            .flat_map(|y| (0..self.row_len(y)).map(move |x| (x, y)))
+            .filter(|&(x, y)| {
+                let cell = self.cell(x, y);
+
+                cell.exists()
+                    && !cell.coin()
+                    && !cell.checkpoint()
+                    && cell.hazard() == Hazard::Safe
+                    && cell.terrain() == Terrain::Plain
+                    && cell.tunnel().is_none()
+                    && !cell.is_open(Direction::Up)
+                    && !cell.is_open(Direction::Down)
+                    && !doors.contains(&(x, y))
+            })
+            .collect();
+        spots.shuffle(rng);
+
+        for _ in 0..(spots.len() / 30).max(1).min(spots.len()) {
+            let (x, y) = spots.pop().expect("there are enough free cells");
+            let power_up = *PowerUp::ALL
+                .choose(rng)
+                .expect("there are power-ups to choose from");
+            self.cell_mut(x, y).set_power_up(Some(power_up));
+        }
+    }
+
+    // Opens the entrance and exit where `placement` says
+    fn place_doors(&mut self, placement: Doors, rng: &mut StdRng) {
+        let entrances = self.door_spots(0, false);
+        let exits = self.door_spots(self.floors - 1, true);
+        let default = [
+            (self.default_door(false), self.grid.door_sides(false)[0]),
+            (self.default_door(true), self.grid.door_sides(true)[0]),
+        ];
+
+        let placed = match placement {
+            Doors::Sides(..) if self.grid == Grid::Polar => None,
+            Doors::Sides(entrance, exit) => self
+                .side_door(&entrances, entrance)
+                .zip(self.side_door(&exits, exit))
+                .map(|(entrance, exit)| [entrance, exit]),
+            Doors::Random => self
+                .off_stairs(&entrances)
+                .choose(rng)
+                .copied()
+                .zip(self.off_stairs(&exits).choose(rng).copied())
+                .map(|(entrance, exit)| [entrance, exit]),
+            Doors::Farthest => {
+                let exits = self.off_stairs(&exits);
+                let index = |(x, y): (usize, usize)| y * self.width + x;
+
+                self.off_stairs(&entrances)
+                    .into_iter()
+                    .filter_map(|entrance| {
+                        let distance = self.distances(entrance.0);
+                        let exit = exits
+                            .iter()
+                            .copied()
+                            .max_by_key(|(cell, _)| distance[index(*cell)])?;
+
+                        Some((distance[index(exit.0)], [entrance, exit]))
+                    })
+                    .max_by_key(|(distance, _)| *distance)
+                    .map(|(_, doors)| doors)
+            }
+        };
+        let doors = placed.unwrap_or(default);
+
+        for ((x, y), side) in doors {
+            self.cell_mut(x, y).carve(side);
+        }
+
+        self.doors = Some(doors);
+        self.placement = placement;
+    }
+
+    // The cell closest to the middle row of the floor starting at row `base`, in the first
+    // of `columns` that has any cells, which is the middle of the edge unless it is shaped
+    fn edge_cell(&self, base: usize, mut columns: impl Iterator<Item = usize>) -> (usize, usize) {
+        let floor_height = self.floor_height();
+
+        columns
+            .find_map(|x| {
+                (base..base + floor_height)
+                    .filter(|&y| self.cell(x, y).exists())
+                    .min_by_key(|y| (y - base).abs_diff(floor_height / 2))
+                    .map(|y| (x, y))
+            })
+            .expect("a maze has cells")
+    }
+
+    // The sides of (x, y) that have a wall or passage. Polar cells only have the inward
+    // and outward sides that lead somewhere, besides the innermost and outermost rims.
+    pub fn sides(&self, x: usize, y: usize) -> impl Iterator<Item = Direction> + '_ {
+        let ring = y % self.floor_height();
+
+        self.grid.directions().iter().copied().filter(move |&dir| {
+            self.grid != Grid::Polar
+                || self.neighbour(x, y, dir).is_some()
+                || (dir == Direction::North && ring == 0)
+                || (dir == Direction::South && ring + 1 == self.floor_height())
+        })
+    }
+
+    // The cell next to (x, y) in `direction`, whether or not there is a wall between them.
+    // Only the stairs lead to another floor.
+    pub fn neighbour(&self, x: usize, y: usize, direction: Direction) -> Option<(usize, usize)> {
+        let floor_height = self.floor_height();
+        let (base, y) = (y - y % floor_height, y % floor_height);
+
+        match direction {
+            Direction::Up => {
+                let above = base + floor_height;
+                return (above < self.height).then_some((x, above + y));
+            }
+            Direction::Down => {
+                return base.checked_sub(floor_height).map(|below| (x, below + y));
+            }
+            _ => {}
+        }
+
+        if self.grid == Grid::Polar {
+            return self
+                .ring_neighbour(x, y, direction)
+                .map(|(x, y)| (x, base + y));
+        }
+
+        let delta = direction.delta();
+        let dx = match self.grid {
+            // A diagonal step only moves sideways when it leaves the row on its offset side
+            Grid::Hex if delta.y != 0 => match y % 2 {
+                1 => (delta.x + 1) / 2,
+                _ => (delta.x - 1) / 2,
+            },
+            _ => delta.x,
+        };
+        let nx = x.checked_add_signed(dx)?;
+        let ny = y.checked_add_signed(delta.y)?;
 
-            if !found {
-                visited.pop();
+        (nx < self.width && ny < floor_height && self.cell(nx, base + ny).exists())
+            .then_some((nx, base + ny))
+    }
+
+    // Works on rings counted from the middle of the floor (x, y) is on
+    fn ring_neighbour(&self, x: usize, y: usize, direction: Direction) -> Option<(usize, usize)> {
+        let cells = ring_len(y);
+        let inner = y.checked_sub(1).map(ring_len);
+        let outer = (y + 1 < self.floor_height()).then(|| ring_len(y + 1));
+
+        match direction {
+            Direction::East => Some(((x + 1) % cells, y)),
+            Direction::West => Some(((x + cells - 1) % cells, y)),
+            Direction::North if inner == Some(cells) => Some((x, y - 1)),
+            Direction::North if inner.is_some() && x.is_multiple_of(2) => Some((x / 2, y - 1)),
+            Direction::NorthWest
+                if inner.is_some_and(|inner| inner < cells) && !x.is_multiple_of(2) =>
+            {
+                Some((x / 2, y - 1))
+            }
+            Direction::South if outer == Some(cells) => Some((x, y + 1)),
+            Direction::South if outer.is_some() => Some((x * 2, y + 1)),
+            Direction::SouthEast if outer.is_some_and(|outer| outer > cells) => {
+                Some((x * 2 + 1, y + 1))
             }
+            _ => None,
+        }
+    }
+
+    // The sides (x, y) can be left through. A crossing is only left along its bridge, as the
+    // tunnel underneath doesn't lead into it.
+    pub fn ways(&self, x: usize, y: usize) -> impl Iterator<Item = Direction> {
+        let cell = self.cell(x, y);
+
+        cell.directions()
+            .filter(move |dir| !cell.tunnel().is_some_and(|tunnel| tunnel.contains(dir)))
+    }
+
+    // Where leaving (x, y) through `direction` leads, going under any crossings on the way
+    pub fn passage(&self, x: usize, y: usize, direction: Direction) -> Option<(usize, usize)> {
+        let mut next = self.neighbour(x, y, direction)?;
+
+        while self
+            .cell(next.0, next.1)
+            .tunnel()
+            .is_some_and(|tunnel| tunnel.contains(&direction))
+        {
+            next = self.neighbour(next.0, next.1, direction)?;
         }
 
-        path[(size as f32 / 2.).floor() as usize][0].carve(Direction::West);
-        path[(size as f32 / 2.).floor() as usize][size - 1].carve(Direction::East);
+        Some(next)
+    }
+
+    // The cells that can be walked to from (x, y) in one step
+    pub fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.ways(x, y)
+            .filter_map(move |dir| self.passage(x, y, dir))
+    }
+
+    // The shortest walk between two cells, both ends included
+    pub fn solve(&self, from: (usize, usize), to: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        let index = |(x, y): (usize, usize)| y * self.width + x;
+        let mut previous = vec![usize::MAX; self.cells.len()];
+        let mut queue = VecDeque::from([from]);
+        previous[index(from)] = index(from);
+
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut path = vec![to];
+                let mut i = index(to);
 
-        let mut coords: Vec<Point> = Vec::new();
-        for (i, row) in path.iter().enumerate() {
-            for (j, cell) in row.iter().enumerate() {
-                if cell.directions.len() == 1 {
-                    coords.push(Point {
-                        x: j as i128,
-                        y: i as i128,
-                    });
+                while i != index(from) {
+                    i = previous[i];
+                    path.push((i % self.width, i / self.width));
                 }
+
+                path.reverse();
+                return Some(path);
             }
+
+            for next in self.neighbours(current.0, current.1) {
+                if previous[index(next)] == usize::MAX {
+                    previous[index(next)] = index(current);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// One step of the randomized depth-first search that carves a maze
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Visit((usize, usize)),
+    Carve((usize, usize), Direction),
+    Backtrack((usize, usize)),
+}
+
+// Carves a maze one `Step` at a time, so the process can be shown as it happens. Floors are
+// carved one after the other, each joined to the one below by a single staircase, so all
+// floors together still form one maze without loops.
+pub struct Generator {
+    maze: Maze,
+    rng: StdRng,
+    visited: Vec<(usize, usize)>,
+    pending: Option<Step>,
+    floor: usize,
+    doors: Doors,
+    coin_placement: CoinPlacement,
+    terrain: bool,
+    hazards: Hazards,
+    power_ups: bool,
+}
+
+impl Generator {
+    pub fn new(grid: Grid, floors: usize, size: usize, seed: u64) -> Generator {
+        let width = match grid {
+            Grid::Polar => ring_len(size - 1),
+            _ => size,
+        };
+        let maze = Maze {
+            width,
+            height: size * floors,
+            grid,
+            floors,
+            cells: vec![Cell::new(); width * size * floors],
+            doors: None,
+            placement: Doors::default(),
+            coin_placement: CoinPlacement::default(),
+            hazards: Hazards::default(),
+            seed,
+            edited: false,
+        };
+
+        Generator::start(maze, seed)
+    }
+
+    // Only carves the cells in the largest connected part of `mask`, on every floor. Polar
+    // mazes have no rows to shape, so they take only the height of the mask.
+    pub fn shaped(grid: Grid, floors: usize, mask: &Mask, seed: u64) -> Generator {
+        let mask = match mask.largest_region() {
+            Some(mask) if grid != Grid::Polar => mask,
+            _ => return Generator::new(grid, floors, mask.height(), seed),
+        };
+        let (width, height) = (mask.width(), mask.height());
+        let cells = (0..height * floors)
+            .flat_map(|y| (0..width).map(move |x| (x, y % height)))
+            .map(|(x, y)| match mask.contains(x, y) {
+                true => Cell::new(),
+                false => Cell::void(),
+            })
+            .collect();
+        let maze = Maze {
+            width,
+            height: height * floors,
+            grid,
+            floors,
+            cells,
+            doors: None,
+            placement: Doors::default(),
+            coin_placement: CoinPlacement::default(),
+            hazards: Hazards::default(),
+            seed,
+            edited: false,
+        };
+
+        Generator::start(maze, seed)
+    }
+
+    pub fn with_doors(mut self, doors: Doors) -> Generator {
+        self.doors = doors;
+        self
+    }
+
+    pub fn with_coin_placement(mut self, coin_placement: CoinPlacement) -> Generator {
+        self.coin_placement = coin_placement;
+        self
+    }
+
+    pub fn with_terrain(mut self, terrain: bool) -> Generator {
+        self.terrain = terrain;
+        self
+    }
+
+    pub fn with_hazards(mut self, hazards: Hazards) -> Generator {
+        self.hazards = hazards;
+        self
+    }
+
+    pub fn with_power_ups(mut self, power_ups: bool) -> Generator {
+        self.power_ups = power_ups;
+        self
+    }
+
+    // Carves `maze` over again, step by step
+    pub fn replaying(maze: &Maze) -> Generator {
+        match maze.mask() {
+            Some(mask) => Generator::shaped(maze.grid, maze.floors, &mask, maze.seed),
+            None => Generator::new(maze.grid, maze.floors, maze.floor_height(), maze.seed),
+        }
+    }
+
+    fn start(maze: Maze, seed: u64) -> Generator {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (x, y) = (
+            rng.gen_range(0..maze.width),
+            rng.gen_range(0..maze.floor_height()),
+        );
+        let start = (x % maze.row_len(y), y);
+        let start = match maze.cell(start.0, start.1).exists() {
+            true => start,
+            false => maze.entrance(),
+        };
+
+        Generator {
+            maze,
+            rng,
+            visited: vec![start],
+            pending: Some(Step::Visit(start)),
+            floor: 0,
+            doors: Doors::default(),
+            coin_placement: CoinPlacement::default(),
+            terrain: false,
+            hazards: Hazards::default(),
+            power_ups: false,
+        }
+    }
+
+    // Once a floor is carved, puts stairs up from one of its dead ends and carves the next
+    // floor from the top of them
+    fn next_floor(&mut self) -> Option<Step> {
+        if self.floor + 1 >= self.maze.floors {
+            return None;
+        }
+
+        let (x, y) = self.stairs();
+        let above = (x, y + self.maze.floor_height());
+        self.maze.cell_mut(x, y).carve(Direction::Up);
+        self.maze.cell_mut(above.0, above.1).carve(Direction::Down);
+        self.floor += 1;
+        self.visited.push(above);
+        self.pending = Some(Step::Visit(above));
+        Some(Step::Carve((x, y), Direction::Up))
+    }
+
+    // The top of the stairs can be the only way into part of a shaped floor, in which case
+    // carving goes on into that part from a cell next to it
+    fn hunt(&mut self) -> Option<Step> {
+        let maze = &self.maze;
+        let floor_height = maze.floor_height();
+        let base = self.floor * floor_height;
+        let (from, dir, to) = (base..base + floor_height)
+            .flat_map(|y| (0..maze.row_len(y)).map(move |x| (x, y)))
+            .filter(|&(x, y)| maze.cell(x, y).is_zero())
+            .find_map(|(x, y)| {
+                maze.grid.directions().iter().find_map(|&dir| {
+                    let (nx, ny) = maze.neighbour(x, y, dir)?;
+                    (!maze.cell(nx, ny).is_zero()).then_some(((nx, ny), dir.opposite(), (x, y)))
+                })
+            })?;
+
+        self.maze.cell_mut(from.0, from.1).carve(dir);
+        self.maze.cell_mut(to.0, to.1).carve(dir.opposite());
+        self.visited.push(to);
+        self.pending = Some(Step::Visit(to));
+        Some(Step::Carve(from, dir))
+    }
+
+    // Stepping on stairs always takes them, so they start from a dead end, where the player
+    // can only be going to take them, unless the floor is too small to have one to spare
+    fn stairs(&mut self) -> (usize, usize) {
+        let maze = &self.maze;
+        let floor_height = maze.floor_height();
+        let base = self.floor * floor_height;
+        let spots: Vec<(usize, usize)> = (base..base + floor_height)
+            .flat_map(|y| (0..maze.row_len(y)).map(move |x| (x, y)))
+            .filter(|&(x, y)| maze.cell(x, y).exists())
+            .collect();
+        let dead_ends: Vec<(usize, usize)> = spots
+            .iter()
+            .copied()
+            .filter(|&(x, y)| {
+                maze.cell(x, y).is_dead_end()
+                    && (x, y) != maze.entrance()
+                    && (x, y + floor_height) != maze.exit()
+            })
+            .collect();
+
+        [dead_ends, spots]
+            .into_iter()
+            .find_map(|spots| spots.choose(&mut self.rng).copied())
+            .expect("every floor has a cell")
+    }
+
+    // The maze as carved so far
+    pub fn maze(&self) -> &Maze {
+        &self.maze
+    }
+
+    // Carves whatever is left, then opens the entrance and exit, places the coins, lays the
+    // terrain, sets the hazards and drops the power-ups
+    pub fn finish(mut self, coins: usize) -> Maze {
+        for _ in &mut self {}
+
+        let mut maze = self.maze;
+        maze.place_doors(self.doors, &mut self.rng);
+        maze.place_coins(coins, self.coin_placement, &mut self.rng);
+
+        if self.terrain {
+            maze.scatter_terrain(&mut self.rng);
+        }
+        maze.place_hazards(self.hazards, &mut self.rng);
+
+        if self.power_ups {
+            maze.place_power_ups(&mut self.rng);
+        }
+
+        maze
+    }
+}
+
+impl Iterator for Generator {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        if let Some(step) = self.pending.take() {
+            return Some(step);
+        }
+
+        let Some(&(x, y)) = self.visited.last() else {
+            return self.hunt().or_else(|| self.next_floor());
+        };
+
+        // The top of the stairs gets a single way on, so it is a dead end like the bottom
+        let cell = self.maze.cell(x, y);
+        if cell.is_open(Direction::Down) && !cell.is_dead_end() {
+            self.visited.pop();
+            return Some(Step::Backtrack((x, y)));
         }
 
-        coords.shuffle(&mut rng);
+        let mut directions = self.maze.grid.directions().to_vec();
+        directions.shuffle(&mut self.rng);
+
+        let maze = &self.maze;
+        let next = directions.into_iter().find_map(|dir| {
+            let (nx, ny) = maze.neighbour(x, y, dir)?;
 
-        for i in 0..coins {
-            if coords.len() > i {
-                path[coords[i].y as usize][coords[i].x as usize].coin = true;
+            if maze.cell(nx, ny).is_zero() {
+                return Some((dir, (nx, ny), None));
+            }
+
+            // A weave maze can go on under a corridor that crosses its way
+            let beyond = maze
+                .neighbour(nx, ny, dir)
+                .filter(|_| maze.grid == Grid::Weave && maze.cell(nx, ny).can_tunnel(dir))?;
+
+            maze.cell(beyond.0, beyond.1)
+                .is_zero()
+                .then_some((dir, beyond, Some((nx, ny))))
+        });
+
+        match next {
+            Some((dir, (nx, ny), under)) => {
+                if let Some((ux, uy)) = under {
+                    self.maze.cell_mut(ux, uy).dig_tunnel(dir);
+                }
+
+                self.maze.cell_mut(x, y).carve(dir);
+                self.maze.cell_mut(nx, ny).carve(dir.opposite());
+                self.visited.push((nx, ny));
+                self.pending = Some(Step::Visit((nx, ny)));
+                Some(Step::Carve((x, y), dir))
+            }
+            None => {
+                self.visited.pop();
+                Some(Step::Backtrack((x, y)))
             }
         }
+    }
+}
+
+// The open sides of a cell, whether it holds a coin, whether a tunnel runs under it, what
+// its floor is made of and what lies in wait or can be picked up on it, packed into bit
+// flags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Cell(u32);
+
+// What the floor of a cell does to the player crossing it. Mud halves its speed and boost
+// pads double it, while ice keeps it sliding the way it was going when nothing is held.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Terrain {
+    #[default]
+    Plain,
+    Mud,
+    Ice,
+    Boost,
+}
+
+impl Terrain {
+    pub const ALL: [Terrain; 4] = [Terrain::Plain, Terrain::Mud, Terrain::Ice, Terrain::Boost];
 
-        Maze { path }
+    // The cheapest a cell can cost, so every step still to take costs at least this much
+    pub const MIN_COST: usize = 1;
+
+    pub fn speed(&self) -> f32 {
+        match self {
+            Terrain::Mud => 0.5,
+            Terrain::Boost => 2.,
+            Terrain::Plain | Terrain::Ice => 1.,
+        }
     }
+
+    // The time it takes to cross a cell, in half the time a plain one takes
+    pub fn cost(&self) -> usize {
+        match self {
+            Terrain::Mud => 4,
+            Terrain::Boost => 1,
+            Terrain::Plain | Terrain::Ice => 2,
+        }
+    }
+}
+
+// Spikes come up and go down again on a timer, and collapsing floors give way a moment
+// after the player steps on them. Either costs a life if the player is caught on it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Hazard {
+    #[default]
+    Safe,
+    Spikes,
+    Collapsing,
 }
 
-#[derive(Debug, Clone)]
-pub struct Cell {
-    pub directions: Vec<Direction>,
-    pub coin: bool,
+impl Hazard {
+    pub const ALL: [Hazard; 3] = [Hazard::Safe, Hazard::Spikes, Hazard::Collapsing];
+}
+
+// Phase lets the player walk through the walls inside the maze, Speed makes it faster and
+// Reveal lifts the fog, each for a short while after being picked up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUp {
+    Phase,
+    Speed,
+    Reveal,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 3] = [PowerUp::Phase, PowerUp::Speed, PowerUp::Reveal];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PowerUp::Phase => "Phase",
+            PowerUp::Speed => "Speed",
+            PowerUp::Reveal => "Reveal",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct Point {
-    x: i128,
-    y: i128,
+    pub x: isize,
+    pub y: isize,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Direction {
     North,
     South,
     East,
     West,
-}
-
-impl Default for Cell {
-    fn default() -> Self {
-        Self::new()
-    }
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+    Up,
+    Down,
 }
 
 impl Cell {
+    const COIN: u32 = 1 << 4;
+    const TUNNEL_NS: u32 = 1 << 11;
+    const TUNNEL_EW: u32 = 1 << 12;
+    const VOID: u32 = 1 << 13;
+    const TERRAIN_SHIFT: u32 = 14;
+    const TERRAIN: u32 = 0b11 << Self::TERRAIN_SHIFT;
+    const HAZARD_SHIFT: u32 = 16;
+    const HAZARD: u32 = 0b11 << Self::HAZARD_SHIFT;
+    const CHECKPOINT: u32 = 1 << 18;
+    const POWER_UP_SHIFT: u32 = 19;
+    const POWER_UP: u32 = 0b11 << Self::POWER_UP_SHIFT;
+    const FLAGS: u32 = Self::COIN
+        | Self::TUNNEL_NS
+        | Self::TUNNEL_EW
+        | Self::TERRAIN
+        | Self::HAZARD
+        | Self::CHECKPOINT
+        | Self::POWER_UP;
+
     pub fn new() -> Cell {
-        Cell {
-            directions: Vec::new(),
-            coin: false,
-        }
+        Cell(0)
+    }
+
+    // A cell outside the shape of a shaped maze
+    pub fn void() -> Cell {
+        Cell(Self::VOID)
+    }
+
+    pub fn exists(&self) -> bool {
+        self.0 & Self::VOID == 0
     }
 
     pub fn carve(&mut self, direction: Direction) {
-        self.directions.push(direction);
+        self.0 |= direction.bit();
+    }
+
+    pub fn close(&mut self, direction: Direction) {
+        self.0 &= !direction.bit();
+    }
+
+    pub fn is_open(&self, direction: Direction) -> bool {
+        self.0 & direction.bit() != 0
+    }
+
+    pub fn directions(&self) -> impl Iterator<Item = Direction> {
+        let cell = *self;
+
+        Direction::ALL
+            .into_iter()
+            .filter(move |dir| cell.is_open(*dir))
     }
 
     pub fn is_zero(&self) -> bool {
-        self.directions.is_empty()
+        self.0 & !Self::FLAGS == 0
+    }
+
+    pub fn is_dead_end(&self) -> bool {
+        self.exists() && (self.0 & !Self::FLAGS).count_ones() == 1
+    }
+
+    // A tunnel going `along` fits under a straight corridor running across it
+    pub fn can_tunnel(&self, along: Direction) -> bool {
+        let across = match along {
+            Direction::North | Direction::South => Direction::East.bit() | Direction::West.bit(),
+            Direction::East | Direction::West => Direction::North.bit() | Direction::South.bit(),
+            _ => return false,
+        };
+
+        self.0 & !Self::FLAGS == across
+    }
+
+    // Opens both ends of a tunnel going `along` under the cell, which makes it a crossing
+    pub fn dig_tunnel(&mut self, along: Direction) {
+        self.carve(along);
+        self.carve(along.opposite());
+        self.0 |= match along {
+            Direction::North | Direction::South => Self::TUNNEL_NS,
+            _ => Self::TUNNEL_EW,
+        };
+    }
+
+    // The two sides joined by the tunnel under a crossing
+    pub fn tunnel(&self) -> Option<[Direction; 2]> {
+        if self.0 & Self::TUNNEL_NS != 0 {
+            Some([Direction::North, Direction::South])
+        } else if self.0 & Self::TUNNEL_EW != 0 {
+            Some([Direction::East, Direction::West])
+        } else {
+            None
+        }
+    }
+
+    pub fn coin(&self) -> bool {
+        self.0 & Self::COIN != 0
+    }
+
+    pub fn set_coin(&mut self, coin: bool) {
+        if coin {
+            self.0 |= Self::COIN;
+        } else {
+            self.0 &= !Self::COIN;
+        }
+    }
+
+    pub fn terrain(&self) -> Terrain {
+        Terrain::ALL[((self.0 & Self::TERRAIN) >> Self::TERRAIN_SHIFT) as usize]
+    }
+
+    pub fn set_terrain(&mut self, terrain: Terrain) {
+        let bits = Terrain::ALL.iter().position(|t| *t == terrain).unwrap_or(0) as u32;
+        self.0 = (self.0 & !Self::TERRAIN) | bits << Self::TERRAIN_SHIFT;
+    }
+
+    pub fn hazard(&self) -> Hazard {
+        Hazard::ALL[((self.0 & Self::HAZARD) >> Self::HAZARD_SHIFT) as usize]
+    }
+
+    pub fn set_hazard(&mut self, hazard: Hazard) {
+        let bits = Hazard::ALL.iter().position(|h| *h == hazard).unwrap_or(0) as u32;
+        self.0 = (self.0 & !Self::HAZARD) | bits << Self::HAZARD_SHIFT;
+    }
+
+    pub fn power_up(&self) -> Option<PowerUp> {
+        match (self.0 & Self::POWER_UP) >> Self::POWER_UP_SHIFT {
+            0 => None,
+            bits => Some(PowerUp::ALL[bits as usize - 1]),
+        }
+    }
+
+    pub fn set_power_up(&mut self, power_up: Option<PowerUp>) {
+        let bits = power_up.map_or(0, |p| {
+            PowerUp::ALL.iter().position(|q| *q == p).unwrap_or(0) as u32 + 1
+        });
+        self.0 = (self.0 & !Self::POWER_UP) | bits << Self::POWER_UP_SHIFT;
+    }
+
+    pub fn checkpoint(&self) -> bool {
+        self.0 & Self::CHECKPOINT != 0
+    }
+
+    pub fn set_checkpoint(&mut self, checkpoint: bool) {
+        if checkpoint {
+            self.0 |= Self::CHECKPOINT;
+        } else {
+            self.0 &= !Self::CHECKPOINT;
+        }
     }
 }
 
-impl Direction{
+impl Direction {
+    pub const ALL: [Direction; 10] = [
+        Direction::North,
+        Direction::South,
+        Direction::East,
+        Direction::West,
+        Direction::NorthEast,
+        Direction::NorthWest,
+        Direction::SouthEast,
+        Direction::SouthWest,
+        Direction::Up,
+        Direction::Down,
+    ];
+
     pub fn delta(&self) -> Point {
         match self {
             Direction::North => Point { x: 0, y: -1 },
             Direction::South => Point { x: 0, y: 1 },
             Direction::East => Point { x: 1, y: 0 },
             Direction::West => Point { x: -1, y: 0 },
+            Direction::NorthEast => Point { x: 1, y: -1 },
+            Direction::NorthWest => Point { x: -1, y: -1 },
+            Direction::SouthEast => Point { x: 1, y: 1 },
+            Direction::SouthWest => Point { x: -1, y: 1 },
+            Direction::Up | Direction::Down => Point { x: 0, y: 0 },
         }
     }
 
@@ -141,6 +1756,28 @@ impl Direction{
             Direction::South => Direction::North,
             Direction::East => Direction::West,
             Direction::West => Direction::East,
+            Direction::NorthEast => Direction::SouthWest,
+            Direction::NorthWest => Direction::SouthEast,
+            Direction::SouthEast => Direction::NorthWest,
+            Direction::SouthWest => Direction::NorthEast,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+
+    // The coin sits at bit 4, between the square and the diagonal sides, with the stairs last
+    fn bit(&self) -> u32 {
+        match self {
+            Direction::North => 1,
+            Direction::South => 1 << 1,
+            Direction::East => 1 << 2,
+            Direction::West => 1 << 3,
+            Direction::NorthEast => 1 << 5,
+            Direction::NorthWest => 1 << 6,
+            Direction::SouthEast => 1 << 7,
+            Direction::SouthWest => 1 << 8,
+            Direction::Up => 1 << 9,
+            Direction::Down => 1 << 10,
         }
     }
-}
\ No newline at end of file
+}
@@ -1,9 +1,15 @@
+use crate::campaign::{CurrentLevel, LEVELS};
+use crate::mask::Mask;
+use crate::maze::Grid;
+use crate::save::SaveGame;
 use crate::utils::{
     colors::{BACKGROUND_COLOR, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON, TEXT_COLOR},
     despawn_screen,
-    vars::{GameState, MazeState},
+    vars::{DataDir, GameState, MazeState},
+    ButtonInteractions,
 };
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use std::time::Duration;
 
 pub struct MenuPlugin;
 
@@ -19,7 +25,27 @@ impl Plugin for MenuPlugin {
 struct OnMenuScreen;
 
 #[derive(Component)]
-struct MazeSize(usize);
+enum ButtonAction {
+    Play(usize),
+    Campaign,
+    Continue,
+    Editor,
+    Grid,
+    Floors,
+    Shape,
+    Settings,
+}
+
+#[derive(Component)]
+struct GridLabel;
+
+#[derive(Component)]
+struct FloorsLabel;
+
+#[derive(Component)]
+struct ShapeLabel;
+
+const MAX_FLOORS: usize = 3;
 
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Difficulty {
@@ -28,15 +54,32 @@ enum Difficulty {
     Hard,
 }
 
+// The shape label, kept apart from the other labels it shares `Text` with
+type ShapeLabelOnly = (With<ShapeLabel>, Without<GridLabel>, Without<FloorsLabel>);
+
+// The labels of the buttons that cycle through the options for the next maze
+#[derive(SystemParam)]
+struct OptionLabels<'w, 's> {
+    grid_label_query: Query<'w, 's, &'static mut Text, With<GridLabel>>,
+    floors_label_query: Query<'w, 's, &'static mut Text, (With<FloorsLabel>, Without<GridLabel>)>,
+    shape_label_query: Query<'w, 's, &'static mut Text, ShapeLabelOnly>,
+}
+
 fn button_system(
-    mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor, &MazeSize),
-        (Changed<Interaction>, With<Button>),
-    >,
+    mut commands: Commands,
+    mut interaction_query: ButtonInteractions<ButtonAction>,
+    labels: OptionLabels,
     mut maze_state: ResMut<MazeState>,
     mut game_state: ResMut<NextState<GameState>>,
+    data: Res<DataDir>,
 ) {
-    for (interaction, mut color, size) in &mut interaction_query {
+    let OptionLabels {
+        mut grid_label_query,
+        mut floors_label_query,
+        mut shape_label_query,
+    } = labels;
+
+    for (interaction, mut color, button_action) in &mut interaction_query {
         *color = match *interaction {
             Interaction::Clicked => PRESSED_BUTTON.into(),
             Interaction::Hovered => HOVERED_BUTTON.into(),
@@ -44,16 +87,89 @@ fn button_system(
         };
 
         if *interaction == Interaction::Clicked {
-            maze_state.size = size.0;
-            maze_state.stopwatch.reset();
-            maze_state.stopwatch.unpause();
-            maze_state.path = None;
-            game_state.set(GameState::Game);
+            match button_action {
+                ButtonAction::Play(size) => {
+                    maze_state.size = *size;
+                    maze_state.stopwatch.reset();
+                    maze_state.stopwatch.unpause();
+                    maze_state.ticks = 0;
+                    maze_state.path = None;
+                    maze_state.saved = None;
+                    game_state.set(GameState::Game);
+                }
+                ButtonAction::Continue => {
+                    if let Some(save) = SaveGame::load(&data) {
+                        maze_state.size = save.size;
+                        maze_state.grid = save.maze.grid();
+                        maze_state.floors = save.maze.floors();
+                        maze_state.coins = save.coins;
+                        maze_state.stopwatch.reset();
+                        maze_state
+                            .stopwatch
+                            .set_elapsed(Duration::from_secs_f32(save.elapsed));
+                        maze_state.stopwatch.unpause();
+                        maze_state.ticks = save.ticks;
+                        maze_state.path = Some(save.maze.clone());
+                        if let Some(level) = save.level {
+                            commands.insert_resource(CurrentLevel(level));
+                        }
+                        maze_state.saved = Some(save);
+                        SaveGame::remove(&data);
+                        game_state.set(GameState::Game);
+                    }
+                }
+                ButtonAction::Grid => {
+                    let next = Grid::ALL.iter().position(|grid| *grid == maze_state.grid);
+                    maze_state.grid = Grid::ALL[next.map_or(0, |i| (i + 1) % Grid::ALL.len())];
+
+                    for mut text in &mut grid_label_query {
+                        text.sections[0].value = format!("Grid: {}", maze_state.grid.name());
+                    }
+                }
+                ButtonAction::Floors => {
+                    maze_state.floors = maze_state.floors % MAX_FLOORS + 1;
+
+                    for mut text in &mut floors_label_query {
+                        text.sections[0].value = format!("Floors: {}", maze_state.floors);
+                    }
+                }
+                ButtonAction::Shape => {
+                    // Goes through the masks in order, then back to no shape
+                    let masks = Mask::load_all();
+                    let next = match &maze_state.shape {
+                        Some((name, _)) => masks
+                            .iter()
+                            .position(|(mask, _)| mask == name)
+                            .map_or(0, |i| i + 1),
+                        None => 0,
+                    };
+                    maze_state.shape = masks.into_iter().nth(next);
+
+                    for mut text in &mut shape_label_query {
+                        text.sections[0].value = shape_label(&maze_state);
+                    }
+                }
+                ButtonAction::Campaign => game_state.set(GameState::LevelSelect),
+                ButtonAction::Editor => game_state.set(GameState::Editor),
+                ButtonAction::Settings => game_state.set(GameState::Settings),
+            }
         }
     }
 }
 
-fn menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn shape_label(maze_state: &MazeState) -> String {
+    match &maze_state.shape {
+        Some((name, _)) => format!("Shape: {name}"),
+        None => "Shape: None".to_string(),
+    }
+}
+
+fn menu_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    maze_state: Res<MazeState>,
+    data: Res<DataDir>,
+) {
     commands
         .spawn((
             NodeBundle {
@@ -88,6 +204,43 @@ fn menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                         },
                     ));
 
+                    // Continuing a saved game, when there is one, and the editor
+                    parent.spawn(NodeBundle::default()).with_children(|parent| {
+                        let actions = match SaveGame::exists(&data) {
+                            true => vec![
+                                (ButtonAction::Continue, "Continue"),
+                                (ButtonAction::Editor, "Editor"),
+                            ],
+                            false => vec![(ButtonAction::Editor, "Editor")],
+                        };
+
+                        for (action, label) in actions {
+                            parent
+                                .spawn(ButtonBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Px(200.), Val::Px(45.)),
+                                        margin: UiRect::all(Val::Px(10.0)),
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        ..default()
+                                    },
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                })
+                                .insert(action)
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(
+                                        label,
+                                        TextStyle {
+                                            font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                            font_size: 30.,
+                                            color: TEXT_COLOR,
+                                        },
+                                    ));
+                                });
+                        }
+                    });
+
                     parent.spawn(TextBundle::from_section(
                         "Choose difficulty",
                         TextStyle {
@@ -97,44 +250,118 @@ fn menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                         },
                     ));
 
-                    for (difficulty, size) in [
-                        (Difficulty::Easy, 11),
-                        (Difficulty::Medium, 21),
-                        (Difficulty::Hard, 31),
-                    ] {
-                        parent
-                            .spawn(ButtonBundle {
-                                style: Style {
-                                    size: Size::new(Val::Px(200.), Val::Px(65.)),
-                                    margin: UiRect::all(Val::Px(20.0)),
-                                    justify_content: JustifyContent::Center,
-                                    align_items: AlignItems::Center,
-                                    flex_direction: FlexDirection::Column,
-                                    ..default()
-                                },
-                                background_color: NORMAL_BUTTON.into(),
-                                ..default()
-                            })
-                            .insert(MazeSize(size))
-                            .with_children(|parent| {
-                                parent.spawn(TextBundle::from_section(
-                                    format!("{difficulty:?}"),
-                                    TextStyle {
-                                        font: asset_server.load("fonts/PixeloidSansBold.ttf"),
-                                        font_size: 30.,
-                                        color: TEXT_COLOR,
+                    // The difficulties and the campaign share a row, like the options below
+                    parent.spawn(NodeBundle::default()).with_children(|parent| {
+                        let choices = [
+                            (Difficulty::Easy, 11),
+                            (Difficulty::Medium, 21),
+                            (Difficulty::Hard, 31),
+                        ]
+                        .map(|(difficulty, size)| {
+                            (
+                                ButtonAction::Play(size),
+                                format!("{difficulty:?}"),
+                                format!("{size} x {size}"),
+                            )
+                        });
+                        let campaign = (
+                            ButtonAction::Campaign,
+                            "Campaign".to_string(),
+                            format!("{} levels", LEVELS.len()),
+                        );
+
+                        for (action, label, detail) in choices.into_iter().chain([campaign]) {
+                            parent
+                                .spawn(ButtonBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Px(200.), Val::Px(65.)),
+                                        margin: UiRect::all(Val::Px(10.0)),
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        flex_direction: FlexDirection::Column,
+                                        ..default()
                                     },
-                                ));
-                                parent.spawn(TextBundle::from_section(
-                                    format!("{size} x {size}"),
-                                    TextStyle {
-                                        font: asset_server.load("fonts/PixeloidSansBold.ttf"),
-                                        font_size: 20.,
-                                        color: TEXT_COLOR,
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                })
+                                .insert(action)
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(
+                                        label,
+                                        TextStyle {
+                                            font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                            font_size: 30.,
+                                            color: TEXT_COLOR,
+                                        },
+                                    ));
+                                    parent.spawn(TextBundle::from_section(
+                                        detail,
+                                        TextStyle {
+                                            font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                            font_size: 20.,
+                                            color: TEXT_COLOR,
+                                        },
+                                    ));
+                                });
+                        }
+                    });
+
+                    // The grid, floors, shape and settings share a row to keep the menu within
+                    // the window
+                    parent.spawn(NodeBundle::default()).with_children(|parent| {
+                        for (action, label) in [
+                            (
+                                ButtonAction::Grid,
+                                format!("Grid: {}", maze_state.grid.name()),
+                            ),
+                            (
+                                ButtonAction::Floors,
+                                format!("Floors: {}", maze_state.floors),
+                            ),
+                            (ButtonAction::Shape, shape_label(&maze_state)),
+                            (ButtonAction::Settings, "Settings".to_string()),
+                        ] {
+                            let is_grid = matches!(action, ButtonAction::Grid);
+                            let is_floors = matches!(action, ButtonAction::Floors);
+                            let is_shape = matches!(action, ButtonAction::Shape);
+
+                            parent
+                                .spawn(ButtonBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Px(200.), Val::Px(45.)),
+                                        margin: UiRect::all(Val::Px(10.0)),
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        ..default()
                                     },
-                                ));
-                            });
-                    }
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                })
+                                .insert(action)
+                                .with_children(|parent| {
+                                    let mut text = parent.spawn(TextBundle::from_section(
+                                        label,
+                                        TextStyle {
+                                            font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                            font_size: 30.,
+                                            color: TEXT_COLOR,
+                                        },
+                                    ));
+
+                                    if is_grid {
+                                        text.insert(GridLabel);
+                                    }
+
+                                    if is_floors {
+                                        text.insert(FloorsLabel);
+                                    }
+
+                                    if is_shape {
+                                        text.insert(ShapeLabel);
+                                    }
+                                });
+                        }
+                    });
 
                     parent.spawn(TextBundle::from_section(
                         "A game by Mikkel Tønder",
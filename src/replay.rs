@@ -0,0 +1,454 @@
+use crate::game::InputSet;
+use crate::mask::Mask;
+use crate::maze::{CoinPlacement, Doors, Generator, Grid, Hazards, Maze};
+use crate::utils::{
+    colors::TEXT_COLOR,
+    despawn_screen,
+    vars::{DataDir, GameState, MazeState, HEIGHT, REPLAY_DIR, TIMESTEP},
+};
+use bevy::{prelude::*, sprite::Anchor};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(save_recording.in_schedule(OnEnter(GameState::EndGame)))
+            .add_systems((start_playback, replay_setup).in_schedule(OnEnter(GameState::Replay)))
+            .add_system(
+                playback_input
+                    .in_set(InputSet)
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .run_if(in_state(GameState::Replay)),
+            )
+            .add_systems(
+                (
+                    replay_controls,
+                    fast_forward.after(replay_controls),
+                    replay_hud,
+                )
+                    .distributive_run_if(in_state(GameState::Replay)),
+            )
+            .add_systems(
+                (despawn_screen::<OnReplayScreen>, reset_speed)
+                    .in_schedule(OnExit(GameState::Replay)),
+            )
+            .add_system(stop_playback.in_schedule(OnEnter(GameState::Menu)));
+    }
+}
+
+// The directional keys held during a single tick, packed into one byte
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InputState(u8);
+
+impl InputState {
+    const UP: u8 = 1;
+    const DOWN: u8 = 1 << 1;
+    const LEFT: u8 = 1 << 2;
+    const RIGHT: u8 = 1 << 3;
+
+    pub fn from_keyboard(keyboard_input: &Input<KeyCode>) -> InputState {
+        let mut bits = 0;
+
+        if keyboard_input.any_pressed([KeyCode::W, KeyCode::Up]) {
+            bits |= Self::UP;
+        }
+
+        if keyboard_input.any_pressed([KeyCode::S, KeyCode::Down]) {
+            bits |= Self::DOWN;
+        }
+
+        if keyboard_input.any_pressed([KeyCode::A, KeyCode::Left]) {
+            bits |= Self::LEFT;
+        }
+
+        if keyboard_input.any_pressed([KeyCode::D, KeyCode::Right]) {
+            bits |= Self::RIGHT;
+        }
+
+        InputState(bits)
+    }
+
+    // Holds whichever keys push towards `direction`
+    pub fn from_direction(direction: Vec2) -> InputState {
+        let mut bits = 0;
+
+        if direction.y > 0. {
+            bits |= Self::UP;
+        }
+
+        if direction.y < 0. {
+            bits |= Self::DOWN;
+        }
+
+        if direction.x < 0. {
+            bits |= Self::LEFT;
+        }
+
+        if direction.x > 0. {
+            bits |= Self::RIGHT;
+        }
+
+        InputState(bits)
+    }
+
+    pub fn direction(&self) -> Vec3 {
+        let mut direction = Vec3::ZERO;
+
+        if self.0 & Self::UP != 0 {
+            direction.y += 1.;
+        }
+
+        if self.0 & Self::DOWN != 0 {
+            direction.y -= 1.;
+        }
+
+        if self.0 & Self::LEFT != 0 {
+            direction.x -= 1.;
+        }
+
+        if self.0 & Self::RIGHT != 0 {
+            direction.x += 1.;
+        }
+
+        direction
+    }
+}
+
+// A run stored as the maze it was played on and every change of input, keyed by tick
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub seed: u64,
+    #[serde(default)]
+    pub grid: Grid,
+    #[serde(default = "crate::maze::single_floor")]
+    pub floors: usize,
+    #[serde(default)]
+    pub mask: Option<Mask>,
+    #[serde(default)]
+    pub doors: Doors,
+    #[serde(default)]
+    pub coin_placement: CoinPlacement,
+    #[serde(default)]
+    pub terrain: bool,
+    #[serde(default)]
+    pub hazards: Hazards,
+    #[serde(default)]
+    pub power_ups: bool,
+    // The whole maze, if it was drawn in the editor
+    #[serde(default)]
+    pub edited: Option<Maze>,
+    pub size: usize,
+    pub coins: usize,
+    pub ticks: u32,
+    pub time: Option<f32>,
+    pub inputs: Vec<(u32, InputState)>,
+}
+
+impl Recording {
+    pub fn new(maze: &Maze, size: usize, coins: usize) -> Recording {
+        Recording {
+            seed: maze.seed,
+            grid: maze.grid(),
+            floors: maze.floors(),
+            mask: maze.mask(),
+            doors: maze.placement(),
+            coin_placement: maze.coin_placement(),
+            terrain: maze.has_terrain(),
+            hazards: maze.hazards(),
+            power_ups: maze.has_power_ups(),
+            edited: maze.edited().then(|| maze.clone()),
+            size,
+            coins,
+            ticks: 0,
+            time: None,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, tick: u32, input: InputState) {
+        if self.inputs.last().map(|(_, last)| *last) != Some(input) {
+            self.inputs.push((tick, input));
+        }
+
+        self.ticks = tick + 1;
+    }
+
+    pub fn input_at(&self, tick: u32) -> InputState {
+        if tick >= self.ticks {
+            return InputState::default();
+        }
+
+        match self.inputs.partition_point(|(t, _)| *t <= tick) {
+            0 => InputState::default(),
+            i => self.inputs[i - 1].1,
+        }
+    }
+
+    pub fn maze(&self) -> Maze {
+        if let Some(maze) = &self.edited {
+            return maze.clone();
+        }
+
+        let generator = match &self.mask {
+            Some(mask) => Generator::shaped(self.grid, self.floors, mask, self.seed),
+            None => Generator::new(self.grid, self.floors, self.size, self.seed),
+        };
+
+        generator
+            .with_doors(self.doors)
+            .with_coin_placement(self.coin_placement)
+            .with_terrain(self.terrain)
+            .with_hazards(self.hazards)
+            .with_power_ups(self.power_ups)
+            .finish(self.coins)
+    }
+
+    pub fn load_all(data: &DataDir) -> Vec<Recording> {
+        Recording::load_named(data, |_| true)
+    }
+
+    // The replays in the data directory whose file names pass `wanted`
+    fn load_named(data: &DataDir, wanted: impl Fn(&str) -> bool) -> Vec<Recording> {
+        let entries = match fs::read_dir(data.path(REPLAY_DIR)) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(&wanted)
+            })
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .filter_map(|contents| ron::from_str(&contents).ok())
+            .collect()
+    }
+
+    // The fastest finished run on this exact maze, if it has been played before. Replays are
+    // named after the seed of their maze, so only the ones of this seed are read.
+    pub fn best(data: &DataDir, maze: &Maze, size: usize, coins: usize) -> Option<Recording> {
+        let prefix = format!("{}-", maze.seed);
+
+        Recording::load_named(data, |name| name.starts_with(&prefix))
+            .into_iter()
+            .filter(|recording| {
+                recording.seed == maze.seed
+                    && recording.grid == maze.grid()
+                    && recording.floors == maze.floors()
+                    && recording.mask == maze.mask()
+                    && recording.doors == maze.placement()
+                    && recording.coin_placement == maze.coin_placement()
+                    && recording.terrain == maze.has_terrain()
+                    && recording.hazards == maze.hazards()
+                    && recording.power_ups == maze.has_power_ups()
+                    && recording.edited.as_ref() == maze.edited().then_some(maze)
+                    && recording.size == size
+                    && recording.coins == coins
+            })
+            .filter(|recording| recording.time.is_some())
+            .min_by(|a, b| a.time.unwrap().total_cmp(&b.time.unwrap()))
+    }
+
+    pub fn save(&self, data: &DataDir) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let dir = data.path(REPLAY_DIR);
+        let name = format!("{}-{timestamp}", self.seed);
+
+        let result = fs::create_dir_all(&dir)
+            .map_err(|err| err.to_string())
+            .and_then(|_| ron::to_string(self).map_err(|err| err.to_string()))
+            .and_then(|contents| write_new(&dir, &name, &contents).map_err(|err| err.to_string()));
+
+        if let Err(err) = result {
+            warn!("Could not write replay {name} in {}: {err}", dir.display());
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Playback {
+    pub recording: Recording,
+    pub speed: f32,
+    pub seek: Option<u32>,
+}
+
+#[derive(Component)]
+struct OnReplayScreen;
+
+#[derive(Component)]
+struct ReplayBoard;
+
+// Writes `name`.ron in `dir`, or `name`-1.ron and so on when runs of the same seed end in the
+// same second, so no replay overwrites another
+fn write_new(dir: &Path, name: &str, contents: &str) -> io::Result<PathBuf> {
+    let mut copy = 0;
+
+    loop {
+        let file = match copy {
+            0 => dir.join(format!("{name}.ron")),
+            _ => dir.join(format!("{name}-{copy}.ron")),
+        };
+
+        match OpenOptions::new().write(true).create_new(true).open(&file) {
+            Ok(mut opened) => return opened.write_all(contents.as_bytes()).map(|_| file),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => copy += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn save_recording(
+    mut recording: ResMut<Recording>,
+    maze_state: Res<MazeState>,
+    data: Res<DataDir>,
+) {
+    recording.time = Some(maze_state.stopwatch.elapsed_secs());
+    recording.save(&data);
+}
+
+fn start_playback(
+    mut commands: Commands,
+    playback: Option<Res<Playback>>,
+    recording: Res<Recording>,
+) {
+    if playback.is_none() {
+        commands.insert_resource(Playback {
+            recording: recording.clone(),
+            speed: 1.,
+            seek: None,
+        });
+    }
+}
+
+fn stop_playback(mut commands: Commands) {
+    commands.remove_resource::<Playback>();
+}
+
+// Fast-forwarding speeds up the simulation, which has to go back to its own pace whichever
+// way the replay is left
+fn reset_speed(mut fixed_time: ResMut<FixedTime>) {
+    fixed_time.period = Duration::from_secs_f32(TIMESTEP);
+}
+
+fn replay_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                    font_size: 20.,
+                    color: TEXT_COLOR,
+                },
+            ),
+            transform: Transform::from_translation(Vec3::new(0., -HEIGHT / 2., 2.)),
+            text_anchor: Anchor::BottomCenter,
+            ..default()
+        },
+        ReplayBoard,
+        OnReplayScreen,
+    ));
+}
+
+fn playback_input(
+    playback: Res<Playback>,
+    maze_state: Res<MazeState>,
+    mut input: ResMut<InputState>,
+) {
+    *input = playback.recording.input_at(maze_state.ticks);
+}
+
+fn replay_controls(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut playback: ResMut<Playback>,
+    mut maze_state: ResMut<MazeState>,
+    mut fixed_time: ResMut<FixedTime>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        if maze_state.stopwatch.paused() {
+            maze_state.stopwatch.unpause();
+        } else {
+            maze_state.stopwatch.pause();
+        }
+    }
+
+    let step = (5. / TIMESTEP) as u32;
+
+    if keyboard_input.just_pressed(KeyCode::Left) {
+        playback.seek = Some(maze_state.ticks.saturating_sub(step));
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Right) {
+        playback.seek = Some((maze_state.ticks + step).min(playback.recording.ticks));
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Home) {
+        playback.seek = Some(0);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        playback.speed = (playback.speed * 2.).min(8.);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        playback.speed = (playback.speed / 2.).max(0.25);
+    }
+
+    fixed_time.period = Duration::from_secs_f32(TIMESTEP / playback.speed);
+}
+
+// Seeking re-simulates the run from the start, so the result is the same as watching it
+fn fast_forward(world: &mut World) {
+    let target = match world.resource_mut::<Playback>().seek.take() {
+        Some(target) => target,
+        None => return,
+    };
+
+    if target < world.resource::<MazeState>().ticks {
+        let mut maze_state = world.resource_mut::<MazeState>();
+        maze_state.ticks = 0;
+        maze_state.stopwatch.reset();
+
+        world.run_schedule(OnExit(GameState::Replay));
+        world.run_schedule(OnEnter(GameState::Replay));
+    }
+
+    let paused = world.resource::<MazeState>().stopwatch.paused();
+    world.resource_mut::<MazeState>().stopwatch.unpause();
+
+    while world.resource::<MazeState>().ticks < target
+        && !world.resource::<MazeState>().stopwatch.paused()
+    {
+        world.run_schedule(CoreSchedule::FixedUpdate);
+    }
+
+    if paused {
+        world.resource_mut::<MazeState>().stopwatch.pause();
+    }
+}
+
+fn replay_hud(
+    mut replay_board_query: Query<&mut Text, With<ReplayBoard>>,
+    playback: Res<Playback>,
+    maze_state: Res<MazeState>,
+) {
+    let mut text = replay_board_query.single_mut();
+
+    text.sections[0].value = format!(
+        "Replay {}x  {:.2}/{:.2}  [Space] pause  [Left/Right] seek  [Up/Down] speed  [Home] restart",
+        playback.speed,
+        maze_state.ticks as f32 * TIMESTEP,
+        playback.recording.ticks as f32 * TIMESTEP,
+    );
+}
@@ -0,0 +1,72 @@
+use crate::maze::Maze;
+use crate::replay::Recording;
+use crate::utils::vars::{DataDir, LIVES, SAVE_FILE};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+// Everything needed to put a game back exactly where the player left it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub size: usize,
+    pub coins: usize,
+    pub maze: Maze,
+    pub player: (f32, f32),
+    #[serde(default)]
+    pub floor: usize,
+    pub remaining_coins: Vec<(usize, usize)>,
+    pub gate_open: bool,
+    pub elapsed: f32,
+    pub ticks: u32,
+    pub recording: Recording,
+    #[serde(default = "full_lives")]
+    pub lives: u32,
+    // The cell of the last checkpoint reached, if any
+    #[serde(default)]
+    pub checkpoint: Option<(usize, usize)>,
+    // The campaign level being played, if any
+    #[serde(default)]
+    pub level: Option<usize>,
+}
+
+fn full_lives() -> u32 {
+    LIVES
+}
+
+impl SaveGame {
+    pub fn exists(data: &DataDir) -> bool {
+        data.path(SAVE_FILE).exists()
+    }
+
+    pub fn load(data: &DataDir) -> Option<SaveGame> {
+        let contents = fs::read_to_string(data.path(SAVE_FILE)).ok()?;
+
+        match ron::from_str(&contents) {
+            Ok(save) => Some(save),
+            Err(err) => {
+                warn!("Could not read save file {SAVE_FILE}: {err}");
+                None
+            }
+        }
+    }
+
+    pub fn write(&self, data: &DataDir) {
+        let result = ron::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                fs::write(data.path(SAVE_FILE), contents).map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            warn!("Could not write save file {SAVE_FILE}: {err}");
+        }
+    }
+
+    pub fn remove(data: &DataDir) {
+        if Self::exists(data) {
+            if let Err(err) = fs::remove_file(data.path(SAVE_FILE)) {
+                warn!("Could not remove save file {SAVE_FILE}: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::bot::Strategy;
+use crate::maze::{CoinPlacement, Doors, Hazards};
+use crate::utils::{
+    colors::{BACKGROUND_COLOR, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON, TEXT_COLOR},
+    despawn_screen,
+    vars::{DataDir, GameState, SETTINGS_FILE},
+    ButtonInteractions,
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = Settings::load(&app.world.get_resource_or_insert_with(DataDir::default));
+
+        app.insert_resource(settings)
+            .add_system(settings_setup.in_schedule(OnEnter(GameState::Settings)))
+            .add_systems(
+                (button_system, setting_text.after(button_system))
+                    .distributive_run_if(in_state(GameState::Settings)),
+            )
+            .add_system(
+                despawn_screen::<OnSettingsScreen>.in_schedule(OnExit(GameState::Settings)),
+            );
+    }
+}
+
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub ghost: bool,
+    pub intro: bool,
+    pub bot: Option<Strategy>,
+    pub doors: Doors,
+    pub coin_placement: CoinPlacement,
+    pub terrain: bool,
+    pub hazards: Hazards,
+    pub power_ups: bool,
+    pub fog: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            ghost: true,
+            intro: false,
+            bot: None,
+            doors: Doors::default(),
+            coin_placement: CoinPlacement::default(),
+            terrain: false,
+            hazards: Hazards::default(),
+            power_ups: false,
+            fog: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(data: &DataDir) -> Settings {
+        fs::read_to_string(data.path(SETTINGS_FILE))
+            .ok()
+            .and_then(|contents| ron::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn write(&self, data: &DataDir) {
+        let result = ron::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                fs::write(data.path(SETTINGS_FILE), contents).map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            warn!("Could not write settings file {SETTINGS_FILE}: {err}");
+        }
+    }
+}
+
+#[derive(Component)]
+struct OnSettingsScreen;
+
+#[derive(Component, Clone, Copy)]
+enum ButtonAction {
+    Toggle(Setting),
+    Back,
+}
+
+#[derive(Component, Clone, Copy)]
+enum Setting {
+    Ghost,
+    Intro,
+    Bot,
+    Doors,
+    Coins,
+    Terrain,
+    Hazards,
+    PowerUps,
+    Fog,
+}
+
+impl Setting {
+    const ALL: [Setting; 9] = [
+        Setting::Ghost,
+        Setting::Intro,
+        Setting::Bot,
+        Setting::Doors,
+        Setting::Coins,
+        Setting::Terrain,
+        Setting::Hazards,
+        Setting::PowerUps,
+        Setting::Fog,
+    ];
+
+    fn label(&self, settings: &Settings) -> String {
+        match self {
+            Setting::Ghost => format!("Ghost: {}", on_off(settings.ghost)),
+            Setting::Intro => format!("Build animation: {}", on_off(settings.intro)),
+            Setting::Bot => match settings.bot {
+                Some(strategy) => format!("Bot: {}", strategy.name()),
+                None => "Bot: Off".to_string(),
+            },
+            Setting::Doors => format!("Doors: {}", settings.doors.name()),
+            Setting::Coins => format!("Coins: {}", settings.coin_placement.name()),
+            Setting::Terrain => format!("Terrain: {}", on_off(settings.terrain)),
+            Setting::Hazards => format!("Hazards: {}", settings.hazards.name()),
+            Setting::PowerUps => format!("Power-ups: {}", on_off(settings.power_ups)),
+            Setting::Fog => format!("Fog: {}", on_off(settings.fog)),
+        }
+    }
+
+    fn toggle(&self, settings: &mut Settings) {
+        match self {
+            Setting::Ghost => settings.ghost = !settings.ghost,
+            Setting::Intro => settings.intro = !settings.intro,
+            Setting::Terrain => settings.terrain = !settings.terrain,
+            Setting::PowerUps => settings.power_ups = !settings.power_ups,
+            Setting::Fog => settings.fog = !settings.fog,
+            Setting::Bot => {
+                settings.bot = match settings.bot {
+                    None => Some(Strategy::ALL[0]),
+                    Some(strategy) => Strategy::ALL
+                        .iter()
+                        .position(|s| *s == strategy)
+                        .and_then(|i| Strategy::ALL.get(i + 1))
+                        .copied(),
+                }
+            }
+            Setting::Doors => {
+                let i = Doors::ALL.iter().position(|d| *d == settings.doors);
+                settings.doors = Doors::ALL[i.map_or(0, |i| (i + 1) % Doors::ALL.len())];
+            }
+            Setting::Coins => {
+                let all = CoinPlacement::ALL;
+                let i = all.iter().position(|p| *p == settings.coin_placement);
+                settings.coin_placement = all[i.map_or(0, |i| (i + 1) % all.len())];
+            }
+            Setting::Hazards => {
+                let i = Hazards::ALL.iter().position(|h| *h == settings.hazards);
+                settings.hazards = Hazards::ALL[i.map_or(0, |i| (i + 1) % Hazards::ALL.len())];
+            }
+        }
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+fn button_system(
+    mut interaction_query: ButtonInteractions<ButtonAction>,
+    mut settings: ResMut<Settings>,
+    mut game_state: ResMut<NextState<GameState>>,
+    data: Res<DataDir>,
+) {
+    for (interaction, mut color, button_action) in &mut interaction_query {
+        *color = match *interaction {
+            Interaction::Clicked => PRESSED_BUTTON.into(),
+            Interaction::Hovered => HOVERED_BUTTON.into(),
+            Interaction::None => NORMAL_BUTTON.into(),
+        };
+
+        if *interaction == Interaction::Clicked {
+            match button_action {
+                ButtonAction::Toggle(setting) => {
+                    setting.toggle(&mut settings);
+                    settings.write(&data);
+                }
+                ButtonAction::Back => game_state.set(GameState::Menu),
+            }
+        }
+    }
+}
+
+fn setting_text(settings: Res<Settings>, mut text_query: Query<(&mut Text, &Setting)>) {
+    if settings.is_changed() {
+        for (mut text, setting) in &mut text_query {
+            text.sections[0].value = setting.label(&settings);
+        }
+    }
+}
+
+fn settings_setup(mut commands: Commands, asset_server: Res<AssetServer>, settings: Res<Settings>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: BACKGROUND_COLOR.into(),
+                ..default()
+            },
+            OnSettingsScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Settings",
+                        TextStyle {
+                            font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                            font_size: 60.,
+                            color: TEXT_COLOR,
+                        },
+                    ));
+
+                    // Two columns of toggles, so they all fit on the screen
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                size: Size::width(Val::Px(640.)),
+                                flex_wrap: FlexWrap::Wrap,
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for setting in Setting::ALL {
+                                parent
+                                    .spawn(ButtonBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Px(300.), Val::Px(50.)),
+                                            margin: UiRect::all(Val::Px(10.0)),
+                                            justify_content: JustifyContent::Center,
+                                            align_items: AlignItems::Center,
+                                            ..default()
+                                        },
+                                        background_color: NORMAL_BUTTON.into(),
+                                        ..default()
+                                    })
+                                    .insert(ButtonAction::Toggle(setting))
+                                    .with_children(|parent| {
+                                        parent.spawn((
+                                            TextBundle::from_section(
+                                                setting.label(&settings),
+                                                TextStyle {
+                                                    font: asset_server
+                                                        .load("fonts/PixeloidSansBold.ttf"),
+                                                    font_size: 25.,
+                                                    color: TEXT_COLOR,
+                                                },
+                                            ),
+                                            setting,
+                                        ));
+                                    });
+                            }
+                        });
+
+                    parent
+                        .spawn(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(300.), Val::Px(50.)),
+                                margin: UiRect::all(Val::Px(20.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        })
+                        .insert(ButtonAction::Back)
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                "Back",
+                                TextStyle {
+                                    font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+                                    font_size: 30.,
+                                    color: TEXT_COLOR,
+                                },
+                            ));
+                        });
+                });
+        });
+}
@@ -0,0 +1,299 @@
+use crate::maze::{Direction, Grid, Maze, Terrain};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    BreadthFirst,
+    DepthFirst,
+    AStar,
+    DeadEndFilling,
+    WallFollower,
+}
+
+// Something a solver did to a cell, in the order it did it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchStep {
+    Frontier((usize, usize)),
+    Visit((usize, usize)),
+}
+
+// Everything a solver looked at on its way to the path it found
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub steps: Vec<SearchStep>,
+    pub path: Option<Vec<(usize, usize)>>,
+}
+
+impl Search {
+    pub fn visited(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| matches!(step, SearchStep::Visit(_)))
+            .count()
+    }
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 5] = [
+        Algorithm::BreadthFirst,
+        Algorithm::DepthFirst,
+        Algorithm::AStar,
+        Algorithm::DeadEndFilling,
+        Algorithm::WallFollower,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::BreadthFirst => "Breadth-first",
+            Algorithm::DepthFirst => "Depth-first",
+            Algorithm::AStar => "A*",
+            Algorithm::DeadEndFilling => "Dead-end filling",
+            Algorithm::WallFollower => "Wall follower",
+        }
+    }
+
+    pub fn solve(&self, maze: &Maze, from: (usize, usize), to: (usize, usize)) -> Search {
+        match self {
+            Algorithm::BreadthFirst => breadth_first(maze, from, to, &[]),
+            Algorithm::DepthFirst => depth_first(maze, from, to),
+            Algorithm::AStar => a_star(maze, from, to),
+            Algorithm::DeadEndFilling => dead_end_filling(maze, from, to),
+            Algorithm::WallFollower => wall_follower(maze, from, to),
+        }
+    }
+}
+
+// Follows the `previous` links back from `to`, both ends included
+fn trace(
+    previous: &[usize],
+    width: usize,
+    from: (usize, usize),
+    to: (usize, usize),
+) -> Option<Vec<(usize, usize)>> {
+    let index = |(x, y): (usize, usize)| y * width + x;
+
+    if previous[index(to)] == usize::MAX {
+        return None;
+    }
+
+    let mut path = vec![to];
+    let mut i = index(to);
+
+    while i != index(from) {
+        i = previous[i];
+        path.push((i % width, i / width));
+    }
+
+    path.reverse();
+    Some(path)
+}
+
+// Cells marked in `blocked` are treated as walls
+fn breadth_first(
+    maze: &Maze,
+    from: (usize, usize),
+    to: (usize, usize),
+    blocked: &[bool],
+) -> Search {
+    let width = maze.width();
+    let index = |(x, y): (usize, usize)| y * width + x;
+    let mut previous = vec![usize::MAX; width * maze.height()];
+    let mut queue = VecDeque::from([from]);
+    let mut steps = vec![SearchStep::Frontier(from)];
+    previous[index(from)] = index(from);
+
+    while let Some(current) = queue.pop_front() {
+        steps.push(SearchStep::Visit(current));
+
+        if current == to {
+            break;
+        }
+
+        for next in maze.neighbours(current.0, current.1) {
+            if previous[index(next)] == usize::MAX
+                && !blocked.get(index(next)).copied().unwrap_or(false)
+            {
+                previous[index(next)] = index(current);
+                queue.push_back(next);
+                steps.push(SearchStep::Frontier(next));
+            }
+        }
+    }
+
+    Search {
+        path: trace(&previous, width, from, to),
+        steps,
+    }
+}
+
+fn depth_first(maze: &Maze, from: (usize, usize), to: (usize, usize)) -> Search {
+    let width = maze.width();
+    let index = |(x, y): (usize, usize)| y * width + x;
+    let mut previous = vec![usize::MAX; width * maze.height()];
+    let mut stack = vec![from];
+    let mut steps = vec![SearchStep::Frontier(from)];
+    previous[index(from)] = index(from);
+
+    while let Some(current) = stack.pop() {
+        steps.push(SearchStep::Visit(current));
+
+        if current == to {
+            break;
+        }
+
+        for next in maze.neighbours(current.0, current.1) {
+            if previous[index(next)] == usize::MAX {
+                previous[index(next)] = index(current);
+                stack.push(next);
+                steps.push(SearchStep::Frontier(next));
+            }
+        }
+    }
+
+    Search {
+        path: trace(&previous, width, from, to),
+        steps,
+    }
+}
+
+// Best-first search ordered by the cost of the walk so far plus a lower bound on the cost
+// left, which weighs each cell by its terrain and finds the quickest way rather than the
+// shortest. A weave step can pass under a crossing and land two cells on, so there the
+// distance is halved, a hex step can change both coordinates at once, so there only the
+// larger one counts, and a polar step can go all the way round a ring, so there only the
+// rings count. Rows are compared within their floors, with every floor in between costing a
+// step. Every step left is taken to cost as little as any cell can.
+fn a_star(maze: &Maze, from: (usize, usize), to: (usize, usize)) -> Search {
+    let width = maze.width();
+    let floor_height = maze.floor_height();
+    let index = |(x, y): (usize, usize)| y * width + x;
+    let heuristic = |(x, y): (usize, usize)| {
+        let floors = maze.floor(y).abs_diff(maze.floor(to.1));
+        let (y, to_y) = (y % floor_height, to.1 % floor_height);
+
+        let steps = floors
+            + match maze.grid() {
+                Grid::Square => x.abs_diff(to.0) + y.abs_diff(to_y),
+                Grid::Weave => (x.abs_diff(to.0) + y.abs_diff(to_y)).div_ceil(2),
+                Grid::Hex => x.abs_diff(to.0).max(y.abs_diff(to_y)),
+                Grid::Polar => y.abs_diff(to_y),
+            };
+
+        steps * Terrain::MIN_COST
+    };
+    let mut previous = vec![usize::MAX; width * maze.height()];
+    let mut distance = vec![usize::MAX; width * maze.height()];
+    let mut open = BinaryHeap::from([Reverse((heuristic(from), 0, from))]);
+    let mut steps = vec![SearchStep::Frontier(from)];
+    previous[index(from)] = index(from);
+    distance[index(from)] = 0;
+
+    while let Some(Reverse((_, walked, current))) = open.pop() {
+        if walked > distance[index(current)] {
+            continue;
+        }
+
+        steps.push(SearchStep::Visit(current));
+
+        if current == to {
+            break;
+        }
+
+        for next in maze.neighbours(current.0, current.1) {
+            let cost = walked + maze.cell(next.0, next.1).terrain().cost();
+
+            if cost < distance[index(next)] {
+                distance[index(next)] = cost;
+                previous[index(next)] = index(current);
+                open.push(Reverse((cost + heuristic(next), cost, next)));
+                steps.push(SearchStep::Frontier(next));
+            }
+        }
+    }
+
+    Search {
+        path: trace(&previous, width, from, to),
+        steps,
+    }
+}
+
+// Keeps filling in dead ends until only the cells on a way through are left
+fn dead_end_filling(maze: &Maze, from: (usize, usize), to: (usize, usize)) -> Search {
+    let width = maze.width();
+    let index = |(x, y): (usize, usize)| y * width + x;
+    let mut filled = vec![false; width * maze.height()];
+    let mut exits: Vec<usize> = (0..filled.len())
+        .map(|i| maze.neighbours(i % width, i / width).count())
+        .collect();
+    let mut steps = Vec::new();
+
+    let mut queue: VecDeque<(usize, usize)> = (0..filled.len())
+        .map(|i| (i % width, i / width))
+        .filter(|&(x, y)| x < maze.row_len(y) && maze.cell(x, y).exists())
+        .filter(|&cell| exits[index(cell)] <= 1 && cell != from && cell != to)
+        .collect();
+
+    while let Some(current) = queue.pop_front() {
+        if filled[index(current)] {
+            continue;
+        }
+
+        filled[index(current)] = true;
+        steps.push(SearchStep::Visit(current));
+
+        for next in maze.neighbours(current.0, current.1) {
+            exits[index(next)] -= 1;
+
+            if exits[index(next)] == 1 && !filled[index(next)] && next != from && next != to {
+                queue.push_back(next);
+                steps.push(SearchStep::Frontier(next));
+            }
+        }
+    }
+
+    Search {
+        path: breadth_first(maze, from, to, &filled).path,
+        steps,
+    }
+}
+
+// Keeps a hand on the right-hand wall, erasing any loop it walks back into
+fn wall_follower(maze: &Maze, from: (usize, usize), to: (usize, usize)) -> Search {
+    let mut steps = vec![SearchStep::Visit(from)];
+    let mut path = vec![from];
+    let mut current = from;
+    let mut facing = Direction::East;
+
+    // Every passage is walked at most once in each direction
+    for _ in 0..2 * maze.grid().directions().len() * maze.width() * maze.height() {
+        if current == to {
+            return Search {
+                steps,
+                path: Some(path),
+            };
+        }
+
+        let next = maze.grid().right_hand_turns(facing).find_map(|dir| {
+            maze.passage(current.0, current.1, dir)
+                .filter(|_| maze.ways(current.0, current.1).any(|way| way == dir))
+                .map(|next| (dir, next))
+        });
+
+        let (dir, next) = match next {
+            Some(next) => next,
+            None => break,
+        };
+
+        facing = dir;
+        current = next;
+        steps.push(SearchStep::Visit(current));
+
+        match path.iter().position(|&cell| cell == current) {
+            Some(i) => path.truncate(i + 1),
+            None => path.push(current),
+        }
+    }
+
+    Search { steps, path: None }
+}
@@ -0,0 +1,236 @@
+use crate::bot::plan_route;
+use crate::campaign;
+use crate::game::coins_for;
+use crate::maze::{Direction, Generator, Grid, Maze};
+use crate::settings::Settings;
+use std::time::{Duration, Instant};
+
+// A game of a square maze in a terminal, played a cell at a time. The clock starts with the
+// first step and stops on walking out through the exit.
+pub struct Run {
+    maze: Maze,
+    position: (usize, usize),
+    taken: Vec<(usize, usize)>,
+    moves: usize,
+    started: Option<Instant>,
+    finished: Option<Duration>,
+    pub hint: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Open,
+    Wall,
+    Gate,
+}
+
+impl Run {
+    pub fn new(maze: Maze) -> Run {
+        Run {
+            position: maze.entrance(),
+            maze,
+            taken: Vec::new(),
+            moves: 0,
+            started: None,
+            finished: None,
+            hint: false,
+        }
+    }
+
+    // A new maze made the way the game makes one, on one floor and without the features a
+    // terminal can't show
+    pub fn generate(size: usize, seed: u64, settings: &Settings) -> Run {
+        let maze = Generator::new(Grid::Square, 1, size, seed)
+            .with_doors(settings.doors)
+            .with_coin_placement(settings.coin_placement)
+            .finish(coins_for(size));
+
+        Run::new(maze)
+    }
+
+    pub fn maze(&self) -> &Maze {
+        &self.maze
+    }
+
+    pub fn position(&self) -> (usize, usize) {
+        self.position
+    }
+
+    pub fn moves(&self) -> usize {
+        self.moves
+    }
+
+    pub fn coins_left(&self) -> Vec<(usize, usize)> {
+        (0..self.maze.height())
+            .flat_map(|y| (0..self.maze.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.maze.cell(x, y).coin() && !self.taken.contains(&(x, y)))
+            .collect()
+    }
+
+    pub fn gate_open(&self) -> bool {
+        self.coins_left().is_empty()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match (self.finished, self.started) {
+            (Some(time), _) => time,
+            (None, Some(started)) => started.elapsed(),
+            (None, None) => Duration::ZERO,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished.is_some()
+    }
+
+    // Rated like a campaign level once out
+    pub fn stars(&self) -> Option<u8> {
+        self.finished
+            .map(|time| campaign::stars(&self.maze, time.as_secs_f32()))
+    }
+
+    // Takes a step, giving whether it went anywhere
+    pub fn step(&mut self, direction: Direction) -> bool {
+        let (x, y) = self.position;
+
+        if self.finished() || !self.maze.cell(x, y).is_open(direction) {
+            return false;
+        }
+
+        let next = self.maze.passage(x, y, direction);
+        let out = ((x, y), direction) == (self.maze.exit(), self.maze.exit_side());
+        if next.is_none() && !(out && self.gate_open()) {
+            return false;
+        }
+
+        self.started.get_or_insert_with(Instant::now);
+        self.moves += 1;
+        let Some(next) = next else {
+            self.finished = Some(self.elapsed());
+            return true;
+        };
+
+        self.position = next;
+        if self.maze.cell(next.0, next.1).coin() && !self.taken.contains(&next) {
+            self.taken.push(next);
+        }
+
+        true
+    }
+
+    // The way out past every coin that's left, as the bot would walk it
+    pub fn hint_path(&self) -> Vec<(usize, usize)> {
+        plan_route(
+            &self.maze,
+            self.position,
+            self.maze.exit(),
+            &self.coins_left(),
+        )
+    }
+
+    // What is on the `direction` side of a cell, where the exit has a gate until the coins are in
+    fn side(&self, cell: (usize, usize), direction: Direction) -> Side {
+        if !self.maze.cell(cell.0, cell.1).is_open(direction) {
+            Side::Wall
+        } else if (cell, direction) == (self.maze.exit(), self.maze.exit_side())
+            && !self.gate_open()
+        {
+            Side::Gate
+        } else {
+            Side::Open
+        }
+    }
+
+    // The maze in box-drawing characters, with each cell three characters wide and one line
+    // high between the walls
+    pub fn render(&self) -> Vec<String> {
+        let (width, height) = (self.maze.width(), self.maze.height());
+        let hint = match self.hint && !self.finished() {
+            true => self.hint_path(),
+            false => Vec::new(),
+        };
+        let coins = self.coins_left();
+
+        // The wall to the left of column `x` and the one above row `y`, counting the outside
+        // walls on the right and at the bottom as one past the last cell
+        let vertical = |x: usize, y: usize| match x < width {
+            true => self.side((x, y), Direction::West),
+            false => self.side((width - 1, y), Direction::East),
+        };
+        let horizontal = |x: usize, y: usize| match y < height {
+            true => self.side((x, y), Direction::North),
+            false => self.side((x, height - 1), Direction::South),
+        };
+
+        let corner = |x: usize, y: usize| {
+            let up = y > 0 && vertical(x, y - 1) != Side::Open;
+            let down = y < height && vertical(x, y) != Side::Open;
+            let left = x > 0 && horizontal(x - 1, y) != Side::Open;
+            let right = x < width && horizontal(x, y) != Side::Open;
+
+            match (up, down, left, right) {
+                (false, false, false, false) => ' ',
+                (true, false, false, false) => '╵',
+                (false, true, false, false) => '╷',
+                (false, false, true, false) => '╴',
+                (false, false, false, true) => '╶',
+                (true, true, false, false) => '│',
+                (false, false, true, true) => '─',
+                (false, true, false, true) => '┌',
+                (false, true, true, false) => '┐',
+                (true, false, false, true) => '└',
+                (true, false, true, false) => '┘',
+                (true, true, false, true) => '├',
+                (true, true, true, false) => '┤',
+                (false, true, true, true) => '┬',
+                (true, false, true, true) => '┴',
+                (true, true, true, true) => '┼',
+            }
+        };
+
+        let upright = |side: Side| match side {
+            Side::Open => ' ',
+            Side::Wall => '│',
+            Side::Gate => '║',
+        };
+
+        let mut lines = Vec::with_capacity(height * 2 + 1);
+        for y in 0..=height {
+            let mut line: String = (0..width)
+                .map(|x| {
+                    let wall = match horizontal(x, y) {
+                        Side::Open => "   ",
+                        Side::Wall => "───",
+                        Side::Gate => "═══",
+                    };
+                    format!("{}{wall}", corner(x, y))
+                })
+                .collect();
+            line.push(corner(width, y));
+            lines.push(line);
+
+            if y == height {
+                break;
+            }
+
+            let mut line: String = (0..width)
+                .map(|x| {
+                    let inside = if (x, y) == self.position && !self.finished() {
+                        " @ "
+                    } else if coins.contains(&(x, y)) {
+                        " o "
+                    } else if hint.contains(&(x, y)) {
+                        " · "
+                    } else {
+                        "   "
+                    };
+                    format!("{}{inside}", upright(vertical(x, y)))
+                })
+                .collect();
+            line.push(upright(vertical(width, y)));
+            lines.push(line);
+        }
+
+        lines
+    }
+}
@@ -2,8 +2,11 @@ use bevy::prelude::*;
 
 pub mod vars {
     use super::*;
-    use crate::maze::Maze;
+    use crate::mask::Mask;
+    use crate::maze::{Grid, Maze};
+    use crate::save::SaveGame;
     use bevy::time::Stopwatch;
+    use std::path::PathBuf;
 
     pub const HEIGHT: f32 = 600.;
     pub const WIDTH: f32 = 1000.;
@@ -11,6 +14,29 @@ pub mod vars {
     pub const MAZE_BORDER_WIDTH: f32 = 3.;
     pub const BUTTON_SIZE: f32 = 50.;
     pub const BUTTON_MARGIN: f32 = 5.;
+    // Files the game writes go in the data directory, while masks ship with the game
+    pub const SAVE_FILE: &str = "savegame.ron";
+    pub const REPLAY_DIR: &str = "replays";
+    pub const MASK_DIR: &str = "assets/masks";
+    pub const SETTINGS_FILE: &str = "settings.ron";
+    pub const CAMPAIGN_FILE: &str = "campaign.ron";
+    pub const MAZE_FILE: &str = "maze.txt";
+    pub const IMPORT_FILE: &str = "maze.png";
+    pub const TIMESTEP: f32 = 1. / 60.;
+    pub const DEMO_DELAY: f32 = 20.;
+    pub const LIVES: u32 = 3;
+    // Hazard timings, in ticks
+    pub const SPIKES_PERIOD: u32 = 120;
+    pub const SPIKES_UP: u32 = 45;
+    pub const CRUMBLE_TICKS: u32 = 30;
+    pub const FALLEN_TICKS: u32 = 180;
+    // Power-up durations, in ticks
+    pub const PHASE_TICKS: u32 = 180;
+    pub const SPEED_TICKS: u32 = 300;
+    pub const REVEAL_TICKS: u32 = 300;
+    pub const SPEED_BOOST: f32 = 1.5;
+    // How many cells from the player can be seen in fog
+    pub const FOG_CELLS: f32 = 2.5;
 
     #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
     pub enum GameState {
@@ -18,14 +44,45 @@ pub mod vars {
         Menu,
         Game,
         EndGame,
+        GameOver,
+        Replay,
+        Settings,
+        Visualize,
+        Demo,
+        LevelSelect,
+        Editor,
+    }
+
+    // Where the files above are read and written, which is the working directory unless
+    // something else is put in before the game's plugins are added
+    #[derive(Resource, Debug, Clone)]
+    pub struct DataDir(pub PathBuf);
+
+    impl Default for DataDir {
+        fn default() -> Self {
+            DataDir(PathBuf::from("."))
+        }
+    }
+
+    impl DataDir {
+        pub fn path(&self, file: &str) -> PathBuf {
+            self.0.join(file)
+        }
     }
 
     #[derive(Resource)]
     pub struct MazeState {
         pub stopwatch: Stopwatch,
         pub size: usize,
+        pub grid: Grid,
+        pub floors: usize,
+        // The name and cells of the mask new mazes are shaped by, if any
+        pub shape: Option<(String, Mask)>,
         pub path: Option<Maze>,
         pub coins: usize,
+        pub ticks: u32,
+        pub lives: u32,
+        pub saved: Option<SaveGame>,
     }
 }
 
@@ -35,9 +92,29 @@ pub mod colors {
     pub const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
     pub const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
     pub const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+    pub const LOCKED_BUTTON: Color = Color::rgb(0.08, 0.08, 0.08);
     pub const TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
     pub const WALL_COLOR: Color = Color::BLACK;
     pub const BACKGROUND_COLOR: Color = Color::CRIMSON;
+    pub const FRONTIER_COLOR: Color = Color::rgb(0.95, 0.75, 0.2);
+    pub const VISITED_COLOR: Color = Color::rgb(0.3, 0.45, 0.8);
+    pub const PATH_COLOR: Color = Color::LIME_GREEN;
+    pub const STAIRS_UP_COLOR: Color = Color::rgb(0.85, 0.85, 0.85);
+    pub const STAIRS_DOWN_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
+    pub const BRIDGE_COLOR: Color = Color::rgba(0.55, 0.35, 0.2, 0.8);
+    pub const TUNNEL_COLOR: Color = Color::rgba(0., 0., 0., 0.3);
+    pub const MUD_COLOR: Color = Color::rgba(0.4, 0.25, 0.1, 0.8);
+    pub const ICE_COLOR: Color = Color::rgba(0.75, 0.9, 1., 0.8);
+    pub const BOOST_COLOR: Color = Color::rgba(1., 0.8, 0.1, 0.8);
+    pub const SPIKES_DOWN_COLOR: Color = Color::rgba(0.5, 0.5, 0.55, 0.5);
+    pub const SPIKES_UP_COLOR: Color = Color::rgb(0.8, 0.8, 0.85);
+    pub const CRUMBLE_COLOR: Color = Color::rgba(0.6, 0.5, 0.4, 0.8);
+    pub const HOLE_COLOR: Color = Color::rgb(0.05, 0.05, 0.05);
+    pub const CHECKPOINT_COLOR: Color = Color::rgba(0.3, 0.6, 1., 0.5);
+    pub const PHASE_COLOR: Color = Color::rgb(0.65, 0.35, 0.95);
+    pub const SPEED_COLOR: Color = Color::rgb(1., 0.55, 0.1);
+    pub const REVEAL_COLOR: Color = Color::rgb(0.2, 0.9, 0.9);
+    pub const OPEN_WALL_COLOR: Color = Color::rgba(0., 0., 0., 0.12);
 }
 
 pub fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
@@ -45,3 +122,15 @@ pub fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut comm
         commands.entity(entity).despawn_recursive();
     }
 }
+
+// The buttons of a screen whose interaction changed this frame, with what each one does
+pub type ButtonInteractions<'w, 's, A> = Query<
+    'w,
+    's,
+    (
+        &'static Interaction,
+        &'static mut BackgroundColor,
+        &'static A,
+    ),
+    (Changed<Interaction>, With<Button>),
+>;
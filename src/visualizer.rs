@@ -0,0 +1,406 @@
+use crate::game::{animation_steps, FloorRoot, Layout};
+use crate::solver::{Algorithm, Search, SearchStep};
+use crate::utils::{
+    colors::{
+        FRONTIER_COLOR, HOVERED_BUTTON, NORMAL_BUTTON, PATH_COLOR, PRESSED_BUTTON, TEXT_COLOR,
+        TUNNEL_COLOR, VISITED_COLOR, WALL_COLOR,
+    },
+    despawn_screen,
+    vars::{GameState, MazeState, HEIGHT, WIDTH},
+    ButtonInteractions,
+};
+use bevy::{prelude::*, sprite::Anchor};
+use std::collections::HashMap;
+
+pub struct VisualizerPlugin;
+
+impl Plugin for VisualizerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(visualizer_setup.in_schedule(OnEnter(GameState::Visualize)))
+            .add_systems(
+                (
+                    visualizer_controls,
+                    animate_search.after(visualizer_controls),
+                    visualizer_hud,
+                    button_system,
+                )
+                    .in_set(OnUpdate(GameState::Visualize)),
+            )
+            .add_system(
+                despawn_screen::<OnVisualizerScreen>.in_schedule(OnExit(GameState::Visualize)),
+            )
+            .add_system(stop_visualization.in_schedule(OnExit(GameState::Visualize)));
+    }
+}
+
+// Every solver is run up front so their step counts can be compared side by side,
+// then the selected one is played back a few steps per frame
+#[derive(Resource)]
+struct Visualization {
+    searches: Vec<Search>,
+    selected: usize,
+    step: usize,
+    progress: f32,
+    speed: f32,
+    cells: HashMap<(usize, usize), Entity>,
+    path_shown: bool,
+}
+
+#[derive(Component)]
+struct OnVisualizerScreen;
+
+#[derive(Component)]
+struct SolverBoard;
+
+#[derive(Component)]
+struct StepBoard;
+
+#[derive(Component)]
+struct BackButton;
+
+fn visualizer_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    maze_state: Res<MazeState>,
+) {
+    let maze = match &maze_state.path {
+        Some(maze) => maze,
+        None => return,
+    };
+
+    let layout = Layout::new(maze);
+    let coord_size = layout.coord_size;
+    let mut cells = HashMap::new();
+
+    let roots: Vec<Entity> = (0..maze.floors())
+        .map(|floor| {
+            commands
+                .spawn((
+                    SpatialBundle::default(),
+                    FloorRoot(floor),
+                    OnVisualizerScreen,
+                ))
+                .id()
+        })
+        .collect();
+
+    for (i, row) in maze.rows().enumerate() {
+        let root = roots[maze.floor(i)];
+
+        for (j, cell) in row.iter().enumerate().filter(|(_, cell)| cell.exists()) {
+            cells.insert(
+                (j, i),
+                commands
+                    .spawn(SpriteBundle {
+                        transform: Transform {
+                            translation: layout.cell_center(j, i).extend(0.5),
+                            scale: Vec3::new(coord_size, coord_size, 1.),
+                            ..default()
+                        },
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    })
+                    .set_parent(root)
+                    .id(),
+            );
+
+            let walls = maze
+                .sides(j, i)
+                .filter(|dir| !cell.is_open(*dir))
+                .flat_map(|dir| layout.wall_pieces(j, i, dir))
+                .map(|(transform, _)| (transform, false));
+            let crossing = cell
+                .tunnel()
+                .map(|tunnel| layout.crossing_walls(j, i, tunnel))
+                .unwrap_or_default();
+
+            for (transform, under) in walls.chain(crossing) {
+                commands
+                    .spawn(SpriteBundle {
+                        sprite: Sprite {
+                            color: if under { TUNNEL_COLOR } else { WALL_COLOR },
+                            ..default()
+                        },
+                        transform,
+                        ..default()
+                    })
+                    .set_parent(root);
+            }
+        }
+    }
+
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/PixeloidSansBold.ttf"),
+        font_size: 14.,
+        color: TEXT_COLOR,
+    };
+
+    // One section per solver so the selected one can be highlighted
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_sections(
+                Algorithm::ALL
+                    .iter()
+                    .map(|_| TextSection::new("", text_style.clone())),
+            ),
+            transform: Transform::from_translation(Vec3::new(
+                -WIDTH / 2. + 10.,
+                HEIGHT / 2. - 10.,
+                2.,
+            )),
+            text_anchor: Anchor::TopLeft,
+            ..default()
+        },
+        SolverBoard,
+        OnVisualizerScreen,
+    ));
+
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.,
+                    ..text_style.clone()
+                },
+            ),
+            transform: Transform::from_translation(Vec3::new(0., -HEIGHT / 2., 2.)),
+            text_anchor: Anchor::BottomCenter,
+            ..default()
+        },
+        StepBoard,
+        OnVisualizerScreen,
+    ));
+
+    commands
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(180.), Val::Px(50.)),
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        right: Val::Px(10.),
+                        top: Val::Px(10.),
+                        ..default()
+                    },
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: NORMAL_BUTTON.into(),
+                ..default()
+            },
+            BackButton,
+            OnVisualizerScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Main menu",
+                TextStyle {
+                    font_size: 25.,
+                    ..text_style
+                },
+            ));
+        });
+
+    commands.insert_resource(Visualization {
+        searches: Algorithm::ALL
+            .iter()
+            .map(|algorithm| algorithm.solve(maze, maze.entrance(), maze.exit()))
+            .collect(),
+        selected: 0,
+        step: 0,
+        progress: 0.,
+        speed: 1.,
+        cells,
+        path_shown: false,
+    });
+}
+
+fn stop_visualization(mut commands: Commands) {
+    commands.remove_resource::<Visualization>();
+}
+
+fn visualizer_controls(
+    keyboard_input: Res<Input<KeyCode>>,
+    visualization: Option<ResMut<Visualization>>,
+    mut cell_query: Query<&mut Visibility>,
+) {
+    let mut visualization = match visualization {
+        Some(visualization) => visualization,
+        None => return,
+    };
+
+    let keys = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+    ];
+
+    let selected = keys
+        .iter()
+        .position(|key| keyboard_input.just_pressed(*key));
+
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        visualization.speed = (visualization.speed * 2.).min(64.);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        visualization.speed = (visualization.speed / 2.).max(0.25);
+    }
+
+    if selected.is_some() || keyboard_input.just_pressed(KeyCode::Space) {
+        visualization.selected = selected.unwrap_or(visualization.selected);
+        visualization.step = 0;
+        visualization.progress = 0.;
+        visualization.path_shown = false;
+
+        for &entity in visualization.cells.values() {
+            if let Ok(mut visibility) = cell_query.get_mut(entity) {
+                *visibility = Visibility::Hidden;
+            }
+        }
+    }
+}
+
+fn animate_search(
+    time: Res<Time>,
+    maze_state: Res<MazeState>,
+    visualization: Option<ResMut<Visualization>>,
+    mut cell_query: Query<(&mut Sprite, &mut Visibility)>,
+) {
+    let (mut visualization, maze) = match (visualization, &maze_state.path) {
+        (Some(visualization), Some(maze)) => (visualization, maze),
+        _ => return,
+    };
+    let visualization = &mut *visualization;
+
+    let mut paint = |(x, y): (usize, usize), color: Color| {
+        if let Some(Ok((mut sprite, mut visibility))) = visualization
+            .cells
+            .get(&(x, y))
+            .map(|&entity| cell_query.get_mut(entity))
+        {
+            sprite.color = color;
+            *visibility = Visibility::Inherited;
+        }
+    };
+
+    let search = &visualization.searches[visualization.selected];
+
+    visualization.progress += animation_steps(maze, time.delta_seconds(), visualization.speed);
+
+    while visualization.progress >= 1. && visualization.step < search.steps.len() {
+        match search.steps[visualization.step] {
+            SearchStep::Frontier(cell) => paint(cell, FRONTIER_COLOR),
+            SearchStep::Visit(cell) => paint(cell, VISITED_COLOR),
+        }
+
+        visualization.step += 1;
+        visualization.progress -= 1.;
+    }
+
+    if visualization.step == search.steps.len() && !visualization.path_shown {
+        for &cell in search.path.iter().flatten() {
+            paint(cell, PATH_COLOR);
+        }
+
+        visualization.path_shown = true;
+    }
+}
+
+// Also shows the floor of a multi-floor maze that the search last reached
+fn visualizer_hud(
+    visualization: Option<Res<Visualization>>,
+    maze_state: Res<MazeState>,
+    mut solver_board_query: Query<&mut Text, (With<SolverBoard>, Without<StepBoard>)>,
+    mut step_board_query: Query<&mut Text, With<StepBoard>>,
+    mut root_query: Query<(&FloorRoot, &mut Visibility)>,
+) {
+    let (visualization, maze) = match (visualization, &maze_state.path) {
+        (Some(visualization), Some(maze)) => (visualization, maze),
+        _ => return,
+    };
+
+    let mut solver_board = solver_board_query.single_mut();
+
+    for (i, (algorithm, search)) in Algorithm::ALL
+        .iter()
+        .zip(&visualization.searches)
+        .enumerate()
+    {
+        let section = &mut solver_board.sections[i];
+        let path = match &search.path {
+            Some(path) if maze.has_terrain() => {
+                format!(
+                    "path {}, cost {}",
+                    path.len(),
+                    maze.path_cost(path) as f32 / 2.
+                )
+            }
+            Some(path) => format!("path {}", path.len()),
+            None => "no path".to_string(),
+        };
+
+        section.value = format!(
+            "[{}] {}\n    {} visited, {path}\n",
+            i + 1,
+            algorithm.name(),
+            search.visited(),
+        );
+        section.style.color = if i == visualization.selected {
+            PATH_COLOR
+        } else {
+            TEXT_COLOR
+        };
+    }
+
+    let search = &visualization.searches[visualization.selected];
+    let floor = match visualization.step.checked_sub(1).map(|i| search.steps[i]) {
+        Some(SearchStep::Frontier((_, y)) | SearchStep::Visit((_, y))) => maze.floor(y),
+        None => maze.floor(maze.entrance().1),
+    };
+
+    for (root, mut visibility) in &mut root_query {
+        let shown = match root.0 == floor {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+
+    let floors = match maze.floors() {
+        1 => String::new(),
+        floors => format!("  floor {}/{floors}", floor + 1),
+    };
+
+    step_board_query.single_mut().sections[0].value = format!(
+        "{} {}x  step {}/{}{floors}  [1-5] solver  [Up/Down] speed  [Space] restart",
+        Algorithm::ALL[visualization.selected].name(),
+        visualization.speed,
+        visualization.step,
+        search.steps.len(),
+    );
+}
+
+fn button_system(
+    mut interaction_query: ButtonInteractions<BackButton>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, mut color, _) in &mut interaction_query {
+        *color = match *interaction {
+            Interaction::Clicked => PRESSED_BUTTON.into(),
+            Interaction::Hovered => HOVERED_BUTTON.into(),
+            Interaction::None => NORMAL_BUTTON.into(),
+        };
+
+        if *interaction == Interaction::Clicked {
+            game_state.set(GameState::Menu);
+        }
+    }
+}
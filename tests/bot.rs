@@ -0,0 +1,277 @@
+mod common;
+
+use bevy::prelude::*;
+use common::TestApp;
+use maze_game::{
+    bot::{plan_route, Strategy},
+    game::Coin,
+    mask::Mask,
+    maze::{CoinPlacement, Doors, Generator, Grid, Hazards, Maze},
+    settings::Settings,
+    utils::vars::{GameState, MazeState, DEMO_DELAY, LIVES, TIMESTEP},
+};
+use std::path::Path;
+
+fn bot_game(strategy: Strategy, grid: Grid) -> TestApp {
+    bot_game_on_floors(strategy, grid, 1)
+}
+
+fn bot_game_on_floors(strategy: Strategy, grid: Grid, floors: usize) -> TestApp {
+    let mut app = TestApp::new();
+    app.app.world.resource_mut::<MazeState>().grid = grid;
+    app.app.world.resource_mut::<MazeState>().floors = floors;
+    app.app.world.resource_mut::<Settings>().bot = Some(strategy);
+    app.app.world.resource_mut::<Settings>().ghost = false;
+
+    app.click("Easy");
+    assert_eq!(app.state(), GameState::Game);
+
+    app
+}
+
+fn assert_bot_finishes(strategy: Strategy, grid: Grid) {
+    assert_bot_finishes_on_floors(strategy, grid, 1);
+}
+
+fn assert_bot_finishes_on_floors(strategy: Strategy, grid: Grid, floors: usize) {
+    let mut app = bot_game_on_floors(strategy, grid, floors);
+
+    // Long enough to walk every passage of an easy maze in both directions, polar ones
+    // included, which have more than twice the cells
+    let finished = app.frames_until(60_000, |app| app.state() == GameState::EndGame);
+
+    assert!(finished, "{} bot did not finish", strategy.name());
+    assert!(app.resource::<MazeState>().stopwatch.elapsed_secs() > 0.);
+}
+
+#[test]
+fn optimal_bot_finishes_a_maze() {
+    assert_bot_finishes(Strategy::Optimal, Grid::Square);
+}
+
+#[test]
+fn wall_follower_bot_finishes_a_maze() {
+    assert_bot_finishes(Strategy::WallFollower, Grid::Square);
+}
+
+#[test]
+fn optimal_bot_finishes_a_hex_maze() {
+    assert_bot_finishes(Strategy::Optimal, Grid::Hex);
+}
+
+#[test]
+fn optimal_bot_finishes_a_polar_maze() {
+    assert_bot_finishes(Strategy::Optimal, Grid::Polar);
+}
+
+#[test]
+fn wall_follower_bot_finishes_a_polar_maze() {
+    assert_bot_finishes(Strategy::WallFollower, Grid::Polar);
+}
+
+#[test]
+fn optimal_bot_finishes_a_maze_with_floors() {
+    assert_bot_finishes_on_floors(Strategy::Optimal, Grid::Square, 3);
+}
+
+#[test]
+fn wall_follower_bot_finishes_a_hex_maze_with_floors() {
+    assert_bot_finishes_on_floors(Strategy::WallFollower, Grid::Hex, 2);
+}
+
+#[test]
+fn optimal_bot_finishes_a_shaped_maze() {
+    let mut app = TestApp::new();
+    let ring = Mask::load(Path::new("assets/masks/ring.png")).unwrap();
+    app.app.world.resource_mut::<MazeState>().shape = Some(("ring".to_string(), ring));
+    app.app.world.resource_mut::<Settings>().bot = Some(Strategy::Optimal);
+    app.app.world.resource_mut::<Settings>().ghost = false;
+
+    app.click("Medium");
+    assert!(app
+        .resource::<MazeState>()
+        .path
+        .as_ref()
+        .unwrap()
+        .mask()
+        .is_some());
+
+    let finished = app.frames_until(60_000, |app| app.state() == GameState::EndGame);
+    assert!(finished);
+}
+
+fn assert_bot_finishes_with_doors(strategy: Strategy, grid: Grid, doors: Doors) {
+    let mut app = TestApp::new();
+    app.app.world.resource_mut::<MazeState>().grid = grid;
+    app.app.world.resource_mut::<Settings>().doors = doors;
+    app.app.world.resource_mut::<Settings>().bot = Some(strategy);
+    app.app.world.resource_mut::<Settings>().ghost = false;
+
+    app.click("Easy");
+    let maze = app.resource::<MazeState>().path.clone().unwrap();
+    assert_eq!(maze.placement(), doors);
+
+    let finished = app.frames_until(60_000, |app| app.state() == GameState::EndGame);
+    assert!(finished, "{} bot did not finish", strategy.name());
+}
+
+#[test]
+fn optimal_bot_finishes_with_random_doors() {
+    assert_bot_finishes_with_doors(Strategy::Optimal, Grid::Weave, Doors::Random);
+}
+
+#[test]
+fn wall_follower_bot_finishes_with_the_doors_farthest_apart() {
+    assert_bot_finishes_with_doors(Strategy::WallFollower, Grid::Square, Doors::Farthest);
+}
+
+#[test]
+fn optimal_bot_finishes_a_maze_with_terrain() {
+    let mut app = TestApp::new();
+    app.app.world.resource_mut::<Settings>().terrain = true;
+    app.app.world.resource_mut::<Settings>().bot = Some(Strategy::Optimal);
+    app.app.world.resource_mut::<Settings>().ghost = false;
+
+    app.click("Medium");
+    let maze = app.resource::<MazeState>().path.clone().unwrap();
+    assert!(maze.has_terrain());
+
+    let finished = app.frames_until(60_000, |app| app.state() == GameState::EndGame);
+    assert!(finished);
+}
+
+#[test]
+fn optimal_bot_keeps_clear_of_hazards_off_the_path() {
+    let mut app = TestApp::new();
+    app.app.world.resource_mut::<Settings>().hazards = Hazards::OffPath;
+    app.app.world.resource_mut::<Settings>().bot = Some(Strategy::Optimal);
+    app.app.world.resource_mut::<Settings>().ghost = false;
+
+    app.click("Medium");
+
+    let finished = app.frames_until(60_000, |app| app.state() == GameState::EndGame);
+    assert!(finished);
+    assert_eq!(app.resource::<MazeState>().lives, LIVES);
+}
+
+#[test]
+fn optimal_bot_finishes_a_maze_with_power_ups() {
+    let mut app = TestApp::new();
+    app.app.world.resource_mut::<Settings>().power_ups = true;
+    app.app.world.resource_mut::<Settings>().bot = Some(Strategy::Optimal);
+    app.app.world.resource_mut::<Settings>().ghost = false;
+
+    app.click("Medium");
+    assert!(app
+        .resource::<MazeState>()
+        .path
+        .as_ref()
+        .unwrap()
+        .has_power_ups());
+
+    let finished = app.frames_until(60_000, |app| app.state() == GameState::EndGame);
+    assert!(finished);
+}
+
+#[test]
+fn random_walk_bot_collects_coins() {
+    let mut app = bot_game(Strategy::RandomWalk, Grid::Square);
+    let coins = app.count::<Coin>();
+    let start = app.player();
+
+    app.frames(600);
+    assert_ne!(app.player(), start);
+
+    let collected = app.frames_until(60_000, |app| app.count::<Coin>() < coins);
+    assert!(collected);
+}
+
+#[test]
+fn idle_menu_starts_the_attract_mode() {
+    let mut app = TestApp::new();
+
+    app.frames((DEMO_DELAY / TIMESTEP) as usize + 2);
+    assert_eq!(app.state(), GameState::Demo);
+
+    let start = app.player();
+    app.frames(120);
+    assert_ne!(app.player(), start);
+
+    app.press(KeyCode::Space);
+    app.frames(2);
+    assert_eq!(app.state(), GameState::Menu);
+}
+
+#[test]
+fn optimal_route_passes_every_coin_and_ends_at_the_exit() {
+    for seed in 0..50 {
+        let maze = Maze::from_seed(11, 5, seed);
+        let coins: Vec<_> = (0..11)
+            .flat_map(|y| (0..11).map(move |x| (x, y)))
+            .filter(|&(x, y)| maze.cell(x, y).coin())
+            .collect();
+
+        let route = plan_route(&maze, maze.entrance(), maze.exit(), &coins);
+
+        assert_eq!(route.first(), Some(&maze.entrance()));
+        assert_eq!(route.last(), Some(&maze.exit()));
+        assert!(coins.iter().all(|coin| route.contains(coin)));
+
+        for step in route.windows(2) {
+            assert!(maze
+                .neighbours(step[0].0, step[0].1)
+                .any(|next| next == step[1]));
+        }
+    }
+}
+
+#[test]
+fn optimal_bot_finishes_a_weave_maze() {
+    assert_bot_finishes(Strategy::Optimal, Grid::Weave);
+}
+
+#[test]
+fn wall_follower_bot_finishes_a_weave_maze() {
+    assert_bot_finishes(Strategy::WallFollower, Grid::Weave);
+}
+
+#[test]
+fn route_placement_sets_the_length_of_the_optimal_route() {
+    let lengths = CoinPlacement::ALL.map(|placement| {
+        let (route, solution) = (0..30)
+            .map(|seed| {
+                let maze = Generator::new(Grid::Square, 1, 11, seed)
+                    .with_coin_placement(placement)
+                    .finish(5);
+                let coins: Vec<_> = (0..11)
+                    .flat_map(|y| (0..11).map(move |x| (x, y)))
+                    .filter(|&(x, y)| maze.cell(x, y).coin())
+                    .collect();
+                let route = plan_route(&maze, maze.entrance(), maze.exit(), &coins);
+                let solution = maze.solve(maze.entrance(), maze.exit()).unwrap();
+
+                (route.len() - 1, solution.len() - 1)
+            })
+            .fold((0, 0), |(a, b), (route, solution)| {
+                (a + route, b + solution)
+            });
+
+        (placement, route as f32 / solution as f32)
+    });
+
+    for (placement, ratio) in lengths {
+        if let CoinPlacement::Route(percent) = placement {
+            // Five coins can't always be fetched quickly enough for the shortest routes
+            if percent >= 200 {
+                let target = percent as f32 / 100.;
+                assert!((ratio - target).abs() < 0.25, "{placement:?} gave {ratio}");
+            }
+        }
+    }
+
+    // Spacing coins along the solution makes for less walking than putting them at the
+    // end of the longest detours
+    let ratio = |placement| lengths.iter().find(|(p, _)| *p == placement).unwrap().1;
+    assert!(ratio(CoinPlacement::Spaced) < ratio(CoinPlacement::Detour));
+    assert!(ratio(CoinPlacement::Route(200)) < ratio(CoinPlacement::Route(300)));
+}
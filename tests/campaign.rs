@@ -0,0 +1,138 @@
+mod common;
+
+use bevy::prelude::*;
+use common::TestApp;
+use maze_game::{
+    bot::Strategy,
+    campaign::{self, par_time, CurrentLevel, Progress, LEVELS},
+    game::{Coin, End},
+    maze::Hazards,
+    settings::Settings,
+    utils::vars::{DataDir, GameState, MazeState},
+};
+
+// Starts from the level select with nothing won yet
+fn level_select() -> TestApp {
+    let mut app = TestApp::new();
+    assert_eq!(app.resource::<Progress>().stars, Vec::<u8>::new());
+
+    app.click("Campaign");
+    assert_eq!(app.state(), GameState::LevelSelect);
+
+    app
+}
+
+#[test]
+fn only_the_first_level_is_unlocked_at_the_start() {
+    let mut app = level_select();
+
+    app.click("2. Left and right");
+    assert_eq!(app.state(), GameState::LevelSelect);
+
+    app.click("Back");
+    assert_eq!(app.state(), GameState::Menu);
+}
+
+#[test]
+fn finishing_a_level_rates_it_and_unlocks_the_next() {
+    let mut app = level_select();
+
+    app.click("1. First steps");
+    assert_eq!(app.state(), GameState::Game);
+    assert_eq!(app.resource::<CurrentLevel>().0, 0);
+    assert_eq!(
+        format!("{:?}", app.resource::<MazeState>().path),
+        format!("{:?}", Some(LEVELS[0].maze()))
+    );
+
+    for coin in app.translations::<Coin>() {
+        app.teleport_player(Vec3::new(coin.x, coin.y, 1.));
+        app.frames(1);
+    }
+
+    let end = app.translations::<End>()[0];
+    app.teleport_player(Vec3::new(end.x, end.y, 1.));
+    app.frames(2);
+
+    assert_eq!(app.state(), GameState::EndGame);
+    assert!(app.resource::<Progress>().stars(0) > 0);
+    assert!(app.resource::<Progress>().unlocked(1));
+
+    // Kept in the data directory for next time
+    let data = DataDir(app.data.clone());
+    assert_eq!(
+        Progress::load(&data).stars,
+        app.resource::<Progress>().stars
+    );
+
+    app.click("Levels");
+    assert_eq!(app.state(), GameState::LevelSelect);
+
+    app.click("2. Left and right");
+    assert_eq!(app.state(), GameState::Game);
+    assert_eq!(app.resource::<CurrentLevel>().0, 1);
+}
+
+#[test]
+fn the_main_menu_leaves_the_campaign() {
+    let mut app = level_select();
+
+    app.click("1. First steps");
+    app.app
+        .world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::Menu);
+    app.frames(1);
+
+    assert!(app.app.world.get_resource::<CurrentLevel>().is_none());
+}
+
+#[test]
+fn stars_follow_the_par_time() {
+    let maze = LEVELS[3].maze();
+    let par = par_time(&maze);
+    assert!(par > 0.);
+
+    assert_eq!(campaign::stars(&maze, par), 3);
+    assert_eq!(campaign::stars(&maze, par * 1.75), 2);
+    assert_eq!(campaign::stars(&maze, par * 3.), 1);
+}
+
+#[test]
+fn a_better_rating_is_kept() {
+    let mut progress = Progress::default();
+
+    progress.record(2, 3);
+    progress.record(2, 1);
+
+    assert_eq!(progress.stars(2), 3);
+    assert_eq!(progress.stars(1), 0);
+    assert!(progress.unlocked(3));
+    assert!(!progress.unlocked(2));
+}
+
+#[test]
+fn levels_grow_and_bring_in_features_one_by_one() {
+    assert!(LEVELS.windows(2).all(|pair| pair[0].size <= pair[1].size));
+
+    let first = |feature: fn(usize) -> bool| (0..LEVELS.len()).find(|&i| feature(i));
+    let terrain = first(|i| LEVELS[i].maze().has_terrain()).unwrap();
+    let floors = first(|i| LEVELS[i].maze().floors() > 1).unwrap();
+    let hazards = first(|i| LEVELS[i].maze().hazards() != Hazards::Off).unwrap();
+    let power_ups = first(|i| LEVELS[i].maze().has_power_ups()).unwrap();
+
+    assert!(0 < terrain && terrain < floors && floors < hazards && hazards < power_ups);
+}
+
+#[test]
+fn the_optimal_bot_earns_every_star_on_the_first_level() {
+    let mut app = level_select();
+    app.app.world.resource_mut::<Settings>().bot = Some(Strategy::Optimal);
+    app.app.world.resource_mut::<Settings>().ghost = false;
+
+    app.click("1. First steps");
+    let finished = app.frames_until(10_000, |app| app.state() == GameState::EndGame);
+
+    assert!(finished);
+    assert_eq!(app.resource::<Progress>().stars(0), 3);
+}
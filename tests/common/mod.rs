@@ -0,0 +1,168 @@
+// Each test binary only uses part of the harness
+#![allow(dead_code)]
+
+use bevy::{
+    input::{keyboard::KeyboardInput, ButtonState, InputPlugin},
+    prelude::*,
+    time::TimeUpdateStrategy,
+    utils::Instant,
+};
+use maze_game::{
+    game::{Player, Position},
+    utils::vars::{DataDir, GameState, TIMESTEP},
+    MazeGamePlugin,
+};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+// Runs the game without a window, one fixed tick per frame, driven by simulated input. Each
+// one reads and writes its files in a fresh directory of its own, removed when it's dropped.
+pub struct TestApp {
+    pub app: App,
+    now: Instant,
+    pub data: PathBuf,
+}
+
+static APPS: AtomicUsize = AtomicUsize::new(0);
+
+impl TestApp {
+    pub fn new() -> TestApp {
+        let mut app = App::new();
+        let now = Instant::now();
+        let data = env::temp_dir().join(format!(
+            "maze-game-test-{}-{}",
+            process::id(),
+            APPS.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&data).unwrap();
+
+        app.insert_resource(DataDir(data.clone()))
+            .add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_plugin(InputPlugin)
+            .insert_resource(TimeUpdateStrategy::ManualInstant(now))
+            .add_plugin(MazeGamePlugin);
+
+        let mut test_app = TestApp { app, now, data };
+        test_app.frames(1);
+        test_app
+    }
+
+    pub fn frames(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.now += Duration::from_secs_f32(TIMESTEP);
+            self.app
+                .insert_resource(TimeUpdateStrategy::ManualInstant(self.now));
+            self.app.update();
+        }
+    }
+
+    // Advances until `done` holds, giving up after `max_frames`
+    pub fn frames_until(&mut self, max_frames: usize, done: impl Fn(&mut TestApp) -> bool) -> bool {
+        for _ in 0..max_frames {
+            if done(self) {
+                return true;
+            }
+
+            self.frames(1);
+        }
+
+        done(self)
+    }
+
+    pub fn press(&mut self, key_code: KeyCode) {
+        self.send_key(key_code, ButtonState::Pressed);
+    }
+
+    pub fn release(&mut self, key_code: KeyCode) {
+        self.send_key(key_code, ButtonState::Released);
+    }
+
+    fn send_key(&mut self, key_code: KeyCode, state: ButtonState) {
+        self.app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key_code),
+            state,
+        });
+    }
+
+    // Clicks the button showing `label` and lets the click take effect
+    pub fn click(&mut self, label: &str) {
+        let button = self
+            .app
+            .world
+            .query_filtered::<(Entity, &Children), With<Button>>()
+            .iter(&self.app.world)
+            .find(|(_, children)| {
+                children.iter().any(|child| {
+                    self.app.world.get::<Text>(*child).is_some_and(|text| {
+                        text.sections.iter().any(|section| section.value == label)
+                    })
+                })
+            })
+            .map(|(entity, _)| entity)
+            .unwrap_or_else(|| panic!("no button labelled {label:?}"));
+
+        self.app
+            .world
+            .entity_mut(button)
+            .insert(Interaction::Clicked);
+        self.frames(1);
+        self.app.world.entity_mut(button).insert(Interaction::None);
+        self.frames(1);
+    }
+
+    pub fn state(&self) -> GameState {
+        self.app.world.resource::<State<GameState>>().0
+    }
+
+    pub fn resource<R: Resource>(&self) -> &R {
+        self.app.world.resource::<R>()
+    }
+
+    pub fn count<T: Component>(&mut self) -> usize {
+        self.app
+            .world
+            .query_filtered::<(), With<T>>()
+            .iter(&self.app.world)
+            .count()
+    }
+
+    pub fn translations<T: Component>(&mut self) -> Vec<Vec3> {
+        self.app
+            .world
+            .query_filtered::<&Transform, With<T>>()
+            .iter(&self.app.world)
+            .map(|transform| transform.translation)
+            .collect()
+    }
+
+    pub fn player(&mut self) -> Vec3 {
+        self.app
+            .world
+            .query_filtered::<&Position, With<Player>>()
+            .single(&self.app.world)
+            .current
+    }
+
+    pub fn teleport_player(&mut self, translation: Vec3) {
+        let mut position = self
+            .app
+            .world
+            .query_filtered::<&mut Position, With<Player>>()
+            .single_mut(&mut self.app.world);
+
+        position.previous = translation;
+        position.current = translation;
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.data);
+    }
+}
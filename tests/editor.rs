@@ -0,0 +1,141 @@
+mod common;
+
+use bevy::prelude::*;
+use common::TestApp;
+use maze_game::{
+    editor::{Editor, EditorClick},
+    game::{Coin, End, EndGate, Layout},
+    maze::{Direction, Maze},
+    utils::vars::{GameState, MazeState},
+};
+
+fn open_editor() -> TestApp {
+    let mut app = TestApp::new();
+    app.app.world.insert_resource(Editor::default());
+
+    app.click("Editor");
+    assert_eq!(app.state(), GameState::Editor);
+
+    app
+}
+
+fn maze(app: &TestApp) -> &Maze {
+    &app.resource::<Editor>().maze
+}
+
+// Clicks just inside the `side` of (x, y)
+fn click_side(app: &mut TestApp, (x, y): (usize, usize), side: Direction) {
+    let layout = Layout::new(maze(app));
+    let toward = match side {
+        Direction::North => Vec2::Y,
+        Direction::South => -Vec2::Y,
+        Direction::East => Vec2::X,
+        _ => -Vec2::X,
+    };
+
+    app.app.world.send_event(EditorClick(
+        layout.cell_center(x, y) + toward * layout.coord_size * 0.4,
+    ));
+    app.frames(1);
+}
+
+#[test]
+fn clicking_between_cells_toggles_the_wall() {
+    let mut app = open_editor();
+    assert!(maze(&app).cell(0, 0).is_open(Direction::East));
+
+    click_side(&mut app, (0, 0), Direction::East);
+    assert!(!maze(&app).cell(0, 0).is_open(Direction::East));
+    assert!(!maze(&app).cell(1, 0).is_open(Direction::West));
+
+    click_side(&mut app, (1, 0), Direction::West);
+    assert!(maze(&app).cell(0, 0).is_open(Direction::East));
+
+    // The outside wall can't be taken down
+    click_side(&mut app, (0, 0), Direction::North);
+    assert!(!maze(&app).cell(0, 0).is_open(Direction::North));
+}
+
+#[test]
+fn coins_and_doors_are_placed_with_their_tools() {
+    let mut app = open_editor();
+
+    app.click("Coins");
+    click_side(&mut app, (3, 4), Direction::North);
+    assert!(maze(&app).cell(3, 4).coin());
+    assert_eq!(maze(&app).coins(), 1);
+
+    app.click("Exit");
+    click_side(&mut app, (5, 0), Direction::North);
+    assert_eq!(maze(&app).exit(), (5, 0));
+    assert_eq!(maze(&app).exit_side(), Direction::North);
+
+    // Doors only go in the outside wall
+    app.click("Entrance");
+    click_side(&mut app, (5, 5), Direction::North);
+    assert_ne!(maze(&app).entrance(), (5, 5));
+    assert_eq!(
+        app.resource::<Editor>().status,
+        "Doors go in the outside wall"
+    );
+}
+
+#[test]
+fn an_unsolvable_maze_is_not_play_tested() {
+    let mut app = open_editor();
+    let width = maze(&app).width();
+
+    for y in 0..maze(&app).height() {
+        click_side(&mut app, (width / 2, y), Direction::East);
+    }
+
+    app.click("Check");
+    assert_eq!(
+        app.resource::<Editor>().status,
+        "There is no way from the entrance to the exit"
+    );
+
+    app.click("Play-test");
+    assert_eq!(app.state(), GameState::Editor);
+}
+
+#[test]
+fn play_testing_plays_the_maze_and_returns_to_the_editor() {
+    let mut app = open_editor();
+
+    app.click("Coins");
+    click_side(&mut app, (2, 2), Direction::North);
+    click_side(&mut app, (8, 8), Direction::North);
+    let edited = maze(&app).clone();
+
+    app.click("Play-test");
+    assert_eq!(app.state(), GameState::Game);
+    assert_eq!(app.resource::<MazeState>().path.as_ref(), Some(&edited));
+    assert_eq!(app.count::<Coin>(), 2);
+    assert_eq!(app.resource::<MazeState>().coins, 2);
+
+    for coin in app.translations::<Coin>() {
+        app.teleport_player(Vec3::new(coin.x, coin.y, 1.));
+        app.frames(1);
+    }
+    assert_eq!(app.count::<EndGate>(), 0);
+
+    let end = app.translations::<End>()[0];
+    app.teleport_player(Vec3::new(end.x, end.y, 1.));
+    app.frames(2);
+    assert_eq!(app.state(), GameState::EndGame);
+
+    app.click("Editor");
+    assert_eq!(app.state(), GameState::Editor);
+    assert_eq!(maze(&app), &edited);
+}
+
+#[test]
+fn a_maze_without_coins_has_no_gate() {
+    let mut app = open_editor();
+
+    app.click("Play-test");
+    assert_eq!(app.state(), GameState::Game);
+    assert_eq!(app.count::<Coin>(), 0);
+    assert_eq!(app.count::<EndGate>(), 0);
+}
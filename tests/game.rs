@@ -0,0 +1,783 @@
+mod common;
+
+use bevy::{app::AppExit, prelude::*};
+use common::TestApp;
+use maze_game::{
+    game::{
+        animation_steps, CheckpointTile, Climber, Coin, Collapsing, End, EndGate, Floor,
+        FloorBoard, FloorRoot, Layout, Player, PowerUpTile, PowerUps, Spikes, TerrainTile, Wall,
+    },
+    ghost::Ghost,
+    maze::{ring_len, Direction, Doors, Grid, Hazards, Maze, PowerUp, Terrain},
+    replay::Recording,
+    save::SaveGame,
+    settings::Settings,
+    utils::vars::{
+        DataDir, GameState, MazeState, CRUMBLE_TICKS, LIVES, REPLAY_DIR, SAVE_FILE, TIMESTEP,
+    },
+};
+use std::{fs, time::Duration};
+
+fn start_easy_game() -> TestApp {
+    let mut app = TestApp::new();
+    assert_eq!(app.state(), GameState::Menu);
+
+    app.click("Easy");
+    assert_eq!(app.state(), GameState::Game);
+
+    app
+}
+
+#[test]
+fn choosing_a_difficulty_starts_a_game() {
+    let mut app = start_easy_game();
+
+    assert_eq!(app.resource::<MazeState>().size, 11);
+    assert_eq!(app.count::<Player>(), 1);
+    assert_eq!(app.count::<EndGate>(), 1);
+    assert_eq!(app.count::<Coin>(), app.resource::<MazeState>().coins);
+}
+
+#[test]
+fn closing_the_game_saves_it_in_the_data_directory() {
+    let mut app = start_easy_game();
+    let data = DataDir(app.data.clone());
+    assert!(!SaveGame::exists(&data));
+
+    app.app.world.send_event(AppExit);
+    app.frames(1);
+
+    assert!(app.data.join(SAVE_FILE).exists());
+    let save = SaveGame::load(&data).unwrap();
+    assert_eq!(Some(save.maze), app.resource::<MazeState>().path.clone());
+}
+
+#[test]
+fn holding_a_key_moves_the_player_and_starts_the_clock() {
+    let mut app = start_easy_game();
+    let start = app.player();
+
+    app.frames(10);
+    assert_eq!(app.player(), start);
+    assert_eq!(app.resource::<MazeState>().stopwatch.elapsed_secs(), 0.);
+
+    app.press(KeyCode::Right);
+    app.frames(60);
+    app.release(KeyCode::Right);
+
+    assert!(app.player().x > start.x);
+    assert_eq!(app.player().y, start.y);
+    assert!(app.resource::<MazeState>().stopwatch.elapsed_secs() > 0.);
+}
+
+#[test]
+fn walls_stop_the_player() {
+    let mut app = start_easy_game();
+    let start = app.player();
+
+    // The start pad is closed off to the left
+    app.press(KeyCode::Left);
+    app.frames(120);
+
+    assert!(app.player().x < start.x);
+    let stopped = app.player();
+
+    app.frames(60);
+    assert_eq!(app.player(), stopped);
+}
+
+#[test]
+fn collecting_every_coin_opens_the_gate() {
+    let mut app = start_easy_game();
+    let coins = app.translations::<Coin>();
+    assert!(!coins.is_empty());
+
+    for (i, coin) in coins.iter().enumerate() {
+        assert_eq!(app.count::<EndGate>(), 1);
+
+        app.teleport_player(Vec3::new(coin.x, coin.y, 1.));
+        app.frames(1);
+
+        assert_eq!(app.count::<Coin>(), coins.len() - i - 1);
+    }
+
+    assert_eq!(app.count::<EndGate>(), 0);
+}
+
+#[test]
+fn reaching_the_end_finishes_the_game() {
+    let mut app = start_easy_game();
+
+    app.press(KeyCode::Right);
+    app.frames(30);
+    app.release(KeyCode::Right);
+
+    let end = app.translations::<End>()[0];
+    app.teleport_player(Vec3::new(end.x, end.y, 1.));
+    app.frames(2);
+
+    assert_eq!(app.state(), GameState::EndGame);
+    assert!(app.resource::<MazeState>().stopwatch.paused());
+    assert_eq!(app.count::<Player>(), 0);
+
+    app.click("Solvers");
+    assert_eq!(app.state(), GameState::Visualize);
+
+    app.click("Main menu");
+    assert_eq!(app.state(), GameState::Menu);
+}
+
+#[test]
+fn the_build_animation_can_be_skipped() {
+    let mut app = TestApp::new();
+    app.app.world.resource_mut::<Settings>().intro = true;
+    app.click("Easy");
+    let start = app.player();
+
+    // Nothing moves while the maze is being built
+    app.press(KeyCode::Right);
+    app.frames(10);
+    assert_eq!(app.player(), start);
+    assert_eq!(app.resource::<MazeState>().ticks, 0);
+    app.release(KeyCode::Right);
+
+    app.press(KeyCode::Space);
+    app.frames(1);
+    app.release(KeyCode::Space);
+
+    app.press(KeyCode::Right);
+    app.frames(60);
+
+    assert!(app.player().x > start.x);
+    assert_eq!(app.count::<Coin>(), app.resource::<MazeState>().coins);
+}
+
+#[test]
+fn angled_walls_stop_the_player_in_a_hex_maze() {
+    let mut app = TestApp::new();
+
+    app.click("Grid: Square");
+    app.click("Easy");
+    assert_eq!(app.state(), GameState::Game);
+
+    let maze = app.resource::<MazeState>().path.clone().unwrap();
+    assert_eq!(maze.grid(), Grid::Hex);
+    assert!(app.count::<Wall>() > 0);
+
+    // A cell closed off on both of its upper sides
+    let layout = Layout::new(&maze);
+    let (x, y) = (0..maze.height())
+        .flat_map(|y| (0..maze.width()).map(move |x| (x, y)))
+        .find(|&(x, y)| {
+            let cell = maze.cell(x, y);
+            !cell.is_open(Direction::NorthEast) && !cell.is_open(Direction::NorthWest)
+        })
+        .unwrap();
+    let center = layout.cell_center(x, y);
+
+    app.teleport_player(center.extend(1.));
+    app.press(KeyCode::Up);
+    app.frames(120);
+
+    assert!(app.player().y > center.y);
+    assert!(app.player().y < center.y + layout.coord_size);
+}
+
+#[test]
+fn polar_mazes_are_finished_in_the_middle() {
+    let mut app = TestApp::new();
+
+    app.click("Grid: Square");
+    app.click("Grid: Hex");
+    app.click("Easy");
+
+    let maze = app.resource::<MazeState>().path.clone().unwrap();
+    assert_eq!(maze.grid(), Grid::Polar);
+    assert!(app.count::<Wall>() > 0);
+
+    // The end sits inside the innermost ring, behind the gate
+    let end = app.translations::<End>()[0];
+    assert_eq!(end.truncate(), Vec2::ZERO);
+
+    for coin in app.translations::<Coin>() {
+        app.teleport_player(Vec3::new(coin.x, coin.y, 1.));
+        app.frames(1);
+    }
+    assert_eq!(app.count::<EndGate>(), 0);
+
+    app.teleport_player(Vec3::new(end.x, end.y, 1.));
+    app.frames(2);
+    assert_eq!(app.state(), GameState::EndGame);
+}
+
+fn player_floor(app: &mut TestApp) -> usize {
+    app.app
+        .world
+        .query_filtered::<&Climber, With<Player>>()
+        .single(&app.app.world)
+        .floor
+}
+
+#[test]
+fn stairs_take_the_player_to_the_next_floor() {
+    let mut app = TestApp::new();
+
+    app.click("Floors: 1");
+    app.click("Easy");
+
+    let maze = app.resource::<MazeState>().path.clone().unwrap();
+    assert_eq!(maze.floors(), 2);
+    assert_eq!(player_floor(&mut app), 0);
+
+    let (x, y) = (0..maze.height())
+        .flat_map(|y| (0..maze.width()).map(move |x| (x, y)))
+        .find(|&(x, y)| maze.cell(x, y).is_open(Direction::Up))
+        .unwrap();
+    let stairs = Layout::new(&maze).cell_center(x, y).extend(1.);
+
+    app.teleport_player(stairs);
+    app.frames(1);
+    assert_eq!(player_floor(&mut app), 1);
+
+    // Only the floor the player is on is drawn
+    let shown: Vec<usize> = app
+        .app
+        .world
+        .query::<(&FloorRoot, &Visibility)>()
+        .iter(&app.app.world)
+        .filter(|(_, visibility)| **visibility != Visibility::Hidden)
+        .map(|(root, _)| root.0)
+        .collect();
+    assert_eq!(shown, vec![1]);
+    let board = app
+        .app
+        .world
+        .query_filtered::<&Text, With<FloorBoard>>()
+        .single(&app.app.world);
+    assert_eq!(board.sections[0].value, "Floor 2/2");
+
+    // Arriving on the stairs down doesn't send the player straight back
+    app.frames(10);
+    assert_eq!(player_floor(&mut app), 1);
+
+    // Stepping off onto a cell next to the stairs and back takes the player down again
+    let (up_x, up_y) = maze.passage(x, y, Direction::Up).unwrap();
+    let (off_x, off_y) = maze
+        .neighbours(up_x, up_y)
+        .find(|&(x, y)| maze.floor(y) == 1 && !maze.cell(x, y).is_open(Direction::Down))
+        .unwrap();
+    app.teleport_player(Layout::new(&maze).cell_center(off_x, off_y).extend(1.));
+    app.frames(1);
+    app.teleport_player(stairs);
+    app.frames(1);
+    assert_eq!(player_floor(&mut app), 0);
+}
+
+fn key_towards(offset: Vec2) -> KeyCode {
+    match (
+        offset.x.abs() > offset.y.abs(),
+        offset.x > 0.,
+        offset.y > 0.,
+    ) {
+        (true, true, _) => KeyCode::Right,
+        (true, false, _) => KeyCode::Left,
+        (false, _, true) => KeyCode::Up,
+        (false, _, false) => KeyCode::Down,
+    }
+}
+
+#[test]
+fn weave_crossings_go_over_or_under_by_the_way_in() {
+    let mut app = TestApp::new();
+    let maze = Maze::generate(Grid::Weave, 1, 11, 0, 0);
+
+    let mut maze_state = app.app.world.resource_mut::<MazeState>();
+    maze_state.size = 11;
+    maze_state.grid = Grid::Weave;
+    maze_state.path = Some(maze.clone());
+    maze_state.stopwatch.unpause();
+    app.app
+        .world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::Game);
+    app.frames(1);
+
+    let layout = Layout::new(&maze);
+    let (x, y, tunnel) = (0..maze.height())
+        .flat_map(|y| (0..maze.width()).map(move |x| (x, y)))
+        .find_map(|(x, y)| maze.cell(x, y).tunnel().map(|tunnel| (x, y, tunnel)))
+        .unwrap();
+    let crossing = layout.cell_center(x, y);
+    let bridge = match tunnel[0] {
+        Direction::North => [Direction::East, Direction::West],
+        _ => [Direction::North, Direction::South],
+    };
+
+    for (along, across, under) in [(tunnel, bridge, true), (bridge, tunnel, false)] {
+        let from = maze.neighbour(x, y, along[0].opposite()).unwrap();
+        let to = maze.passage(x, y, along[0]).unwrap();
+        let start = layout.cell_center(from.0, from.1);
+        let end = layout.cell_center(to.0, to.1);
+        let key = key_towards(end - start);
+        let heading = (end - start).normalize();
+
+        app.teleport_player(start.extend(1.));
+        app.press(key);
+        let reached = app.frames_until(300, |app| {
+            (app.player().truncate() - start).dot(heading) >= (crossing - start).length()
+        });
+        app.release(key);
+        assert!(reached);
+        assert_eq!(app.player().z < 0.5, under);
+
+        // Neither the tunnel walls nor the bridge rails can be walked through
+        let (sx, sy) = maze.neighbour(x, y, across[0]).unwrap();
+        let sideways = key_towards(layout.cell_center(sx, sy) - crossing);
+        app.press(sideways);
+        app.frames(60);
+        app.release(sideways);
+        assert!((app.player().truncate() - crossing).length() < layout.coord_size / 2.);
+
+        app.press(key);
+        let through = app.frames_until(300, |app| {
+            (app.player().truncate() - start).dot(heading) >= (end - start).length()
+        });
+        app.release(key);
+        assert!(through);
+        assert_eq!(app.player().z, 1.);
+    }
+}
+
+#[test]
+fn runs_of_one_maze_that_end_together_keep_their_own_replays() {
+    let app = start_easy_game();
+    let data = DataDir(app.data.clone());
+    let mut recording = app.resource::<Recording>().clone();
+
+    for time in [30., 20.] {
+        recording.time = Some(time);
+        recording.save(&data);
+    }
+
+    assert_eq!(fs::read_dir(data.path(REPLAY_DIR)).unwrap().count(), 2);
+    assert_eq!(Recording::load_all(&data).len(), 2);
+}
+
+#[test]
+fn the_best_run_is_looked_for_among_the_replays_of_its_seed() {
+    let app = start_easy_game();
+    let data = DataDir(app.data.clone());
+    let maze = app.resource::<MazeState>().path.clone().unwrap();
+    let mut recording = app.resource::<Recording>().clone();
+    recording.time = Some(30.);
+    recording.save(&data);
+
+    // A faster run of the same maze, filed under another seed, isn't read
+    let mut misfiled = recording.clone();
+    misfiled.time = Some(10.);
+    fs::write(
+        data.path(REPLAY_DIR)
+            .join(format!("{}-0.ron", maze.seed.wrapping_add(1))),
+        ron::to_string(&misfiled).unwrap(),
+    )
+    .unwrap();
+
+    let best = Recording::best(&data, &maze, recording.size, recording.coins).unwrap();
+    assert_eq!(best.time, Some(30.));
+    assert_eq!(Recording::load_all(&data).len(), 2);
+}
+
+#[test]
+fn leaving_a_fast_forwarded_replay_puts_the_timestep_back() {
+    let mut app = start_easy_game();
+
+    let end = app.translations::<End>()[0];
+    app.teleport_player(Vec3::new(end.x, end.y, 1.));
+    app.frames(2);
+    assert_eq!(app.state(), GameState::EndGame);
+
+    app.click("Watch replay");
+    assert_eq!(app.state(), GameState::Replay);
+    app.press(KeyCode::Up);
+    app.frames(1);
+    app.release(KeyCode::Up);
+    let period = app.resource::<FixedTime>().period;
+    assert_eq!(period, Duration::from_secs_f32(TIMESTEP / 2.));
+
+    app.app
+        .world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::EndGame);
+    app.frames(2);
+    let period = app.resource::<FixedTime>().period;
+    assert_eq!(period, Duration::from_secs_f32(TIMESTEP));
+}
+
+#[test]
+fn the_ghost_races_the_run_this_game_saved() {
+    let mut app = TestApp::new();
+    let data = DataDir(app.data.clone());
+
+    app.click("Campaign");
+    app.click("1. First steps");
+    assert_eq!(app.count::<Ghost>(), 0);
+
+    for coin in app.translations::<Coin>() {
+        app.teleport_player(Vec3::new(coin.x, coin.y, 1.));
+        app.frames(1);
+    }
+    let end = app.translations::<End>()[0];
+    app.teleport_player(Vec3::new(end.x, end.y, 1.));
+    app.frames(2);
+    assert_eq!(app.state(), GameState::EndGame);
+
+    let saved = Recording::load_all(&data);
+    assert_eq!(saved.len(), 1);
+
+    app.click("Levels");
+    app.click("1. First steps");
+    let ghosts: Vec<Option<f32>> = app
+        .app
+        .world
+        .query::<&Ghost>()
+        .iter(&app.app.world)
+        .map(|ghost| ghost.recording.time)
+        .collect();
+    assert_eq!(ghosts, [saved[0].time]);
+}
+
+#[test]
+fn a_shape_from_the_menu_shapes_the_maze() {
+    let mut app = TestApp::new();
+
+    app.click("Shape: None");
+    app.click("Easy");
+    assert_eq!(app.state(), GameState::Game);
+
+    let maze = app.resource::<MazeState>().path.clone().unwrap();
+    let mask = maze.mask().unwrap();
+    assert_eq!((mask.width(), mask.height()), (11, 9));
+    assert!(!mask.contains(0, 8));
+    assert_eq!(app.resource::<Recording>().mask, Some(mask));
+
+    // The player starts outside the leftmost column of the shape
+    let entrance = Layout::new(&maze).cell_center(maze.entrance().0, maze.entrance().1);
+    assert!(app.player().x < entrance.x);
+}
+
+#[test]
+fn doors_on_the_top_and_bottom_move_the_pads() {
+    let mut app = TestApp::new();
+    app.app.world.resource_mut::<Settings>().doors =
+        Doors::Sides(Direction::North, Direction::South);
+
+    app.click("Easy");
+    assert_eq!(app.state(), GameState::Game);
+
+    let maze = app.resource::<MazeState>().path.clone().unwrap();
+    assert_eq!(maze.entrance_side(), Direction::North);
+    assert_eq!(maze.exit_side(), Direction::South);
+    assert_eq!(maze.entrance().1, 0);
+    assert_eq!(maze.exit().1, maze.height() - 1);
+    assert_eq!(app.resource::<Recording>().doors, maze.placement());
+
+    // The player starts above the entrance, the end is below the exit and the gate lies
+    // across the bottom of the exit
+    let layout = Layout::new(&maze);
+    let entrance = layout.cell_center(maze.entrance().0, maze.entrance().1);
+    let exit = layout.cell_center(maze.exit().0, maze.exit().1);
+    let start = app.player();
+    let end = app.translations::<End>()[0];
+    let gate = app.translations::<EndGate>()[0];
+
+    assert_eq!(start.x, entrance.x);
+    assert!(start.y > entrance.y);
+    assert_eq!(end.x, exit.x);
+    assert!(end.y < exit.y);
+    assert!(gate.y < exit.y && gate.y > end.y);
+
+    // and walking down leads into the maze
+    app.press(KeyCode::Down);
+    app.frames(120);
+    app.release(KeyCode::Down);
+
+    assert!(app.player().y < start.y);
+    assert_eq!(app.player().x, start.x);
+}
+
+// How far the player gets walking right for `frames` ticks from the start pad, with all of
+// it covered in `terrain`, and how far it goes on after letting go
+fn walk_on(terrain: Terrain, frames: usize) -> (f32, f32) {
+    let mut app = start_easy_game();
+    let start = app.player();
+    app.app.world.spawn((
+        Transform {
+            translation: start,
+            scale: Vec3::new(1000., 1000., 1.),
+            ..default()
+        },
+        TerrainTile(terrain),
+        Floor(0),
+    ));
+
+    app.press(KeyCode::Right);
+    app.frames(frames);
+    app.release(KeyCode::Right);
+    let walked = app.player().x - start.x;
+
+    app.frames(frames);
+    (walked, app.player().x - start.x - walked)
+}
+
+#[test]
+fn terrain_changes_how_the_player_moves() {
+    let (plain, plain_after) = walk_on(Terrain::Plain, 4);
+    let (mud, _) = walk_on(Terrain::Mud, 4);
+    let (boost, _) = walk_on(Terrain::Boost, 4);
+    let (ice, ice_after) = walk_on(Terrain::Ice, 4);
+
+    assert!(plain > 0.);
+    assert!(mud < plain && boost > plain);
+    assert_eq!(ice, plain);
+
+    // Only ice keeps the player going once the key is let go
+    assert_eq!(plain_after, 0.);
+    assert!(ice_after > 0.);
+}
+
+fn start_game_with_hazards() -> TestApp {
+    let mut app = TestApp::new();
+    app.app.world.resource_mut::<Settings>().hazards = Hazards::OffPath;
+    app.app.world.resource_mut::<Settings>().ghost = false;
+
+    app.click("Easy");
+    assert_eq!(app.resource::<MazeState>().lives, LIVES);
+
+    app
+}
+
+// Puts a tile one player wide around `translation` on the bottom floor
+fn spawn_tile(app: &mut TestApp, translation: Vec3, tile: impl Component) {
+    app.app.world.spawn((
+        Transform {
+            translation,
+            scale: Vec3::new(5., 5., 1.),
+            ..default()
+        },
+        Sprite::default(),
+        tile,
+        Floor(0),
+    ));
+}
+
+#[test]
+fn raised_spikes_cost_a_life_and_send_the_player_back_to_the_start() {
+    let mut app = start_game_with_hazards();
+    let start = app.player();
+    let coin = app.translations::<Coin>()[0];
+
+    spawn_tile(&mut app, coin, Spikes { phase: 0 });
+    app.teleport_player(Vec3::new(coin.x, coin.y, 1.));
+    app.frames(1);
+
+    assert_eq!(app.resource::<MazeState>().lives, LIVES - 1);
+    assert_eq!(app.player(), start);
+}
+
+#[test]
+fn collapsing_floors_send_the_player_back_to_the_last_checkpoint() {
+    let mut app = start_game_with_hazards();
+    let coins = app.translations::<Coin>();
+    let (checkpoint, hole) = (coins[0], coins[1]);
+
+    spawn_tile(&mut app, checkpoint, CheckpointTile { x: 0, y: 0 });
+    app.teleport_player(Vec3::new(checkpoint.x, checkpoint.y, 1.));
+    app.frames(1);
+
+    spawn_tile(&mut app, hole, Collapsing::default());
+    app.teleport_player(Vec3::new(hole.x, hole.y, 1.));
+    app.frames(CRUMBLE_TICKS as usize - 1);
+    assert_eq!(app.resource::<MazeState>().lives, LIVES);
+
+    app.frames(2);
+    assert_eq!(app.resource::<MazeState>().lives, LIVES - 1);
+    assert_eq!(app.player().truncate(), checkpoint.truncate());
+}
+
+#[test]
+fn losing_every_life_ends_the_game() {
+    let mut app = start_game_with_hazards();
+    let seed = app.resource::<MazeState>().path.as_ref().unwrap().seed;
+    let start = app.player();
+    let spikes = app
+        .app
+        .world
+        .spawn((
+            Transform {
+                translation: start,
+                scale: Vec3::new(1000., 1000., 1.),
+                ..default()
+            },
+            Sprite::default(),
+            Spikes { phase: 0 },
+            Floor(0),
+        ))
+        .id();
+
+    let over = app.frames_until(10, |app| app.state() == GameState::GameOver);
+    assert!(over);
+    assert_eq!(app.resource::<MazeState>().lives, 0);
+
+    // Trying again plays the same maze with every life back
+    app.app.world.despawn(spikes);
+    app.click("Try again");
+    assert_eq!(app.state(), GameState::Game);
+    assert_eq!(app.resource::<MazeState>().lives, LIVES);
+    assert_eq!(
+        app.resource::<MazeState>().path.as_ref().unwrap().seed,
+        seed
+    );
+}
+
+fn start_game_with_power_ups() -> TestApp {
+    let mut app = TestApp::new();
+    app.app.world.resource_mut::<Settings>().power_ups = true;
+    app.app.world.resource_mut::<Settings>().ghost = false;
+
+    app.click("Easy");
+    assert!(app.count::<PowerUpTile>() > 0);
+
+    app
+}
+
+fn power_ups(app: &mut TestApp) -> PowerUps {
+    *app.app
+        .world
+        .query_filtered::<&PowerUps, With<Player>>()
+        .single(&app.app.world)
+}
+
+fn start_power_up(app: &mut TestApp, power_up: PowerUp) {
+    app.app
+        .world
+        .query_filtered::<&mut PowerUps, With<Player>>()
+        .single_mut(&mut app.app.world)
+        .start(power_up);
+}
+
+#[test]
+fn power_ups_are_picked_up_and_wear_off() {
+    let mut app = start_game_with_power_ups();
+    let tiles = app.count::<PowerUpTile>();
+    let (tile, power_up) = app
+        .app
+        .world
+        .query::<(&Transform, &PowerUpTile)>()
+        .iter(&app.app.world)
+        .map(|(transform, tile)| (transform.translation, tile.0))
+        .next()
+        .unwrap();
+
+    app.teleport_player(Vec3::new(tile.x, tile.y, 1.));
+    app.frames(1);
+
+    assert_eq!(app.count::<PowerUpTile>(), tiles - 1);
+    let remaining = power_ups(&mut app).remaining(power_up);
+    assert!(remaining > 0);
+
+    app.frames(remaining as usize + 1);
+    assert_eq!(power_ups(&mut app).remaining(power_up), 0);
+}
+
+#[test]
+fn phasing_goes_through_walls_inside_the_maze_but_not_the_border() {
+    let mut app = start_game_with_power_ups();
+    let maze = app.resource::<MazeState>().path.clone().unwrap();
+    let layout = Layout::new(&maze);
+    let walled = |inside: bool| {
+        (0..maze.height())
+            .find_map(|y| {
+                let x = (0..maze.width()).find(|&x| {
+                    (x + 1 < maze.width()) == inside
+                        && !maze.cell(x, y).is_open(Direction::East)
+                        && (x, y) != maze.exit()
+                })?;
+                Some(layout.cell_center(x, y).extend(1.))
+            })
+            .unwrap()
+    };
+    // The far side of the wall east of a cell, less the half of the player that fits
+    let beyond = |center: Vec3| center.x + layout.coord_size / 2.;
+
+    for (inside, phasing, gets_through) in [
+        (true, false, false),
+        (true, true, true),
+        (false, true, false),
+    ] {
+        let center = walled(inside);
+        app.teleport_player(center);
+        if phasing {
+            start_power_up(&mut app, PowerUp::Phase);
+        }
+
+        app.press(KeyCode::Right);
+        app.frames(40);
+        app.release(KeyCode::Right);
+        app.frames(1);
+
+        assert_eq!(app.player().x > beyond(center), gets_through);
+    }
+}
+
+#[test]
+fn the_speed_power_up_makes_the_player_faster() {
+    let mut walked = Vec::new();
+
+    for boosted in [false, true] {
+        let mut app = start_game_with_power_ups();
+        let start = app.player();
+        if boosted {
+            start_power_up(&mut app, PowerUp::Speed);
+        }
+
+        app.press(KeyCode::Right);
+        app.frames(4);
+        walked.push(app.player().x - start.x);
+    }
+
+    assert!(walked[0] > 0.);
+    assert!((walked[1] / walked[0] - 1.5).abs() < 0.01);
+}
+
+#[test]
+fn fog_hides_the_far_side_of_the_maze_until_revealed() {
+    let mut app = TestApp::new();
+    app.app.world.resource_mut::<Settings>().fog = true;
+    app.click("Easy");
+    app.frames(1);
+
+    let hidden = |app: &mut TestApp| {
+        app.app
+            .world
+            .query_filtered::<&Visibility, With<Coin>>()
+            .iter(&app.app.world)
+            .filter(|visibility| **visibility == Visibility::Hidden)
+            .count()
+    };
+    assert!(hidden(&mut app) > 0);
+
+    start_power_up(&mut app, PowerUp::Reveal);
+    app.frames(1);
+    assert_eq!(hidden(&mut app), 0);
+}
+
+#[test]
+fn animations_give_every_cell_that_exists_a_step_a_second() {
+    let square = Maze::generate(Grid::Square, 2, 5, 0, 0);
+    assert_eq!(animation_steps(&square, 1., 1.), 50.);
+    assert_eq!(animation_steps(&square, 0.5, 4.), 100.);
+
+    let polar = Maze::generate(Grid::Polar, 1, 5, 0, 0);
+    let cells: usize = (0..5).map(ring_len).sum();
+    assert_eq!(animation_steps(&polar, 1., 1.), cells as f32);
+}
@@ -0,0 +1,142 @@
+use maze_game::import::{self, ImportError};
+use maze_game::maze::{Direction, Doors, Generator, Grid, Maze};
+use proptest::prelude::*;
+use std::path::Path;
+
+const MARGIN: usize = 6;
+
+fn encode(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(pixels)
+        .unwrap();
+
+    bytes
+}
+
+// Draws a square maze in black on white, `pitch` pixels from one wall to the next, with a
+// blank margin around it
+fn draw(maze: &Maze, pitch: usize, thickness: usize) -> Vec<u8> {
+    let width = maze.width() * pitch + thickness + 2 * MARGIN;
+    let height = maze.height() * pitch + thickness + 2 * MARGIN;
+    let mut pixels = vec![255; width * height];
+    let mut fill = |x: usize, y: usize, w: usize, h: usize| {
+        for y in y..y + h {
+            for x in x..x + w {
+                pixels[(y + MARGIN) * width + x + MARGIN] = 0;
+            }
+        }
+    };
+
+    for y in 0..=maze.height() {
+        for x in 0..=maze.width() {
+            fill(x * pitch, y * pitch, thickness, thickness);
+        }
+    }
+
+    for y in 0..maze.height() {
+        for x in 0..maze.width() {
+            let cell = maze.cell(x, y);
+            let (left, top) = (x * pitch, y * pitch);
+
+            if !cell.is_open(Direction::North) {
+                fill(left, top, pitch + thickness, thickness);
+            }
+            if !cell.is_open(Direction::South) {
+                fill(left, top + pitch, pitch + thickness, thickness);
+            }
+            if !cell.is_open(Direction::West) {
+                fill(left, top, thickness, pitch + thickness);
+            }
+            if !cell.is_open(Direction::East) {
+                fill(left + pitch, top, thickness, pitch + thickness);
+            }
+        }
+    }
+
+    encode(width, height, &pixels)
+}
+
+fn doors(maze: &Maze) -> [((usize, usize), Direction); 2] {
+    let mut doors = [
+        (maze.entrance(), maze.entrance_side()),
+        (maze.exit(), maze.exit_side()),
+    ];
+    doors.sort_by_key(|&((x, y), side)| (x, y, side as u8));
+
+    doors
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn drawn_mazes_are_imported_wall_for_wall(size in 1usize..16, thickness in 1usize..5, space in 3usize..12, doors_at in prop::sample::select(Doors::ALL.to_vec()), seed: u64) {
+        let maze = Generator::new(Grid::Square, 1, size, seed).with_doors(doors_at).finish(0);
+        // Random doors can land on the same spot, which leaves only one gap to find
+        prop_assume!((maze.entrance(), maze.entrance_side()) != (maze.exit(), maze.exit_side()));
+        let imported = import::from_png(&draw(&maze, space + thickness, thickness)).unwrap();
+
+        prop_assert_eq!((imported.width(), imported.height()), (size, size));
+        prop_assert_eq!(doors(&imported), doors(&maze));
+        for y in 0..size {
+            for x in 0..size {
+                prop_assert_eq!(imported.cell(x, y), maze.cell(x, y), "cell ({}, {})", x, y);
+            }
+        }
+        prop_assert!(imported.edited());
+    }
+}
+
+#[test]
+fn the_first_gap_down_the_left_and_along_the_top_is_the_entrance() {
+    let maze = Maze::from_text(
+        "
++---+---+---+
+|   |     o E
++   +   +   +
+|         o |
++---+ S +---+
+",
+    )
+    .unwrap();
+    let imported = import::from_png(&draw(&maze, 10, 2)).unwrap();
+
+    assert_eq!(
+        (imported.entrance(), imported.entrance_side()),
+        ((2, 0), Direction::East)
+    );
+    assert_eq!(
+        (imported.exit(), imported.exit_side()),
+        ((1, 1), Direction::South)
+    );
+    assert_eq!(imported.coins(), 0);
+}
+
+#[test]
+fn images_that_are_not_mazes_are_refused() {
+    assert!(matches!(
+        import::from_png(b"not a png"),
+        Err(ImportError::Image(_))
+    ));
+    assert_eq!(
+        import::from_png(&encode(20, 20, &[255; 400])),
+        Err(ImportError::Blank)
+    );
+    assert_eq!(
+        import::from_png(&encode(20, 20, &[0; 400])),
+        Err(ImportError::Grid)
+    );
+    assert!(matches!(
+        import::load(Path::new("no/such/maze.png")),
+        Err(ImportError::Io(_))
+    ));
+
+    let closed = draw(&Maze::closed(4, 3), 8, 1);
+    assert_eq!(import::from_png(&closed), Err(ImportError::Doors(0)));
+}
@@ -0,0 +1,48 @@
+use maze_game::mask::{Mask, MaskError};
+use std::path::Path;
+
+#[test]
+fn text_masks_are_cropped_to_their_cells() {
+    let mask = Mask::from_text("......\n..##..\n..#\n\n").unwrap();
+
+    assert_eq!((mask.width(), mask.height()), (2, 2));
+    assert!(mask.contains(0, 0) && mask.contains(1, 0) && mask.contains(0, 1));
+    assert!(!mask.contains(1, 1));
+}
+
+#[test]
+fn masks_without_cells_are_rejected() {
+    assert_eq!(Mask::from_text(" . \n\n"), Err(MaskError::Empty));
+    assert!(matches!(
+        Mask::from_png(b"not a png"),
+        Err(MaskError::Image(_))
+    ));
+}
+
+#[test]
+fn dark_pixels_of_an_image_are_cells() {
+    let mask = Mask::load(Path::new("assets/masks/ring.png")).unwrap();
+
+    assert_eq!((mask.width(), mask.height()), (30, 30));
+    assert!(mask.contains(0, 15));
+    assert!(!mask.contains(15, 15));
+    assert!(!mask.contains(0, 0));
+}
+
+#[test]
+fn fitting_keeps_the_shape_and_drops_islands() {
+    let mask = Mask::from_text("####..#\n####...\n").unwrap();
+    let fitted = mask.fit(14);
+
+    assert_eq!((fitted.width(), fitted.height()), (14, 4));
+    assert!(fitted.contains(0, 0) && fitted.contains(7, 3));
+    assert!((8..14).all(|x| (0..4).all(|y| !fitted.contains(x, y))));
+}
+
+#[test]
+fn bundled_masks_all_load() {
+    let masks = Mask::load_all();
+    let names: Vec<&str> = masks.iter().map(|(name, _)| name.as_str()).collect();
+
+    assert_eq!(names, ["heart", "ring"]);
+}
@@ -0,0 +1,598 @@
+use maze_game::mask::Mask;
+use maze_game::maze::{
+    Cell, CoinPlacement, Direction, Doors, Generator, Grid, Hazard, Hazards, Maze, MazeTextError,
+    Step, Terrain,
+};
+use proptest::prelude::*;
+use std::collections::{HashSet, VecDeque};
+
+fn reachable(maze: &Maze) -> usize {
+    let (x, y) = maze.entrance();
+    let mut seen = vec![vec![false; maze.width()]; maze.height()];
+    let mut queue = VecDeque::from([(x, y)]);
+    seen[y][x] = true;
+    let mut count = 1;
+
+    while let Some((x, y)) = queue.pop_front() {
+        for dir in maze.cell(x, y).directions() {
+            if let Some((nx, ny)) = maze.neighbour(x, y, dir) {
+                if !seen[ny][nx] {
+                    seen[ny][nx] = true;
+                    count += 1;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+    }
+
+    count
+}
+
+fn grids() -> impl Strategy<Value = Grid> {
+    prop_oneof![
+        Just(Grid::Square),
+        Just(Grid::Hex),
+        Just(Grid::Polar),
+        Just(Grid::Weave)
+    ]
+}
+
+fn masks() -> impl Strategy<Value = Mask> {
+    (1usize..12, 1usize..12).prop_flat_map(|(width, height)| {
+        prop::collection::vec(any::<bool>(), width * height)
+            .prop_map(move |cells| Mask::new(width, height, cells))
+    })
+}
+
+fn doors() -> impl Strategy<Value = Doors> {
+    prop::sample::select(Doors::ALL.to_vec())
+}
+
+fn coin_placements() -> impl Strategy<Value = CoinPlacement> {
+    prop::sample::select(CoinPlacement::ALL.to_vec())
+}
+
+fn cells(maze: &Maze) -> impl Iterator<Item = (usize, usize, Cell)> + '_ {
+    maze.rows()
+        .enumerate()
+        .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, cell)| (x, y, *cell)))
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(200))]
+
+    #[test]
+    fn every_cell_is_reachable(grid in grids(), floors in 1usize..4, size in 1usize..40, coins in 0usize..20, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, coins, seed);
+
+        prop_assert_eq!(maze.height(), size * floors);
+        prop_assert_eq!(maze.floor_height(), size);
+        prop_assert_eq!(reachable(&maze), cells(&maze).count());
+
+        if grid != Grid::Polar {
+            prop_assert_eq!(maze.width(), size);
+            prop_assert!(maze.rows().all(|row| row.len() == size));
+        }
+    }
+
+    #[test]
+    fn polar_rings_split_their_cells_in_two(size in 1usize..40, seed: u64) {
+        let maze = Maze::generate(Grid::Polar, 1, size, 0, seed);
+        let rings: Vec<usize> = maze.rows().map(|row| row.len()).collect();
+
+        prop_assert_eq!(rings.last(), Some(&maze.width()));
+
+        for ring in rings.windows(2) {
+            prop_assert!(ring[1] == ring[0] || ring[1] == ring[0] * 2);
+        }
+    }
+
+    #[test]
+    fn carving_is_symmetric(grid in grids(), floors in 1usize..4, size in 1usize..40, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, 0, seed);
+
+        for (x, y, cell) in cells(&maze) {
+            for dir in cell.directions() {
+                if let Some((nx, ny)) = maze.neighbour(x, y, dir) {
+                    prop_assert!(maze.cell(nx, ny).is_open(dir.opposite()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_entrance_and_exit_are_the_only_openings(grid in grids(), floors in 1usize..4, size in 1usize..40, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, 0, seed);
+        let top = (floors - 1) * size;
+
+        let openings: HashSet<_> = cells(&maze)
+            .flat_map(|(x, y, cell)| cell.directions().map(move |dir| (x, y, dir)))
+            .filter(|(x, y, dir)| maze.neighbour(*x, *y, *dir).is_none())
+            .collect();
+
+        let (entrance, exit) = match grid {
+            Grid::Polar => ((0, size - 1, Direction::South), (0, top, Direction::North)),
+            _ => ((0, size / 2, Direction::West), (size - 1, top + size / 2, Direction::East)),
+        };
+
+        prop_assert_eq!(openings, HashSet::from([entrance, exit]));
+    }
+
+    #[test]
+    fn doors_open_where_they_are_placed(grid in grids(), floors in 1usize..4, size in 2usize..30, doors in doors(), seed: u64) {
+        let maze = Generator::new(grid, floors, size, seed).with_doors(doors).finish(0);
+
+        let openings: HashSet<_> = cells(&maze)
+            .flat_map(|(x, y, cell)| cell.directions().map(move |dir| (x, y, dir)))
+            .filter(|(x, y, dir)| maze.neighbour(*x, *y, *dir).is_none())
+            .collect();
+        let (entrance, exit) = (maze.entrance(), maze.exit());
+
+        prop_assert_eq!(
+            openings,
+            HashSet::from([
+                (entrance.0, entrance.1, maze.entrance_side()),
+                (exit.0, exit.1, maze.exit_side()),
+            ])
+        );
+        prop_assert_eq!(maze.floor(entrance.1), 0);
+        prop_assert_eq!(maze.floor(exit.1), floors - 1);
+        prop_assert_eq!(maze.placement(), doors);
+
+        // Hex mazes only have doors on their left and right, and polar ones on the rim
+        if let Doors::Sides(entrance, exit) = doors {
+            let sides = match grid {
+                Grid::Polar => vec![],
+                Grid::Hex => vec![Direction::West, Direction::East],
+                _ => vec![Direction::West, Direction::East, Direction::North, Direction::South],
+            };
+
+            if sides.contains(&entrance) {
+                prop_assert_eq!(maze.entrance_side(), entrance);
+                prop_assert_eq!(maze.exit_side(), exit);
+            }
+        }
+    }
+
+    #[test]
+    fn farthest_doors_are_at_least_as_far_apart_as_the_default(grid in grids(), size in 2usize..20, seed: u64) {
+        let farthest = Generator::new(grid, 1, size, seed).with_doors(Doors::Farthest).finish(0);
+        let default = Maze::generate(grid, 1, size, 0, seed);
+        let length = |maze: &Maze| maze.solve(maze.entrance(), maze.exit()).unwrap().len();
+
+        prop_assert!(length(&farthest) >= length(&default));
+    }
+
+    #[test]
+    fn maze_is_a_perfect_tree(grid in grids(), floors in 1usize..4, size in 1usize..40, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, 0, seed);
+
+        let passages: usize = cells(&maze)
+            .map(|(x, y, _)| maze.neighbours(x, y).count())
+            .sum();
+
+        // Each passage is counted from both ends, and a spanning tree has one edge less than cells
+        prop_assert_eq!(passages, 2 * (cells(&maze).count() - 1));
+    }
+
+    #[test]
+    fn coins_are_only_placed_in_dead_ends(grid in grids(), floors in 1usize..4, size in 1usize..40, coins in 0usize..60, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, coins, seed);
+
+        let dead_ends = cells(&maze).filter(|(_, _, cell)| cell.directions().count() == 1).count();
+        let placed: Vec<_> = cells(&maze).filter(|(_, _, cell)| cell.coin()).collect();
+
+        prop_assert_eq!(placed.len(), coins.min(dead_ends));
+        prop_assert!(placed.iter().all(|(_, _, cell)| cell.is_dead_end()));
+    }
+
+    #[test]
+    fn every_placement_puts_coins_in_dead_ends(grid in grids(), floors in 1usize..4, size in 1usize..30, coins in 0usize..30, placement in coin_placements(), seed: u64) {
+        let maze = Generator::new(grid, floors, size, seed).with_coin_placement(placement).finish(coins);
+
+        let dead_ends = cells(&maze).filter(|(_, _, cell)| cell.is_dead_end()).count();
+        let placed: Vec<_> = cells(&maze).filter(|(_, _, cell)| cell.coin()).collect();
+
+        prop_assert_eq!(placed.len(), coins.min(dead_ends));
+        prop_assert!(placed.iter().all(|(_, _, cell)| cell.is_dead_end()));
+        prop_assert_eq!(maze.coin_placement(), placement);
+    }
+
+    #[test]
+    fn detour_coins_are_the_farthest_from_the_solution(size in 2usize..30, coins in 1usize..10, seed: u64) {
+        let maze = Generator::new(Grid::Square, 1, size, seed)
+            .with_coin_placement(CoinPlacement::Detour)
+            .finish(coins);
+        let path = maze.solve(maze.entrance(), maze.exit()).unwrap();
+
+        // Walking back from a dead end reaches the solution after its detour
+        let detour = |(x, y): (usize, usize)| maze.solve((x, y), maze.exit()).unwrap().iter().position(|cell| path.contains(cell)).unwrap();
+        let dead_ends: Vec<_> = cells(&maze).filter(|(_, _, cell)| cell.is_dead_end()).collect();
+        let shortest = dead_ends.iter().filter(|(_, _, cell)| cell.coin()).map(|&(x, y, _)| detour((x, y))).min();
+        let longest = dead_ends.iter().filter(|(_, _, cell)| !cell.coin()).map(|&(x, y, _)| detour((x, y))).max();
+
+        if let (Some(shortest), Some(longest)) = (shortest, longest) {
+            prop_assert!(shortest >= longest);
+        }
+    }
+
+    #[test]
+    fn terrain_only_covers_the_floor(grid in grids(), floors in 1usize..4, size in 1usize..30, coins in 0usize..10, seed: u64) {
+        let plain = Generator::new(grid, floors, size, seed).finish(coins);
+        let maze = Generator::new(grid, floors, size, seed).with_terrain(true).finish(coins);
+
+        prop_assert!(!plain.has_terrain());
+
+        for ((x, y, cell), (_, _, before)) in cells(&maze).zip(cells(&plain)) {
+            let mut bare = cell;
+            bare.set_terrain(Terrain::Plain);
+            prop_assert_eq!(bare, before);
+
+            if cell.terrain() != Terrain::Plain {
+                prop_assert!(cell.tunnel().is_none());
+                prop_assert!(!cell.is_open(Direction::Up) && !cell.is_open(Direction::Down));
+                prop_assert!((x, y) != maze.entrance() && (x, y) != maze.exit());
+            }
+        }
+
+        let loaded: Maze = ron::from_str(&ron::to_string(&maze).unwrap()).unwrap();
+        prop_assert!(cells(&loaded).eq(cells(&maze)));
+    }
+
+    #[test]
+    fn hazards_keep_out_of_the_way_unless_asked(grid in grids(), floors in 1usize..4, size in 1usize..30, coins in 0usize..10, seed: u64) {
+        let plain = Generator::new(grid, floors, size, seed).with_terrain(true).finish(coins);
+        prop_assert_eq!(plain.hazards(), Hazards::Off);
+        prop_assert!(cells(&plain).all(|(_, _, cell)| cell.hazard() == Hazard::Safe && !cell.checkpoint()));
+
+        for hazards in [Hazards::OffPath, Hazards::OnPath] {
+            let maze = Generator::new(grid, floors, size, seed)
+                .with_terrain(true)
+                .with_hazards(hazards)
+                .finish(coins);
+
+            for ((x, y, cell), (_, _, before)) in cells(&maze).zip(cells(&plain)) {
+                let mut bare = cell;
+                bare.set_hazard(Hazard::Safe);
+                bare.set_checkpoint(false);
+                prop_assert_eq!(bare, before);
+
+                if cell.hazard() != Hazard::Safe || cell.checkpoint() {
+                    prop_assert!(cell.hazard() == Hazard::Safe || !cell.checkpoint());
+                    prop_assert!(!cell.coin() && cell.terrain() == Terrain::Plain);
+                    prop_assert!(cell.tunnel().is_none());
+                    prop_assert!(!cell.is_open(Direction::Up) && !cell.is_open(Direction::Down));
+                    prop_assert!((x, y) != maze.entrance() && (x, y) != maze.exit());
+                }
+            }
+
+            let path = maze.solve(maze.entrance(), maze.exit()).unwrap();
+            let checkpoints = cells(&maze).filter(|(_, _, cell)| cell.checkpoint()).count();
+            prop_assert!(checkpoints <= 2);
+            prop_assert!(cells(&maze)
+                .filter(|(_, _, cell)| cell.checkpoint())
+                .all(|(x, y, _)| path.contains(&(x, y))));
+
+            // Off the path, every coin and the exit can be reached without crossing a hazard
+            if hazards == Hazards::OffPath {
+                for (x, y, _) in cells(&maze).filter(|(_, _, cell)| cell.coin()) {
+                    let to_coin = maze.solve(maze.entrance(), (x, y)).unwrap();
+                    prop_assert!(to_coin
+                        .iter()
+                        .chain(&path)
+                        .all(|&(x, y)| maze.cell(x, y).hazard() == Hazard::Safe));
+                }
+            }
+
+            let loaded: Maze = ron::from_str(&ron::to_string(&maze).unwrap()).unwrap();
+            prop_assert!(cells(&loaded).eq(cells(&maze)));
+            prop_assert_eq!(loaded.hazards(), hazards);
+        }
+    }
+
+    #[test]
+    fn power_ups_go_where_nothing_else_is(grid in grids(), floors in 1usize..4, size in 1usize..30, coins in 0usize..10, seed: u64) {
+        let generator = || {
+            Generator::new(grid, floors, size, seed)
+                .with_terrain(true)
+                .with_hazards(Hazards::OnPath)
+        };
+        let plain = generator().finish(coins);
+        let maze = generator().with_power_ups(true).finish(coins);
+
+        prop_assert!(!plain.has_power_ups());
+
+        for ((x, y, cell), (_, _, before)) in cells(&maze).zip(cells(&plain)) {
+            let mut bare = cell;
+            bare.set_power_up(None);
+            prop_assert_eq!(bare, before);
+
+            if cell.power_up().is_some() {
+                prop_assert!(!cell.coin() && !cell.checkpoint());
+                prop_assert!(cell.hazard() == Hazard::Safe && cell.terrain() == Terrain::Plain);
+                prop_assert!(cell.tunnel().is_none());
+                prop_assert!(!cell.is_open(Direction::Up) && !cell.is_open(Direction::Down));
+                prop_assert!((x, y) != maze.entrance() && (x, y) != maze.exit());
+            }
+        }
+
+        let loaded: Maze = ron::from_str(&ron::to_string(&maze).unwrap()).unwrap();
+        prop_assert!(cells(&loaded).eq(cells(&maze)));
+    }
+
+    #[test]
+    fn each_floor_gets_its_share_of_coins(grid in grids(), floors in 2usize..4, size in 1usize..20, coins in 0usize..30, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, coins, seed);
+
+        for floor in 0..floors {
+            let on_floor = |(_, y, _): &(usize, usize, Cell)| maze.floor(*y) == floor;
+            let dead_ends = cells(&maze).filter(on_floor).filter(|(_, _, cell)| cell.is_dead_end()).count();
+            let placed = cells(&maze).filter(on_floor).filter(|(_, _, cell)| cell.coin()).count();
+
+            prop_assert!(placed >= dead_ends.min(coins / floors));
+        }
+    }
+
+    #[test]
+    fn one_staircase_joins_each_pair_of_floors(grid in grids(), floors in 1usize..4, size in 1usize..40, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, 0, seed);
+
+        let stairs: Vec<usize> = cells(&maze)
+            .filter(|(_, _, cell)| cell.is_open(Direction::Up))
+            .map(|(_, y, _)| maze.floor(y))
+            .collect();
+
+        prop_assert_eq!(stairs.len(), floors - 1);
+        prop_assert!((0..floors - 1).all(|floor| stairs.contains(&floor)));
+    }
+
+    #[test]
+    fn stairs_are_the_only_way_on_from_a_dead_end(grid in grids(), size in 4usize..40, seed: u64) {
+        let maze = Maze::generate(grid, 3, size, 0, seed);
+
+        for (x, y, cell) in cells(&maze) {
+            let stairs = [Direction::Up, Direction::Down].iter().filter(|dir| cell.is_open(**dir)).count();
+            prop_assert!(stairs <= 1);
+
+            // Unless the only dead ends left on the floor are the doors, or under them
+            if cell.is_open(Direction::Up) && cell.directions().count() > 2 {
+                let spare = cells(&maze).any(|(dx, dy, other)| {
+                    maze.floor(dy) == maze.floor(y)
+                        && other.is_dead_end()
+                        && (dx, dy) != maze.entrance()
+                        && (dx, dy + size) != maze.exit()
+                });
+                prop_assert!(!spare, "{:?}", (x, y));
+            } else if stairs > 0 {
+                prop_assert_eq!(cell.directions().count(), 2, "{:?}", (x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn tunnels_only_run_under_straight_corridors(floors in 1usize..4, size in 1usize..40, seed: u64) {
+        let maze = Maze::generate(Grid::Weave, floors, size, 0, seed);
+
+        for (x, y, cell) in cells(&maze) {
+            if let Some(tunnel) = cell.tunnel() {
+                let ways: Vec<_> = maze.ways(x, y).collect();
+
+                prop_assert_eq!(ways.len(), 2);
+                prop_assert!(ways.iter().all(|way| !tunnel.contains(way)));
+                prop_assert_eq!(cell.directions().count(), 4);
+
+                // Going along the tunnel passes the crossing by
+                for dir in tunnel {
+                    let (nx, ny) = maze.neighbour(x, y, dir.opposite()).unwrap();
+                    prop_assert!(maze.passage(nx, ny, dir).is_some_and(|far| far != (x, y)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shaped_mazes_fill_the_largest_part_of_their_mask(
+        grid in prop_oneof![Just(Grid::Square), Just(Grid::Hex), Just(Grid::Weave)],
+        floors in 1usize..3,
+        mask in masks(),
+        seed: u64,
+    ) {
+        let region = mask.largest_region();
+        prop_assume!(region.is_some());
+        let region = region.unwrap();
+        let maze = Maze::shaped(grid, floors, &mask, 3, seed);
+        let existing = cells(&maze).filter(|(_, _, cell)| cell.exists()).count();
+
+        for (x, y, cell) in cells(&maze) {
+            prop_assert_eq!(cell.exists(), region.contains(x, y % maze.floor_height()));
+
+            if !cell.exists() {
+                prop_assert_eq!(cell.directions().count(), 0);
+            }
+        }
+
+        prop_assert_eq!(reachable(&maze), existing);
+
+        // The entrance and exit are on the far left and right of the shape
+        let (entrance, exit) = (maze.entrance(), maze.exit());
+        prop_assert!(maze.cell(entrance.0, entrance.1).is_open(Direction::West));
+        prop_assert!(maze.cell(exit.0, exit.1).is_open(Direction::East));
+        prop_assert!((0..entrance.0).all(|x| (0..region.height()).all(|y| !region.contains(x, y))));
+        prop_assert!((exit.0 + 1..maze.width()).all(|x| (0..region.height()).all(|y| !region.contains(x, y))));
+
+        let replayed = Generator::replaying(&maze).finish(3);
+        prop_assert!(cells(&replayed).eq(cells(&maze)));
+    }
+
+    #[test]
+    fn a_full_mask_gives_the_unshaped_maze(grid in grids(), size in 1usize..30, seed: u64) {
+        let shaped = Maze::shaped(grid, 1, &Mask::full(size, size), 5, seed);
+        let maze = Maze::generate(grid, 1, size, 5, seed);
+
+        prop_assert!(maze.mask().is_none());
+        prop_assert!(cells(&shaped).eq(cells(&maze)));
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_maze(size in 1usize..40, coins in 0usize..20, seed: u64) {
+        let a = Maze::from_seed(size, coins, seed);
+        let b = Maze::from_seed(size, coins, seed);
+
+        prop_assert!(cells(&a).eq(cells(&b)));
+    }
+
+    #[test]
+    fn solution_walks_from_entrance_to_exit(grid in grids(), floors in 1usize..4, size in 1usize..40, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, 0, seed);
+        let path = maze.solve(maze.entrance(), maze.exit()).unwrap();
+
+        prop_assert_eq!(path.first(), Some(&maze.entrance()));
+        prop_assert_eq!(path.last(), Some(&maze.exit()));
+
+        for step in path.windows(2) {
+            prop_assert!(maze.neighbours(step[0].0, step[0].1).any(|next| next == step[1]));
+        }
+    }
+
+    #[test]
+    fn generator_steps_carve_the_same_maze(grid in grids(), floors in 1usize..4, size in 1usize..40, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, 0, seed);
+        let mut generator = Generator::new(grid, floors, size, seed);
+        let mut carved = 0;
+        let mut visited = 0;
+        let mut backtracked = 0;
+
+        for step in &mut generator {
+            match step {
+                Step::Visit(_) => visited += 1,
+                Step::Carve((x, y), dir) => {
+                    prop_assert!(maze.cell(x, y).is_open(dir));
+                    carved += 1;
+                }
+                Step::Backtrack(_) => backtracked += 1,
+            }
+        }
+
+        let count = cells(&maze).count();
+        prop_assert_eq!(visited, count);
+        prop_assert_eq!(carved, count - 1);
+        prop_assert_eq!(backtracked, count);
+        prop_assert!(cells(&Generator::new(grid, floors, size, seed).finish(0)).eq(cells(&maze)));
+    }
+
+    #[test]
+    fn mazes_survive_a_round_trip(grid in grids(), floors in 1usize..4, size in 1usize..20, coins in 0usize..10, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, coins, seed);
+        let loaded: Maze = ron::from_str(&ron::to_string(&maze).unwrap()).unwrap();
+
+        prop_assert_eq!(loaded.grid(), grid);
+        prop_assert_eq!(loaded.floors(), floors);
+        prop_assert_eq!(loaded.seed, seed);
+        prop_assert!(cells(&loaded).eq(cells(&maze)));
+    }
+
+    #[test]
+    fn square_mazes_survive_a_round_trip_through_text(size in 1usize..20, coins in 0usize..10, doors in prop::sample::select(Doors::ALL.to_vec()), seed: u64) {
+        let maze = Generator::new(Grid::Square, 1, size, seed).with_doors(doors).finish(coins);
+        // Random doors can land on the same spot, which text has no way to show
+        prop_assume!((maze.entrance(), maze.entrance_side()) != (maze.exit(), maze.exit_side()));
+        let loaded = Maze::from_text(&maze.to_text()).unwrap();
+
+        prop_assert_eq!((loaded.width(), loaded.height()), (size, size));
+        prop_assert_eq!(loaded.entrance(), maze.entrance());
+        prop_assert_eq!(loaded.entrance_side(), maze.entrance_side());
+        prop_assert_eq!(loaded.exit(), maze.exit());
+        prop_assert_eq!(loaded.exit_side(), maze.exit_side());
+        prop_assert!(cells(&loaded).eq(cells(&maze)));
+        prop_assert!(loaded.edited());
+    }
+}
+
+#[test]
+fn the_old_constructor_and_rows_still_work() {
+    let maze = Maze::new(7, 3);
+    assert_eq!((maze.grid(), maze.floors()), (Grid::Square, 1));
+    assert_eq!(maze.coins(), 3);
+
+    let path = maze.path();
+    assert_eq!(path.len(), 7);
+    for (y, row) in path.iter().enumerate() {
+        assert_eq!(row.len(), 7);
+        for (x, cell) in row.iter().enumerate() {
+            assert_eq!(*cell, maze.cell(x, y));
+        }
+    }
+}
+
+#[test]
+fn mazes_are_read_from_text() {
+    let maze = Maze::from_text(
+        "
++---+---+---+
+S   |   | o |
++   +   +   +
+|         o E
++---+---+---+
+",
+    )
+    .unwrap();
+
+    assert_eq!((maze.width(), maze.height()), (3, 2));
+    assert_eq!(
+        (maze.entrance(), maze.entrance_side()),
+        ((0, 0), Direction::West)
+    );
+    assert_eq!((maze.exit(), maze.exit_side()), ((2, 1), Direction::East));
+    assert_eq!(maze.coins(), 2);
+    assert!(maze.cell(2, 0).coin() && maze.cell(2, 1).coin());
+    assert!(!maze.cell(0, 0).is_open(Direction::East));
+    assert!(maze.cell(0, 0).is_open(Direction::South));
+    assert_eq!(maze.solve(maze.entrance(), maze.exit()).unwrap().len(), 4);
+}
+
+#[test]
+fn text_that_is_not_a_maze_is_refused() {
+    let no_exit = "+---+---+\nS       |\n+---+---+\n";
+    let door_inside = "+---+---+\nS   E   |\n+---+---+\n";
+    let ragged = "+---+---+\nS      E\n+---+---+--\n";
+
+    assert_eq!(Maze::from_text(""), Err(MazeTextError::Empty));
+    assert_eq!(Maze::from_text(no_exit), Err(MazeTextError::Door('E')));
+    assert_eq!(Maze::from_text(door_inside), Err(MazeTextError::Door('E')));
+    assert_eq!(Maze::from_text(ragged), Err(MazeTextError::Grid));
+}
+
+#[test]
+fn walls_and_doors_can_be_moved_by_hand() {
+    let mut maze = Maze::blank(5);
+    assert!(maze.edited());
+    assert_eq!(maze.solve(maze.entrance(), maze.exit()).unwrap().len(), 5);
+
+    for y in 0..5 {
+        maze.set_wall(2, y, Direction::East, true);
+    }
+    assert!(!maze.cell(3, 0).is_open(Direction::West));
+    assert_eq!(maze.solve(maze.entrance(), maze.exit()), None);
+
+    maze.set_wall(2, 4, Direction::East, false);
+    maze.set_door(true, (4, 0), Direction::North);
+
+    assert!(!maze.cell(4, 2).is_open(Direction::East));
+    assert!(maze.cell(4, 0).is_open(Direction::North));
+    assert_eq!((maze.exit(), maze.exit_side()), ((4, 0), Direction::North));
+    assert!(maze.solve(maze.entrance(), maze.exit()).is_some());
+}
+
+#[test]
+fn weave_mazes_have_crossings() {
+    let crossings: usize = (0..10)
+        .map(|seed| {
+            let maze = Maze::generate(Grid::Weave, 1, 11, 0, seed);
+            cells(&maze)
+                .filter(|(_, _, cell)| cell.tunnel().is_some())
+                .count()
+        })
+        .sum();
+
+    assert!(crossings > 0);
+}
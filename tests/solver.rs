@@ -0,0 +1,56 @@
+use maze_game::maze::{Generator, Grid, Maze, Terrain};
+use maze_game::solver::{Algorithm, SearchStep};
+use proptest::prelude::*;
+
+fn grids() -> impl Strategy<Value = Grid> {
+    prop_oneof![
+        Just(Grid::Square),
+        Just(Grid::Hex),
+        Just(Grid::Polar),
+        Just(Grid::Weave)
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(100))]
+
+    // A perfect maze has exactly one way through, so every solver has to find it
+    #[test]
+    fn every_solver_finds_the_only_path(grid in grids(), floors in 1usize..4, size in 1usize..30, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, 0, seed);
+        let path = maze.solve(maze.entrance(), maze.exit());
+
+        for algorithm in Algorithm::ALL {
+            let search = algorithm.solve(&maze, maze.entrance(), maze.exit());
+
+            prop_assert_eq!(&search.path, &path, "{}", algorithm.name());
+        }
+    }
+
+    // Terrain makes some cells slower than others, but the only way is still the quickest
+    #[test]
+    fn a_star_finds_the_only_path_over_terrain(grid in grids(), floors in 1usize..4, size in 1usize..30, seed: u64) {
+        let maze = Generator::new(grid, floors, size, seed).with_terrain(true).finish(0);
+        let path = maze.solve(maze.entrance(), maze.exit()).unwrap();
+        let search = Algorithm::AStar.solve(&maze, maze.entrance(), maze.exit());
+
+        prop_assert_eq!(search.path.as_ref(), Some(&path));
+
+        let cost = maze.path_cost(&path);
+        let steps = path.len() - 1;
+        prop_assert!(cost >= steps * Terrain::Boost.cost() && cost <= steps * Terrain::Mud.cost());
+    }
+
+    #[test]
+    fn searches_only_visit_cells_they_can_reach(grid in grids(), floors in 1usize..4, size in 1usize..30, seed: u64) {
+        let maze = Maze::generate(grid, floors, size, 0, seed);
+
+        for algorithm in [Algorithm::BreadthFirst, Algorithm::DepthFirst, Algorithm::AStar] {
+            let search = algorithm.solve(&maze, maze.entrance(), maze.exit());
+
+            prop_assert!(search.visited() <= maze.rows().map(|row| row.len()).sum::<usize>());
+            prop_assert_eq!(search.steps.first(), Some(&SearchStep::Frontier(maze.entrance())));
+            prop_assert_eq!(search.steps.last(), Some(&SearchStep::Visit(maze.exit())));
+        }
+    }
+}
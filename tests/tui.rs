@@ -0,0 +1,97 @@
+use maze_game::game::coins_for;
+use maze_game::maze::{Direction, Maze};
+use maze_game::settings::Settings;
+use maze_game::tui::Run;
+use std::time::Duration;
+
+fn run() -> Run {
+    let maze = Maze::from_text(
+        "
++---+---+---+
+S   |   | o |
++   +   +   +
+|         o E
++---+---+---+
+",
+    )
+    .unwrap();
+
+    Run::new(maze)
+}
+
+#[test]
+fn the_maze_is_drawn_with_box_drawing_characters() {
+    let mut run = run();
+
+    assert_eq!(
+        run.render(),
+        [
+            "╶───┬───┬───┐",
+            "  @ │   │ o │",
+            "╷   ╵   ╵   │",
+            "│         o ║",
+            "└───────────┘",
+        ]
+    );
+
+    run.hint = true;
+    assert_eq!(run.render()[3], "│ ·   ·   o ║");
+}
+
+#[test]
+fn walls_block_steps_and_the_clock_waits_for_the_first_one() {
+    let mut run = run();
+
+    assert!(!run.step(Direction::East));
+    assert!(!run.step(Direction::West));
+    assert_eq!(run.position(), (0, 0));
+    assert_eq!(run.moves(), 0);
+    assert_eq!(run.elapsed(), Duration::ZERO);
+
+    assert!(run.step(Direction::South));
+    assert_eq!(run.position(), (0, 1));
+    assert!(!run.finished());
+}
+
+#[test]
+fn the_gate_opens_once_every_coin_is_taken() {
+    let mut run = run();
+
+    for direction in [Direction::South, Direction::East, Direction::East] {
+        assert!(run.step(direction));
+    }
+    assert_eq!(run.coins_left(), [(2, 0)]);
+    assert!(!run.step(Direction::East));
+    assert!(run.stars().is_none());
+
+    assert!(run.step(Direction::North));
+    assert!(run.step(Direction::South));
+    assert!(run.gate_open());
+    assert_eq!(run.render()[3], "│         @  ");
+
+    assert!(run.step(Direction::East));
+    assert!(run.finished());
+    assert_eq!(run.moves(), 6);
+    assert_eq!(run.stars(), Some(3));
+    assert!(!run.step(Direction::West));
+}
+
+#[test]
+fn the_hint_walks_past_every_coin_to_the_exit() {
+    let run = run();
+    let path = run.hint_path();
+
+    assert_eq!(path.first(), Some(&(0, 0)));
+    assert_eq!(path.last(), Some(&(2, 1)));
+    assert!(run.coins_left().iter().all(|coin| path.contains(coin)));
+}
+
+#[test]
+fn new_runs_have_as_many_coins_as_the_game_gives() {
+    let run = Run::generate(11, 7, &Settings::default());
+
+    assert_eq!((run.maze().width(), run.maze().height()), (11, 11));
+    assert_eq!(run.coins_left().len(), coins_for(11));
+    assert_eq!(run.position(), run.maze().entrance());
+    assert_eq!(run.render().len(), 23);
+}